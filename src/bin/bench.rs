@@ -85,7 +85,7 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
     match err {
         None => Ok(None),
         Some(err) => {
-            Err(match err.exit.as_ref().and_then(|c| c.code()) {
+            Err(match err.code() {
                 Some(i) => CliError::new("", i),
                 None => CliError::from_error(Human(err), 101)
             })
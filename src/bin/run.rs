@@ -82,7 +82,7 @@ pub fn execute(options: Options, config: &Config) -> CliResult<Option<()>> {
     match err {
         None => Ok(None),
         Some(err) => {
-            Err(match err.exit.as_ref().and_then(|e| e.code()) {
+            Err(match err.code() {
                 Some(i) => CliError::from_error(err, i),
                 None => CliError::from_error(err, 101),
             })
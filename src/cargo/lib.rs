@@ -4,6 +4,7 @@
 #![cfg_attr(test, deny(warnings))]
 
 #[cfg(test)] extern crate hamcrest;
+#[cfg(test)] extern crate tempdir;
 #[macro_use] extern crate log;
 extern crate "rustc-serialize" as rustc_serialize;
 extern crate curl;
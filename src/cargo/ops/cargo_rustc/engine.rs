@@ -37,12 +37,12 @@ pub struct CommandPrototype {
 impl CommandPrototype {
     pub fn new(ty: CommandType) -> CargoResult<CommandPrototype> {
         Ok(CommandPrototype {
-            builder: try!(match ty {
+            builder: match ty {
                 CommandType::Rustc => process("rustc"),
                 CommandType::Rustdoc => process("rustdoc"),
                 CommandType::Target(ref s) |
                 CommandType::Host(ref s) => process(s),
-            }),
+            },
             ty: ty,
         })
     }
@@ -70,8 +70,9 @@ impl CommandPrototype {
         self
     }
 
+    pub fn get_program(&self) -> &OsString { self.builder.get_program() }
     pub fn get_args(&self) -> &[OsString] { self.builder.get_args() }
-    pub fn get_cwd(&self) -> &Path { self.builder.get_cwd() }
+    pub fn get_cwd(&self) -> Option<&Path> { self.builder.get_cwd() }
 
     pub fn get_env(&self, var: &str) -> Option<OsString> {
         self.builder.get_env(var)
@@ -0,0 +1,91 @@
+//! Removal of terminal escape sequences from captured output.
+//!
+//! Tools which believe they're writing to a terminal litter their output with
+//! colors and cursor movement, which gets in the way of anything trying to
+//! parse that output afterwards.
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+
+/// Returns a copy of `bytes` with all ANSI escape sequences removed.
+///
+/// This covers CSI sequences (colors, cursor movement, erasing), OSC
+/// sequences (such as setting the window title), and two byte escapes. A
+/// sequence which is cut off or contains a byte it can't contain is dropped
+/// up to that byte, and everything after it is kept.
+pub fn strip_ansi(bytes: &[u8]) -> Vec<u8> {
+    let mut ret = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != ESC {
+            ret.push(bytes[i]);
+            i += 1;
+            continue
+        }
+        i += 1;
+        match bytes.get(i) {
+            Some(&b'[') => {
+                i += 1;
+                // Parameter and intermediate bytes, followed by a final byte.
+                while i < bytes.len() {
+                    match bytes[i] {
+                        0x20...0x3f => i += 1,
+                        0x40...0x7e => { i += 1; break }
+                        _ => break,
+                    }
+                }
+            }
+            Some(&b']') => {
+                i += 1;
+                // Terminated by either BEL or `ESC \`.
+                while i < bytes.len() {
+                    if bytes[i] == BEL {
+                        i += 1;
+                        break
+                    }
+                    if bytes[i] == ESC {
+                        if bytes.get(i + 1) == Some(&b'\\') { i += 2; }
+                        break
+                    }
+                    i += 1;
+                }
+            }
+            Some(&b) if b >= 0x20 && b <= 0x7e => i += 1,
+            _ => {}
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::strip_ansi;
+
+    #[test]
+    fn removes_colors() {
+        let input = b"\x1b[1m\x1b[31merror\x1b[0m: expected `;`\n";
+        assert_eq!(strip_ansi(input), b"error: expected `;`\n".to_vec());
+        let input = b"\x1b[38;5;208mwarning\x1b[m: unused";
+        assert_eq!(strip_ansi(input), b"warning: unused".to_vec());
+    }
+
+    #[test]
+    fn removes_cursor_movement_and_titles() {
+        let input = b"a\x1b[2K\x1b[1Gb\x1b]0;title\x07c\x1b]2;t\x1b\\d\x1b7e";
+        assert_eq!(strip_ansi(input), b"abcde".to_vec());
+    }
+
+    #[test]
+    fn leaves_plain_text_alone() {
+        let input = "plain [text] with ünïcode\n".as_bytes();
+        assert_eq!(strip_ansi(input), input.to_vec());
+    }
+
+    #[test]
+    fn survives_malformed_sequences() {
+        assert_eq!(strip_ansi(b"ok\x1b[31"), b"ok".to_vec());
+        assert_eq!(strip_ansi(b"ok\x1b"), b"ok".to_vec());
+        assert_eq!(strip_ansi(b"a\x1b[3\nb"), b"a\nb".to_vec());
+        assert_eq!(strip_ansi(b"a\x1b]0;never ends"), b"a".to_vec());
+    }
+}
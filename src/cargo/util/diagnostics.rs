@@ -0,0 +1,89 @@
+//! Parsing of the diagnostics `rustc --error-format=json` prints to stderr.
+
+use std::str;
+
+use rustc_serialize::json;
+
+/// A diagnostic, such as an error or a warning, reported by the compiler.
+#[derive(RustcDecodable, Clone, PartialEq, Debug)]
+pub struct JsonMessage {
+    /// How severe the diagnostic is, such as "error", "warning" or "note".
+    pub level: String,
+    pub message: String,
+    /// The places in the source the diagnostic refers to, if any.
+    pub spans: Vec<JsonSpan>,
+}
+
+/// A region of a source file a `JsonMessage` refers to.
+///
+/// Lines and columns start at one.
+#[derive(RustcDecodable, Clone, PartialEq, Debug)]
+pub struct JsonSpan {
+    pub file_name: String,
+    pub line_start: usize,
+    pub line_end: usize,
+    pub column_start: usize,
+    pub column_end: usize,
+}
+
+/// Parses each line of the captured `stderr` of the compiler as a
+/// `JsonMessage`.
+///
+/// Lines which aren't JSON, or aren't diagnostics, are skipped, as are lines
+/// which aren't valid UTF-8. Fields of the diagnostics other than those of
+/// `JsonMessage` are ignored.
+pub fn parse_json_messages(stderr: &[u8]) -> Vec<JsonMessage> {
+    stderr.split(|b| *b == b'\n').filter_map(parse_json_message).collect()
+}
+
+/// Parses a single line the compiler printed to stderr as a `JsonMessage`,
+/// returning `None` for anything which isn't a diagnostic.
+pub fn parse_json_message(line: &[u8]) -> Option<JsonMessage> {
+    let line = match str::from_utf8(line) {
+        Ok(line) => line.trim(),
+        Err(..) => return None,
+    };
+    if !line.starts_with("{") { return None }
+    json::decode(line).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_json_messages, JsonSpan};
+
+    #[test]
+    fn skips_lines_which_are_not_diagnostics() {
+        let stderr = b"\
+warning: plain text from a build script
+{\"message\":\"unused variable: `x`\",\"code\":null,\"level\":\"warning\",\
+\"spans\":[{\"file_name\":\"src/lib.rs\",\"byte_start\":4,\"byte_end\":5,\
+\"line_start\":1,\"line_end\":1,\"column_start\":5,\"column_end\":6}]}
+{\"not\":\"a diagnostic\"}
+{ this isn't json
+{\"message\":\"aborting due to previous error\",\"level\":\"error\",\
+\"spans\":[]}\r
+";
+        let messages = parse_json_messages(stderr);
+        assert_eq!(messages.len(), 2);
+
+        assert_eq!(messages[0].level, "warning");
+        assert_eq!(messages[0].message, "unused variable: `x`");
+        assert_eq!(messages[0].spans, [JsonSpan {
+            file_name: "src/lib.rs".to_string(),
+            line_start: 1,
+            line_end: 1,
+            column_start: 5,
+            column_end: 6,
+        }]);
+
+        assert_eq!(messages[1].level, "error");
+        assert_eq!(messages[1].message, "aborting due to previous error");
+        assert!(messages[1].spans.is_empty());
+    }
+
+    #[test]
+    fn empty_output_has_no_messages() {
+        assert!(parse_json_messages(b"").is_empty());
+        assert!(parse_json_messages(b"\n\n").is_empty());
+    }
+}
@@ -1,11 +1,14 @@
 use std::error::{FromError, Error};
-use std::ffi;
+use std::ffi::{self, OsString};
 use std::fmt;
 use std::io;
+use std::path::PathBuf;
 use std::process::{Output, ExitStatus};
 use std::str;
+use std::time::Duration;
 
 use semver;
+use rustc_serialize::base64::{ToBase64, STANDARD};
 use rustc_serialize::json;
 
 use curl;
@@ -113,13 +116,198 @@ pub struct ProcessError {
     pub desc: String,
     pub exit: Option<ExitStatus>,
     pub output: Option<Output>,
-    cause: Option<io::Error>,
+    /// How long the process ran for, if it was spawned successfully.
+    pub duration: Option<Duration>,
+    /// If the captured output was cut short, how many bytes were dropped.
+    pub truncated: Option<usize>,
+    /// Whether the process was killed because it was cancelled.
+    pub cancelled: bool,
+    /// The command which failed, if the error came from a `ProcessBuilder`.
+    pub program: Option<OsString>,
+    pub args: Vec<OsString>,
+    pub cwd: Option<PathBuf>,
+    cause: Option<Box<Error + Send>>,
+}
+
+/// The command behind a `ProcessError` whose message only describes it.
+struct CommandLine {
+    line: String,
+    cause: Option<Box<Error + Send>>,
+}
+
+/// The form in which `ProcessError::to_json` describes a failed process.
+#[derive(RustcEncodable, RustcDecodable, PartialEq, Debug)]
+pub struct SerializedProcessError {
+    pub message: String,
+    pub program: Option<String>,
+    pub args: Vec<String>,
+    pub cwd: Option<String>,
+    pub code: Option<i32>,
+    pub signal: Option<i32>,
+    pub stdout: Option<SerializedOutput>,
+    pub stderr: Option<SerializedOutput>,
+    pub truncated: Option<usize>,
+}
+
+/// A captured output stream, which is base64 encoded if it isn't valid UTF-8.
+#[derive(RustcEncodable, RustcDecodable, PartialEq, Debug)]
+pub struct SerializedOutput {
+    pub data: String,
+    pub base64: bool,
+}
+
+impl SerializedOutput {
+    fn new(bytes: &[u8]) -> SerializedOutput {
+        match str::from_utf8(bytes) {
+            Ok(s) => SerializedOutput { data: s.to_string(), base64: false },
+            Err(..) => SerializedOutput {
+                data: bytes.to_base64(STANDARD),
+                base64: true,
+            },
+        }
+    }
+}
+
+impl ProcessError {
+    /// Records the command line behind an error whose message only describes
+    /// the process, which is then reported as the cause of the error.
+    pub fn set_command_line(&mut self, line: String) {
+        let cause = self.cause.take();
+        self.cause = Some(Box::new(CommandLine { line: line, cause: cause }));
+    }
+
+    /// Returns whether the process failed to start at all, for example because
+    /// the program doesn't exist, as opposed to running and then failing.
+    pub fn did_not_start(&self) -> bool {
+        self.exit.is_none()
+    }
+
+    /// Returns the exit code of the process, if it ran to completion.
+    ///
+    /// This is `None` if the process could not be spawned, or if it was
+    /// terminated by a signal.
+    pub fn code(&self) -> Option<i32> {
+        self.exit.as_ref().and_then(|s| s.code())
+    }
+
+    /// Returns the signal which terminated the process, if any.
+    #[cfg(unix)]
+    pub fn signal(&self) -> Option<i32> {
+        self.exit.as_ref().and_then(exit_signal)
+    }
+
+    /// Returns the exact bytes the process wrote to stdout, if its output was
+    /// captured.
+    ///
+    /// The error message renders output lossily, so this is the way to get at
+    /// output which isn't valid unicode.
+    pub fn raw_stdout(&self) -> Option<&[u8]> {
+        self.output.as_ref().map(|o| &o.stdout[..])
+    }
+
+    /// Returns the exact bytes the process wrote to stderr, if its output was
+    /// captured.
+    pub fn raw_stderr(&self) -> Option<&[u8]> {
+        self.output.as_ref().map(|o| &o.stderr[..])
+    }
+
+    /// Describes the error as a JSON object, for tools which want to inspect
+    /// failures without parsing the error message.
+    ///
+    /// The object is a `SerializedProcessError`. Arguments and paths which
+    /// aren't valid unicode are rendered lossily.
+    pub fn to_json(&self) -> String {
+        let output = self.output.as_ref();
+        let serialized = SerializedProcessError {
+            message: self.desc.clone(),
+            program: self.program.as_ref().map(|p| {
+                p.to_string_lossy().into_owned()
+            }),
+            args: self.args.iter().map(|a| {
+                a.to_string_lossy().into_owned()
+            }).collect(),
+            cwd: self.cwd.as_ref().map(|p| p.display().to_string()),
+            code: self.code(),
+            signal: self.exit.as_ref().and_then(exit_signal),
+            stdout: output.map(|o| SerializedOutput::new(&o.stdout)),
+            stderr: output.map(|o| SerializedOutput::new(&o.stderr)),
+            truncated: self.truncated,
+        };
+        json::encode(&serialized).unwrap()
+    }
+}
+
+/// Returns the signal which terminated the process that exited with `status`,
+/// if any. This is always `None` on Windows.
+#[cfg(unix)]
+pub fn exit_signal(status: &ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(windows)]
+pub fn exit_signal(_status: &ExitStatus) -> Option<i32> { None }
+
+fn describe_signal(sig: i32) -> String {
+    match signal_name(sig) {
+        Some(name) => format!("terminated by signal {}, {}", sig, name),
+        None => format!("terminated by signal {}", sig),
+    }
+}
+
+#[cfg(unix)]
+fn signal_name(sig: i32) -> Option<&'static str> {
+    use libc;
+    let name = match sig {
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGINT => "SIGINT",
+        libc::SIGQUIT => "SIGQUIT",
+        libc::SIGILL => "SIGILL",
+        libc::SIGTRAP => "SIGTRAP",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGPIPE => "SIGPIPE",
+        libc::SIGALRM => "SIGALRM",
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGXCPU => "SIGXCPU",
+        libc::SIGXFSZ => "SIGXFSZ",
+        _ => return None,
+    };
+    Some(name)
 }
 
+#[cfg(windows)]
+fn signal_name(_sig: i32) -> Option<&'static str> { None }
+
 impl Error for ProcessError {
     fn description(&self) -> &str { &self.desc }
     fn cause(&self) -> Option<&Error> {
-        self.cause.as_ref().map(|s| s as &Error)
+        self.cause.as_ref().map(|c| {
+            let e: &Error = &**c; e
+        })
+    }
+}
+
+impl Error for CommandLine {
+    fn description(&self) -> &str { &self.line }
+    fn cause(&self) -> Option<&Error> {
+        self.cause.as_ref().map(|c| {
+            let e: &Error = &**c; e
+        })
+    }
+}
+
+impl fmt::Display for CommandLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.line, f)
+    }
+}
+impl fmt::Debug for CommandLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
     }
 }
 
@@ -286,12 +474,29 @@ impl CargoError for ffi::NulError {}
 // =============================================================================
 // Construction helpers
 
+/// How many lines from the end of stderr the message of a `ProcessError`
+/// includes by default.
+pub const STDERR_TAIL_LINES: usize = 20;
+
 pub fn process_error(msg: &str,
                      cause: Option<io::Error>,
                      status: Option<&ExitStatus>,
                      output: Option<&Output>) -> ProcessError {
+    process_error_with_tail(msg, cause, status, output, STDERR_TAIL_LINES)
+}
+
+/// Like `process_error`, but includes up to `tail` lines from the end of
+/// stderr in the message instead of the default number.
+pub fn process_error_with_tail(msg: &str,
+                               cause: Option<io::Error>,
+                               status: Option<&ExitStatus>,
+                               output: Option<&Output>,
+                               tail: usize) -> ProcessError {
     let exit = match status {
-        Some(s) => s.to_string(),
+        Some(s) => match exit_signal(s) {
+            Some(sig) => describe_signal(sig),
+            None => s.to_string(),
+        },
         None => "never executed".to_string(),
     };
     let mut desc = format!("{} ({})", &msg, exit);
@@ -304,12 +509,18 @@ pub fn process_error(msg: &str,
             }
             Ok(..) | Err(..) => {}
         }
-        match str::from_utf8(&out.stderr) {
-            Ok(s) if s.trim().len() > 0 => {
-                desc.push_str("\n--- stderr\n");
-                desc.push_str(s);
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        if stderr.trim().len() > 0 {
+            desc.push_str("\n--- stderr\n");
+            let lines = stderr.lines().collect::<Vec<_>>();
+            if lines.len() <= tail {
+                desc.push_str(&stderr);
+            } else {
+                let omitted = lines.len() - tail;
+                desc.push_str(&format!("({} earlier lines omitted)\n", omitted));
+                desc.push_str(&lines[omitted..].connect("\n"));
+                desc.push('\n');
             }
-            Ok(..) | Err(..) => {}
         }
     }
 
@@ -317,7 +528,13 @@ pub fn process_error(msg: &str,
         desc: desc,
         exit: status.map(|a| a.clone()),
         output: output.map(|a| a.clone()),
-        cause: cause,
+        duration: None,
+        truncated: None,
+        cancelled: false,
+        program: None,
+        args: Vec::new(),
+        cwd: None,
+        cause: cause.map(|e| Box::new(e) as Box<Error + Send>),
     }
 }
 
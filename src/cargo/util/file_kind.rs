@@ -0,0 +1,103 @@
+//! Telling scripts apart from binaries, to explain why a program won't run.
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str;
+
+/// How many bytes from the start of a file are looked at, which bounds the
+/// length of a shebang line that is understood.
+const HEADER_LEN: u64 = 256;
+
+/// What sort of program a file contains, as told by its first few bytes.
+#[derive(Clone, PartialEq, Debug)]
+pub enum FileKind {
+    /// A script starting with a `#!` line naming its interpreter, along with
+    /// the argument to the interpreter given on that line, if any.
+    Script { interpreter: PathBuf, arg: Option<String> },
+    /// An ELF, Mach-O or PE executable.
+    Binary,
+    /// Anything else, including empty files.
+    Unknown,
+}
+
+/// Reads the start of the file at `path` to find out what kind of file it is.
+pub fn file_kind(path: &Path) -> io::Result<FileKind> {
+    let mut header = Vec::new();
+    let file = try!(File::open(path));
+    try!(file.take(HEADER_LEN).read_to_end(&mut header));
+    Ok(classify(&header))
+}
+
+/// Tells what kind of file starts with `header`.
+pub fn classify(header: &[u8]) -> FileKind {
+    if header.starts_with(b"#!") {
+        return parse_shebang(&header[2..])
+    }
+    let magic: &[&[u8]] = &[
+        b"\x7fELF",
+        b"\xfe\xed\xfa\xce", b"\xfe\xed\xfa\xcf",
+        b"\xce\xfa\xed\xfe", b"\xcf\xfa\xed\xfe",
+        b"\xca\xfe\xba\xbe",
+        b"MZ",
+    ];
+    if magic.iter().any(|m| header.starts_with(m)) {
+        FileKind::Binary
+    } else {
+        FileKind::Unknown
+    }
+}
+
+fn parse_shebang(line: &[u8]) -> FileKind {
+    let line = match line.iter().position(|b| *b == b'\n') {
+        Some(end) => &line[..end],
+        None => line,
+    };
+    let line = match str::from_utf8(line) {
+        Ok(line) => line.trim(),
+        Err(..) => return FileKind::Unknown,
+    };
+    if line.is_empty() { return FileKind::Unknown }
+    let (interpreter, arg) = match line.find(|c: char| c.is_whitespace()) {
+        Some(i) => (&line[..i], Some(line[i..].trim().to_string())),
+        None => (line, None),
+    };
+    FileKind::Script { interpreter: PathBuf::new(interpreter), arg: arg }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{classify, FileKind};
+
+    #[test]
+    fn scripts_name_their_interpreter() {
+        assert_eq!(classify(b"#!/bin/sh\necho hi\n"), FileKind::Script {
+            interpreter: PathBuf::new("/bin/sh"),
+            arg: None,
+        });
+        let env = b"#! /usr/bin/env  python3 \r\n";
+        assert_eq!(classify(env), FileKind::Script {
+            interpreter: PathBuf::new("/usr/bin/env"),
+            arg: Some("python3".to_string()),
+        });
+        assert_eq!(classify(b"#!"), FileKind::Unknown);
+        assert_eq!(classify(b"#!\n/bin/sh"), FileKind::Unknown);
+    }
+
+    #[test]
+    fn binaries_are_recognized() {
+        assert_eq!(classify(b"\x7fELF\x02\x01\x01\0"), FileKind::Binary);
+        assert_eq!(classify(b"\xcf\xfa\xed\xfe\x07\0\0\x01"), FileKind::Binary);
+        assert_eq!(classify(b"MZ\x90\0"), FileKind::Binary);
+    }
+
+    #[test]
+    fn short_files_are_unknown() {
+        assert_eq!(classify(b""), FileKind::Unknown);
+        assert_eq!(classify(b"#"), FileKind::Unknown);
+        assert_eq!(classify(b"plain text"), FileKind::Unknown);
+    }
+}
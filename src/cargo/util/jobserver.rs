@@ -0,0 +1,263 @@
+//! A minimal implementation of the GNU make jobserver protocol.
+//!
+//! A jobserver is a pool of tokens shared among a tree of processes, each of
+//! which has to acquire a token before doing a unit of parallel work. Handing
+//! the same jobserver to every `rustc` and build script we spawn bounds the
+//! total parallelism of a build, rather than letting each of them pick their
+//! own limit. On Unix the pool is a pipe containing one byte per token, and
+//! on Windows it is a named semaphore.
+
+use std::io;
+use std::sync::Arc;
+
+use util::spawn::Command;
+
+/// A handle to a jobserver which can be passed on to child processes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Client {
+    inner: Arc<imp::Client>,
+}
+
+/// A token acquired from a jobserver, which is released again when dropped.
+pub struct Acquired {
+    client: Arc<imp::Client>,
+}
+
+impl Client {
+    /// Creates a new jobserver holding `limit` tokens.
+    pub fn new(limit: usize) -> io::Result<Client> {
+        let client = try!(imp::Client::new());
+        for _ in 0..limit {
+            try!(client.release());
+        }
+        Ok(Client { inner: Arc::new(client) })
+    }
+
+    /// Blocks until a token is available, and acquires it.
+    pub fn acquire(&self) -> io::Result<Acquired> {
+        try!(self.inner.acquire());
+        Ok(Acquired { client: self.inner.clone() })
+    }
+
+    /// Returns the value of `MAKEFLAGS` which tells a child how to connect to
+    /// this jobserver.
+    pub fn makeflags(&self) -> String {
+        self.inner.makeflags()
+    }
+
+    /// Configures `cmd` such that the spawned process can connect to this
+    /// jobserver.
+    ///
+    /// This only takes care of making the jobserver itself available to the
+    /// child, the environment variables describing it are set separately.
+    pub fn configure(&self, cmd: &mut Command) {
+        self.inner.configure(cmd)
+    }
+}
+
+impl Drop for Acquired {
+    fn drop(&mut self) {
+        let _ = self.client.release();
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::io;
+
+    use libc;
+
+    use util::spawn::Command;
+
+    #[derive(Debug, PartialEq)]
+    pub struct Client {
+        read: libc::c_int,
+        write: libc::c_int,
+    }
+
+    impl Client {
+        pub fn new() -> io::Result<Client> {
+            let mut fds = [0; 2];
+            if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+                return Err(io::Error::last_os_error())
+            }
+            let client = Client { read: fds[0], write: fds[1] };
+
+            // Only the processes we explicitly configure should get to see the
+            // pipe, so keep it from leaking into everything else we spawn.
+            try!(set_cloexec(client.read, true));
+            try!(set_cloexec(client.write, true));
+            Ok(client)
+        }
+
+        pub fn acquire(&self) -> io::Result<()> {
+            let mut buf = [0u8];
+            loop {
+                let ptr = buf.as_mut_ptr() as *mut libc::c_void;
+                let n = unsafe { libc::read(self.read, ptr, 1) };
+                match n {
+                    1 => return Ok(()),
+                    0 => return Err(io::Error::new(io::ErrorKind::Other,
+                                                   "jobserver pipe was closed",
+                                                   None)),
+                    _ => {
+                        let err = io::Error::last_os_error();
+                        if err.kind() != io::ErrorKind::Interrupted {
+                            return Err(err)
+                        }
+                    }
+                }
+            }
+        }
+
+        pub fn release(&self) -> io::Result<()> {
+            let buf = [b'|'];
+            loop {
+                let ptr = buf.as_ptr() as *const libc::c_void;
+                let n = unsafe { libc::write(self.write, ptr, 1) };
+                if n == 1 { return Ok(()) }
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err)
+                }
+            }
+        }
+
+        pub fn makeflags(&self) -> String {
+            format!("-j --jobserver-fds={0},{1} --jobserver-auth={0},{1}",
+                    self.read, self.write)
+        }
+
+        pub fn configure(&self, cmd: &mut Command) {
+            let (read, write) = (self.read, self.write);
+            // Runs between fork and exec, and `fcntl` is async-signal-safe.
+            unsafe {
+                cmd.pre_exec(move || {
+                    try!(set_cloexec(read, false));
+                    set_cloexec(write, false)
+                });
+            }
+        }
+    }
+
+    impl Drop for Client {
+        fn drop(&mut self) {
+            unsafe {
+                libc::close(self.read);
+                libc::close(self.write);
+            }
+        }
+    }
+
+    fn set_cloexec(fd: libc::c_int, set: bool) -> io::Result<()> {
+        unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFD);
+            if flags == -1 {
+                return Err(io::Error::last_os_error())
+            }
+            let flags = if set {
+                flags | libc::FD_CLOEXEC
+            } else {
+                flags & !libc::FD_CLOEXEC
+            };
+            if libc::fcntl(fd, libc::F_SETFD, flags) == -1 {
+                return Err(io::Error::last_os_error())
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    extern crate "kernel32-sys" as kernel32;
+    extern crate winapi;
+
+    use std::ffi::CString;
+    use std::i32;
+    use std::io;
+    use std::ptr;
+    use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+
+    use util::spawn::Command;
+
+    static COUNTER: AtomicUsize = ATOMIC_USIZE_INIT;
+
+    #[derive(Debug, PartialEq)]
+    pub struct Client {
+        sem: winapi::HANDLE,
+        name: String,
+    }
+
+    // Semaphore handles may be used from any thread.
+    unsafe impl Send for Client {}
+    unsafe impl Sync for Client {}
+
+    impl Client {
+        pub fn new() -> io::Result<Client> {
+            let name = format!("__cargo_jobserver_{}_{}",
+                               unsafe { kernel32::GetCurrentProcessId() },
+                               COUNTER.fetch_add(1, Ordering::SeqCst));
+            let c_name = CString::new(name.clone()).unwrap();
+            let sem = unsafe {
+                kernel32::CreateSemaphoreA(ptr::null_mut(), 0, i32::MAX,
+                                           c_name.as_ptr())
+            };
+            if sem.is_null() {
+                return Err(io::Error::last_os_error())
+            }
+            Ok(Client { sem: sem, name: name })
+        }
+
+        pub fn acquire(&self) -> io::Result<()> {
+            let r = unsafe {
+                kernel32::WaitForSingleObject(self.sem, winapi::INFINITE)
+            };
+            if r == winapi::WAIT_OBJECT_0 {
+                Ok(())
+            } else {
+                Err(io::Error::last_os_error())
+            }
+        }
+
+        pub fn release(&self) -> io::Result<()> {
+            let r = unsafe {
+                kernel32::ReleaseSemaphore(self.sem, 1, ptr::null_mut())
+            };
+            if r != 0 {
+                Ok(())
+            } else {
+                Err(io::Error::last_os_error())
+            }
+        }
+
+        pub fn makeflags(&self) -> String {
+            format!("-j --jobserver-auth={}", self.name)
+        }
+
+        // Children open the semaphore by name, so there's nothing to inherit.
+        pub fn configure(&self, _cmd: &mut Command) {}
+    }
+
+    impl Drop for Client {
+        fn drop(&mut self) {
+            unsafe { kernel32::CloseHandle(self.sem); }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Client;
+
+    #[test]
+    fn tokens_are_returned_on_drop() {
+        let client = Client::new(2).unwrap();
+        let a = client.acquire().unwrap();
+        let b = client.acquire().unwrap();
+        drop(a);
+        let _c = client.acquire().unwrap();
+        drop(b);
+        let _d = client.acquire().unwrap();
+    }
+}
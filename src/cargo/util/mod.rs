@@ -1,11 +1,32 @@
 pub use self::config::Config;
-pub use self::process_builder::{process, ProcessBuilder};
+pub use self::process::{process, process_os, process_from_shell};
+pub use self::process::{process_with_args, process_shell};
+pub use self::process::{run_parallel, run_parallel_cancellable, run_in_dirs};
+pub use self::process::{wait_timeout, BatchHandle};
+pub use self::process::{ProcessBuilder, ProcessBuilderState};
+pub use self::process::ProcessHandle;
+pub use self::process::{ArgsLossy, CommandDescription, InstrumentedOutput};
+pub use self::process::CommandOutcome;
+pub use self::process::{ColorMode, Encoding};
+pub use self::process::{OutputChunk, ProcessErrorContext};
+pub use self::process::{EnvConfig, EnvConfigEntry};
+pub use self::process::{CommandRecorder, RecordedCommand};
+pub use self::process::{ProbeCache, ProbeResult};
+#[cfg(unix)]
+pub use self::process::ResourceUsage;
+#[cfg(all(target_os = "linux",
+          any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub use self::process::SeccompProfile;
+pub use self::ansi::strip_ansi;
+pub use self::diagnostics::{parse_json_messages, JsonMessage, JsonSpan};
+pub use self::file_kind::{file_kind, FileKind};
 pub use self::errors::{CargoResult, CargoError, ChainError, CliResult};
 pub use self::errors::{CliError, ProcessError};
-pub use self::errors::{process_error, internal_error, internal, human};
+pub use self::errors::{process_error, process_error_with_tail, internal_error};
+pub use self::errors::{internal, human};
 pub use self::errors::{Human, caused_human};
 pub use self::paths::{join_paths, path2bytes, bytes2path, dylib_path};
-pub use self::paths::{normalize_path, dylib_path_envvar};
+pub use self::paths::{normalize_path, dylib_path_envvar, with_exe_suffix};
 pub use self::lev_distance::{lev_distance};
 pub use self::hex::{to_hex, short_hash};
 pub use self::dependency_queue::{DependencyQueue, Fresh, Dirty, Freshness};
@@ -16,14 +37,19 @@ pub use self::to_semver::ToSemver;
 pub use self::vcs::{GitRepo, HgRepo};
 pub use self::sha256::Sha256;
 
+pub mod ansi;
 pub mod config;
+pub mod diagnostics;
 pub mod errors;
+pub mod file_kind;
 pub mod graph;
 pub mod hex;
 pub mod important_paths;
+pub mod jobserver;
 pub mod paths;
-pub mod process_builder;
+pub mod process;
 pub mod profile;
+pub mod spawn;
 pub mod to_semver;
 pub mod to_url;
 pub mod toml;
@@ -1,3 +1,4 @@
+use std::ascii::AsciiExt;
 use std::env;
 use std::ffi::{AsOsStr, OsString};
 use std::path::{Path, PathBuf, Component};
@@ -17,6 +18,20 @@ pub fn join_paths<T: AsOsStr>(paths: &[T], env: &str) -> CargoResult<OsString> {
     })
 }
 
+/// Returns `name` with the suffix executables have on this platform appended,
+/// such as `rustc.exe` for `rustc` on Windows.
+///
+/// Names which already end in the suffix, ignoring case, are returned as they
+/// are, and on platforms without a suffix this is always `name` itself.
+pub fn with_exe_suffix(name: &str) -> String {
+    let suffix = env::consts::EXE_SUFFIX;
+    if name.to_ascii_lowercase().ends_with(suffix) {
+        name.to_string()
+    } else {
+        format!("{}{}", name, suffix)
+    }
+}
+
 pub fn dylib_path_envvar() -> &'static str {
     if cfg!(windows) {"PATH"}
     else if cfg!(target_os = "macos") {"DYLD_LIBRARY_PATH"}
@@ -80,3 +95,23 @@ pub fn bytes2path(bytes: &[u8]) -> CargoResult<PathBuf> {
         Err(..) => Err(human("invalid non-unicode path")),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::with_exe_suffix;
+
+    #[cfg(windows)]
+    #[test]
+    fn exe_suffix_is_added_once() {
+        assert_eq!(with_exe_suffix("rustc"), "rustc.exe");
+        assert_eq!(with_exe_suffix("rustc.exe"), "rustc.exe");
+        assert_eq!(with_exe_suffix("RUSTC.EXE"), "RUSTC.EXE");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exe_suffix_is_not_added() {
+        assert_eq!(with_exe_suffix("rustc"), "rustc");
+        assert_eq!(with_exe_suffix("rustc.exe"), "rustc.exe");
+    }
+}
@@ -0,0 +1,337 @@
+//! Inspecting the command a `ProcessBuilder` would run.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::Str;
+
+use util::{CargoResult, human, caused_human};
+use util::{file_kind, FileKind};
+use util::spawn::Command;
+use super::{ProcessBuilder, find_in_path, is_bare_name, imp};
+use super::shell::shell_quote;
+
+impl ProcessBuilder {
+    /// Checks that the arguments of the process fit within the limit the
+    /// platform puts on them, so that an over-long command line can be pointed
+    /// out instead of failing to spawn with a confusing error.
+    ///
+    /// On Unix the limit is `ARG_MAX`, which covers the arguments and the
+    /// environment together. On Windows it's the 32K characters allowed for
+    /// the command line, less some room for the program to be resolved to its
+    /// full path. Callers may also use this to decide whether to pass the
+    /// arguments through `use_argfile` instead.
+    pub fn check_arg_length(&self) -> CargoResult<()> {
+        let limit = match imp::arg_max() {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        let length = imp::arg_length(self);
+        if length <= limit {
+            return Ok(())
+        }
+        Err(human(format!("the arguments of process {} take up {} {}, more \
+                           than the limit of {} {}", self.error_name(), length,
+                          imp::ARG_LENGTH_UNIT, limit, imp::ARG_LENGTH_UNIT)))
+    }
+
+    /// Joins the program and its arguments into a single command line, quoted
+    /// the way the platform expects.
+    ///
+    /// On Unix each word is quoted for `sh` as `to_shell_command` does, and on
+    /// Windows the arguments are quoted such that `CommandLineToArgvW` splits
+    /// them up again. Unlike the `Display` implementation this never loses
+    /// any part of arguments which aren't unicode.
+    pub fn os_command_line(&self) -> OsString {
+        imp::command_line(&self.get_argv())
+    }
+
+    /// Renders the process as a POSIX shell command which reproduces it,
+    /// including its working directory and environment overrides.
+    ///
+    /// The result looks like `cd DIR && unset A && B=value PROGRAM ARGS...`,
+    /// with everything quoted for `sh`. This is meant for telling users how to
+    /// rerun a failing command by hand, and there is no equivalent for the
+    /// Windows command prompt.
+    pub fn to_shell_command(&self) -> String {
+        let mut cmd = String::new();
+        if let Some(ref cwd) = self.cwd {
+            cmd.push_str("cd ");
+            cmd.push_str(&shell_quote(&cwd.to_string_lossy()));
+            cmd.push_str(" && ");
+        }
+
+        let vars = self.env_overrides();
+        let removed = vars.iter().filter(|&&(_, ref val)| val.is_none())
+                          .map(|&(ref key, _)| key.as_slice())
+                          .collect::<Vec<_>>();
+        if self.env_clear {
+            cmd.push_str("env -i ");
+            for key in self.forwarded_env().iter() {
+                if let Some(val) = env::var_os(key) {
+                    cmd.push_str(key);
+                    cmd.push('=');
+                    cmd.push_str(&shell_quote(&val.to_string_lossy()));
+                    cmd.push(' ');
+                }
+            }
+        } else if removed.len() > 0 {
+            cmd.push_str("unset ");
+            cmd.push_str(&removed.connect(" "));
+            cmd.push_str(" && ");
+        }
+        for &(ref key, ref val) in vars.iter() {
+            if let Some(ref val) = *val {
+                cmd.push_str(key);
+                cmd.push('=');
+                cmd.push_str(&shell_quote(&val.to_string_lossy()));
+                cmd.push(' ');
+            }
+        }
+
+        cmd.push_str(&shell_quote(&self.program.to_string_lossy()));
+        for arg in self.args.iter() {
+            cmd.push(' ');
+            cmd.push_str(&shell_quote(&arg.to_string_lossy()));
+        }
+        cmd
+    }
+
+    /// Builds the `Command` which runs the process.
+    ///
+    /// Environment variables are applied in the order of their names, however
+    /// they were added, so that the same builder always results in the same
+    /// `Command`.
+    pub fn build_command(&self) -> Command {
+        self.build_command_for(&self.program)
+    }
+
+    /// Checks that the program exists and is executable, without running it.
+    ///
+    /// Bare program names are looked up in the `PATH` of the process, and
+    /// other paths are taken relative to its working directory. A program
+    /// counts as executable if any of its execute permission bits are set on
+    /// Unix, and if it has an extension such as `.exe` on Windows. On Unix the
+    /// interpreter named by the `#!` line of a script has to exist as well.
+    pub fn check_runnable(&self) -> CargoResult<()> {
+        let name = self.program.to_string_lossy();
+        let path = if is_bare_name(&self.program) {
+            let paths = self.get_env("PATH").unwrap_or(OsString::new());
+            let exe_suffix = env::consts::EXE_SUFFIX;
+            match find_in_path(&self.program, &paths, exe_suffix) {
+                Some(path) => path,
+                None => {
+                    return Err(human(format!("could not find executable `{}` \
+                                              on PATH", name)))
+                }
+            }
+        } else {
+            Path::new(&self.program_path(&self.program)).to_path_buf()
+        };
+
+        let metadata = try!(fs::metadata(&path).map_err(|e| {
+            caused_human(format!("could not find executable `{}`",
+                                 path.display()), e)
+        }));
+        if !metadata.is_file() {
+            return Err(human(format!("`{}` is not a file", path.display())))
+        }
+        // Failing to read the file just means there's less to go on.
+        let kind = file_kind(&path).unwrap_or(FileKind::Unknown);
+        if !imp::is_executable(&path, &metadata) {
+            let msg = match kind {
+                FileKind::Script { ref interpreter, .. } => {
+                    format!("`{}` is not executable, although it's a script \
+                             for `{}`", path.display(), interpreter.display())
+                }
+                _ => format!("`{}` is not executable", path.display()),
+            };
+            return Err(human(msg))
+        }
+        if let FileKind::Script { ref interpreter, .. } = kind {
+            if cfg!(unix) && fs::metadata(interpreter).is_err() {
+                return Err(human(format!("the interpreter `{}` of script `{}` \
+                                          could not be found",
+                                         interpreter.display(),
+                                         path.display())))
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the program, arguments, working directory and environment of
+    /// the `Command` that `build_command` produces, in a form which can be
+    /// inspected.
+    ///
+    /// The environment is the complete one the process would start with,
+    /// with every explicit change applied to the inherited variables, except
+    /// that inherited variables whose names aren't unicode are left out.
+    pub fn describe_command(&self) -> CommandDescription {
+        CommandDescription {
+            program: self.program_path(&self.program),
+            args: self.args.clone(),
+            cwd: self.get_cwd().map(|cwd| cwd.to_path_buf()),
+            env: self.effective_env(),
+        }
+    }
+}
+
+/// The command a `ProcessBuilder` runs, as returned by `describe_command`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CommandDescription {
+    pub program: OsString,
+    pub args: Vec<OsString>,
+    pub cwd: Option<PathBuf>,
+    /// Every variable in the environment of the process, with its value.
+    pub env: BTreeMap<String, OsString>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::ffi::{OsStr, OsString};
+    use std::fs::File;
+    use std::io::prelude::*;
+    use std::path::Path;
+
+    use tempdir::TempDir;
+
+    use util::process::tests::shell;
+    use util::process::process;
+
+    #[test]
+    fn check_runnable_finds_programs() {
+        assert!(shell("exit 0").check_runnable().is_ok());
+
+        let err = process("cargo-no-such-program").check_runnable().unwrap_err();
+        assert!(err.to_string().contains("could not find executable"), "{}", err);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_runnable_rejects_non_executable_files() {
+        let td = TempDir::new("cargo").unwrap();
+        let path = td.path().join("script");
+        File::create(&path).unwrap();
+
+        let err = process(&path).check_runnable().unwrap_err();
+        assert!(err.to_string().contains("is not executable"), "{}", err);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_runnable_explains_broken_scripts() {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let td = TempDir::new("cargo").unwrap();
+        let path = td.path().join("script");
+        File::create(&path).unwrap()
+            .write_all(b"#!/cargo/no/such/interpreter\n").unwrap();
+
+        let err = process(&path).check_runnable().unwrap_err();
+        assert!(err.to_string().contains("it's a script for \
+                                          `/cargo/no/such/interpreter`"),
+                "{}", err);
+
+        let mut perms = fs::metadata(&path).unwrap().permissions();
+        perms.set_mode(0o755);
+        fs::set_permissions(&path, perms).unwrap();
+        let err = process(&path).check_runnable().unwrap_err();
+        assert!(err.to_string().contains("the interpreter \
+                                          `/cargo/no/such/interpreter`"),
+                "{}", err);
+    }
+
+    #[test]
+    fn describe_command_resolves_env() {
+        env::set_var("__CARGO_TEST_DESCRIBE_REMOVED", "inherited");
+        env::set_var("__CARGO_TEST_DESCRIBE_OVERRIDDEN", "inherited");
+        env::set_var("__CARGO_TEST_DESCRIBE_KEPT", "inherited");
+
+        let mut p = process("foo");
+        p.arg("bar").cwd("/tmp")
+         .env_remove("__CARGO_TEST_DESCRIBE_REMOVED")
+         .env("__CARGO_TEST_DESCRIBE_OVERRIDDEN", "new");
+        let desc = p.describe_command();
+        assert_eq!(desc.program, OsString::from_str("foo"));
+        assert_eq!(desc.args, [OsString::from_str("bar")]);
+        assert_eq!(desc.cwd, Some(Path::new("/tmp").to_path_buf()));
+        assert!(!desc.env.contains_key("__CARGO_TEST_DESCRIBE_REMOVED"));
+        assert_eq!(desc.env["__CARGO_TEST_DESCRIBE_OVERRIDDEN"],
+                   OsString::from_str("new"));
+        assert_eq!(desc.env["__CARGO_TEST_DESCRIBE_KEPT"],
+                   OsString::from_str("inherited"));
+    }
+
+    #[test]
+    fn to_shell_command_quotes_everything() {
+        let mut p = process("foo");
+        p.arg("hello world").arg("it's").arg("plain").arg("")
+         .cwd("/some dir").env("B", "x y").env("A", "").env_remove("C");
+        assert_eq!(p.to_shell_command(),
+                   "cd '/some dir' && unset C && A='' B='x y' foo \
+                    'hello world' 'it'\\''s' plain ''");
+
+        p.env_clear();
+        assert_eq!(p.to_shell_command(),
+                   "cd '/some dir' && env -i A='' B='x y' foo \
+                    'hello world' 'it'\\''s' plain ''");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn to_shell_command_round_trips() {
+        let mut p = shell("printf '%s|%s' \"$FOO\" \"$1\"");
+        p.arg("sh").arg("hello world").env("FOO", "it's here");
+        let expected = p.exec_with_output().unwrap().stdout;
+
+        let output = shell(&p.to_shell_command()).exec_with_output().unwrap();
+        assert_eq!(output.stdout, expected);
+        assert_eq!(String::from_utf8(expected).unwrap(),
+                   "it's here|hello world");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn os_command_line_quotes_for_sh() {
+        use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+        let mut p = process("foo");
+        p.arg("a b").arg("it's").arg(OsStr::from_bytes(b"\xff")).arg("plain");
+        assert_eq!(p.os_command_line().into_vec(),
+                   b"foo 'a b' 'it'\\''s' '\xff' plain".to_vec());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn os_command_line_quotes_for_windows() {
+        let mut p = process("foo");
+        p.arg("a b").arg("say \"hi\"").arg("C:\\dir\\").arg("C:\\my dir\\");
+        assert_eq!(p.os_command_line().to_str().unwrap(),
+                   "foo \"a b\" \"say \\\"hi\\\"\" C:\\dir\\ \"C:\\my dir\\\\\"");
+    }
+
+    #[test]
+    fn check_arg_length_catches_huge_command_lines() {
+        use std::iter::repeat;
+        use util::process::imp;
+
+        let p = process("rustc");
+        p.check_arg_length().unwrap();
+
+        // Stay clear of limits high enough that testing them gets costly.
+        let limit = imp::arg_max().unwrap();
+        if limit > 64 * 1024 * 1024 { return }
+        let arg = repeat('x').take(1024).collect::<String>();
+        let mut p = process("rustc");
+        for _ in 0..limit / 1024 + 1 {
+            p.arg(&arg);
+        }
+        let err = p.check_arg_length().unwrap_err().to_string();
+        assert!(err.contains(&format!("more than the limit of {}", limit)),
+                "{}", err);
+    }
+}
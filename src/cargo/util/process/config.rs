@@ -0,0 +1,2344 @@
+//! The options of a `ProcessBuilder` which aren't about its environment.
+
+use std::borrow::Cow;
+use std::cmp;
+use std::ffi::{OsStr, OsString, AsOsStr};
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher, SipHasher};
+use std::io::prelude::*;
+use std::io;
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+#[cfg(windows)]
+use std::os::windows::io::RawHandle;
+use std::path::{Path, PathBuf};
+use std::slice;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use util::{CargoResult, caused_human};
+use util::spawn::Command;
+use super::{CommandRecorder, Encoding, Ignored, ProcessBuilder};
+use super::{ProcessBuilderState, ProcessErrorContext};
+
+impl ProcessBuilder {
+    pub fn arg<T: AsOsStr + ?Sized>(&mut self, arg: &T) -> &mut ProcessBuilder {
+        self.args.push(arg.as_os_str().to_os_string());
+        self
+    }
+
+    pub fn args<T: AsOsStr>(&mut self, arguments: &[T]) -> &mut ProcessBuilder {
+        self.args.extend(arguments.iter().map(|t| {
+            t.as_os_str().to_os_string()
+        }));
+        self
+    }
+
+    /// Inserts `arg` before all of the arguments added so far, for options
+    /// which have to come before everything else such as `git -C <dir>`.
+    ///
+    /// Each call puts its argument first, so arguments added this way end up
+    /// in the reverse of the order they were added in.
+    pub fn arg_front<T: AsOsStr + ?Sized>(&mut self, arg: &T)
+                                          -> &mut ProcessBuilder {
+        self.args.insert(0, arg.as_os_str().to_os_string());
+        self
+    }
+
+    /// Appends each of the owned arguments yielded by `args`.
+    pub fn extend_args<I>(&mut self, args: I) -> &mut ProcessBuilder
+        where I: IntoIterator<Item=OsString>
+    {
+        self.args.extend(args);
+        self
+    }
+
+    /// Saves the arguments, environment overrides and working directory of
+    /// the process, so that changes to them can be undone with `restore`.
+    pub fn snapshot(&self) -> ProcessBuilderState {
+        ProcessBuilderState {
+            args: self.args.clone(),
+            env: self.env.clone(),
+            lazy_env: self.lazy_env.clone(),
+            cwd: self.cwd.clone(),
+        }
+    }
+
+    /// Puts back the arguments, environment overrides and working directory
+    /// saved in `state`, leaving the rest of the configuration as is.
+    pub fn restore(&mut self, state: ProcessBuilderState)
+                   -> &mut ProcessBuilder {
+        self.args = state.args;
+        self.env = state.env;
+        self.lazy_env = state.lazy_env;
+        self.cwd = state.cwd;
+        self
+    }
+
+    /// Removes every argument added so far, keeping the rest of the
+    /// configuration.
+    pub fn clear_args(&mut self) -> &mut ProcessBuilder {
+        self.args.clear();
+        self
+    }
+
+    /// Registers a function which rewrites each argument as the `Command` for
+    /// the process is built, such as to remap absolute paths.
+    ///
+    /// The arguments stored on the builder are left as they are, so methods
+    /// such as `get_args` and `Display` show them untransformed. Arguments
+    /// written to an argfile are transformed as well. This replaces any
+    /// function registered previously.
+    pub fn arg_transform(&mut self, f: Box<Fn(&OsStr) -> OsString + Send + Sync>)
+                         -> &mut ProcessBuilder {
+        self.arg_transform = Some(Ignored(Arc::new(f)));
+        self
+    }
+
+    /// Configures whether repeated flags are dropped as the `Command` for the
+    /// process is built, keeping their first occurrence.
+    ///
+    /// Only flags which assign a value, and so can be assumed to mean the same
+    /// thing however often they're given, are dropped: a single argument such
+    /// as `--cfg=unix`, or a flag followed by an assignment such as `-C` and
+    /// `opt-level=2`, which is dropped along with it. Everything else is
+    /// kept, including bare switches like `-v` which may count how often
+    /// they're given, flags with plain values like `-l foo` whose order may
+    /// matter, positional arguments and anything following `--`. Like
+    /// `arg_transform` this leaves the arguments stored on the builder alone.
+    /// By default arguments aren't deduplicated.
+    pub fn dedup_args(&mut self, yes: bool) -> &mut ProcessBuilder {
+        self.dedup_args = yes;
+        self
+    }
+
+    /// Appends all of the arguments of `other` to those of this builder, in
+    /// order. Nothing else about `other` is carried over.
+    pub fn append_args_of(&mut self, other: &ProcessBuilder)
+                          -> &mut ProcessBuilder {
+        self.args.push_all(&other.args);
+        self
+    }
+
+    /// Appends `arg` to the arguments of the process only if `cond` is true.
+    pub fn arg_if<T: AsOsStr + ?Sized>(&mut self, cond: bool,
+                                       arg: &T) -> &mut ProcessBuilder {
+        if cond {
+            self.arg(arg);
+        }
+        self
+    }
+
+    /// Appends `arg` to the arguments of the process only if `var` is set to a
+    /// non-empty value in its environment.
+    ///
+    /// The value is looked up through `get_env`, so variables set or removed
+    /// on this builder are taken into account. As a result the argument has
+    /// to be added after the environment is configured.
+    pub fn arg_if_env<T: AsOsStr + ?Sized>(&mut self, var: &str,
+                                           arg: &T) -> &mut ProcessBuilder {
+        let set = self.get_env(var).map(|v| !v.is_empty()).unwrap_or(false);
+        self.arg_if(set, arg)
+    }
+
+    /// Appends `arguments` to the arguments of the process only if `cond` is
+    /// true.
+    pub fn args_if<T: AsOsStr>(&mut self, cond: bool,
+                               arguments: &[T]) -> &mut ProcessBuilder {
+        if cond {
+            self.args(arguments);
+        }
+        self
+    }
+
+    pub fn cwd<T: AsOsStr + ?Sized>(&mut self, path: &T) -> &mut ProcessBuilder {
+        self.cwd = Some(path.as_os_str().to_os_string());
+        self
+    }
+
+    /// Configures whether the program is looked up in `PATH` before the
+    /// process is spawned.
+    ///
+    /// The lookup takes any `PATH` set through `env` into account, and on
+    /// Windows also tries the program name with `.exe` appended. When the
+    /// program can't be found the error lists every directory that was
+    /// searched. Programs given as paths rather than bare names are never
+    /// looked up.
+    pub fn search_path(&mut self, search: bool) -> &mut ProcessBuilder {
+        self.search_path = search;
+        self
+    }
+
+    /// Looks the program up in `PATH` right away, as `search_path` does, and
+    /// replaces it with the path found.
+    ///
+    /// Later executions then run that same program even if `PATH` changes in
+    /// the meantime. Programs given as paths rather than bare names are left
+    /// alone, so calling this again has no effect. A program which can't be
+    /// found is an error.
+    pub fn resolve_program(&mut self) -> CargoResult<&mut ProcessBuilder> {
+        self.program = try!(self.find_program());
+        Ok(self)
+    }
+
+    /// Redirects the stdout of the process to the file at `path`, which is
+    /// truncated if it already exists.
+    ///
+    /// Methods which capture output return an empty stdout when this is set.
+    pub fn stdout_to_file(&mut self, path: PathBuf) -> &mut ProcessBuilder {
+        self.stdout_file = Some(path);
+        self.stdout_fd = None;
+        self
+    }
+
+    /// Redirects the stderr of the process to the file at `path`, which is
+    /// truncated if it already exists.
+    ///
+    /// Methods which capture output return an empty stderr when this is set.
+    pub fn stderr_to_file(&mut self, path: PathBuf) -> &mut ProcessBuilder {
+        self.stderr_file = Some(path);
+        self.stderr_fd = None;
+        self
+    }
+
+    /// Connects the stdout of the process to the open descriptor `fd`, such
+    /// as a socket or a pipe to some other program.
+    ///
+    /// The descriptor stays owned by the caller: each run gets a duplicate of
+    /// it which is closed once the process has been spawned, so `fd` has to
+    /// stay open for as long as the builder may be run, and closing it
+    /// afterwards is up to the caller. Methods which capture output return an
+    /// empty stdout when this is set. This replaces `stdout_to_file`, and is
+    /// only available on Unix.
+    #[cfg(unix)]
+    pub fn stdout_fd(&mut self, fd: RawFd) -> &mut ProcessBuilder {
+        self.stdout_fd = Some(fd as usize);
+        self.stdout_file = None;
+        self
+    }
+
+    /// Like `stdout_fd`, for stderr.
+    #[cfg(unix)]
+    pub fn stderr_fd(&mut self, fd: RawFd) -> &mut ProcessBuilder {
+        self.stderr_fd = Some(fd as usize);
+        self.stderr_file = None;
+        self
+    }
+
+    /// Connects the stdout of the process to the open handle `handle`, such
+    /// as a pipe to some other program.
+    ///
+    /// The handle stays owned by the caller, in the same way as the
+    /// descriptor given to `stdout_fd` on Unix. This is only available on
+    /// Windows.
+    #[cfg(windows)]
+    pub fn stdout_handle(&mut self, handle: RawHandle) -> &mut ProcessBuilder {
+        self.stdout_fd = Some(handle as usize);
+        self.stdout_file = None;
+        self
+    }
+
+    /// Like `stdout_handle`, for stderr.
+    #[cfg(windows)]
+    pub fn stderr_handle(&mut self, handle: RawHandle) -> &mut ProcessBuilder {
+        self.stderr_fd = Some(handle as usize);
+        self.stderr_file = None;
+        self
+    }
+
+    /// Replaces the program which is run, keeping the rest of the
+    /// configuration as is.
+    pub fn set_program(&mut self, program: OsString) -> &mut ProcessBuilder {
+        self.program = program;
+        self
+    }
+
+    /// Runs the process through `wrapper`, such as an emulator or a profiler.
+    ///
+    /// The program and arguments of `wrapper` are put in front of those of
+    /// this builder, so the process runs as `wrapper wrapper-args... program
+    /// args...`. The environment overrides of `wrapper` are merged in as by
+    /// `inherit_env_from`, with this builder winning on conflicts, and the
+    /// working directory of `wrapper` is only used if this builder doesn't
+    /// have one. Nothing else about `wrapper` is carried over.
+    pub fn wrap_with(&mut self, wrapper: ProcessBuilder) -> &mut ProcessBuilder {
+        let mut args = wrapper.args.clone();
+        args.push(self.program.clone());
+        args.push_all(&self.args);
+        self.args = args;
+        self.program = wrapper.program.clone();
+        self.inherit_env_from(&wrapper);
+        if self.cwd.is_none() {
+            self.cwd = wrapper.cwd;
+        }
+        self
+    }
+
+    /// Adds `alt` as a program to run instead if the program can't be found.
+    ///
+    /// Fallbacks are tried in the order they were added, and the first
+    /// program which exists is run with the same arguments and environment.
+    /// Bare program names are looked for in the `PATH` of the process, and
+    /// paths relative to its working directory. If none of the programs can
+    /// be found the error lists every one of them.
+    pub fn fallback_program(&mut self, alt: OsString) -> &mut ProcessBuilder {
+        self.fallbacks.push(alt);
+        self
+    }
+
+    pub fn get_program(&self) -> &OsString { &self.program }
+
+    pub fn get_args(&self) -> &[OsString] {
+        &self.args
+    }
+
+    /// Returns the arguments of the process as strings, for use in log
+    /// messages. Arguments which aren't unicode are converted lossily.
+    pub fn args_lossy(&self) -> ArgsLossy {
+        ArgsLossy { iter: self.args.iter() }
+    }
+
+    /// Returns the program followed by all of its arguments, in the form of
+    /// the `argv` a process is started with.
+    pub fn get_argv(&self) -> Vec<OsString> {
+        let mut argv = Vec::with_capacity(self.args.len() + 1);
+        argv.push(self.program.clone());
+        argv.push_all(&self.args);
+        argv
+    }
+
+    /// Returns the working directory configured for the process, if any.
+    ///
+    /// When this is `None` the process runs in the current directory of
+    /// cargo at the time it's spawned.
+    pub fn get_cwd(&self) -> Option<&Path> {
+        self.cwd.as_ref().map(|cwd| Path::new(cwd))
+    }
+
+    /// Returns whether `other` runs the same program with the same arguments
+    /// and environment, regardless of the working directory.
+    ///
+    /// Environments are compared by the values the process would actually
+    /// see, so setting a variable to the value it would inherit anyway makes
+    /// no difference.
+    pub fn same_command_as(&self, other: &ProcessBuilder) -> bool {
+        self.program == other.program && self.args == other.args &&
+            self.effective_env() == other.effective_env()
+    }
+
+    /// Hashes everything `same_command_as` compares, for use as a cache key.
+    pub fn command_fingerprint(&self) -> u64 {
+        let mut hasher = SipHasher::new_with_keys(0, 0);
+        self.program.hash(&mut hasher);
+        self.args.hash(&mut hasher);
+        self.effective_env().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Hashes the `command_fingerprint` of the process together with the
+    /// contents of each file in `inputs`, to tell whether an earlier run with
+    /// the same inputs can be reused.
+    ///
+    /// The hash doesn't depend on anything else, so it's stable across runs
+    /// of cargo. It's an error for any of the inputs not to be readable.
+    pub fn fingerprint_with_inputs(&self, inputs: &[PathBuf])
+                                   -> CargoResult<u64> {
+        self.fingerprint_inputs(inputs, true)
+    }
+
+    /// Like `fingerprint_with_inputs`, but hashes the size and modification
+    /// time of each input rather than reading its contents.
+    pub fn fingerprint_with_input_mtimes(&self, inputs: &[PathBuf])
+                                         -> CargoResult<u64> {
+        self.fingerprint_inputs(inputs, false)
+    }
+
+    fn fingerprint_inputs(&self, inputs: &[PathBuf], contents: bool)
+                          -> CargoResult<u64> {
+        let mut hasher = SipHasher::new_with_keys(0, 0);
+        self.command_fingerprint().hash(&mut hasher);
+        for input in inputs.iter() {
+            let failed = |e: io::Error| {
+                caused_human(format!("failed to fingerprint input `{}`",
+                                     input.display()), e)
+            };
+            input.hash(&mut hasher);
+            if contents {
+                let mut data = Vec::new();
+                try!(File::open(input).and_then(|mut f| {
+                    f.read_to_end(&mut data)
+                }).map_err(&failed));
+                data.hash(&mut hasher);
+            } else {
+                let metadata = try!(fs::metadata(input).map_err(&failed));
+                metadata.len().hash(&mut hasher);
+                metadata.modified().hash(&mut hasher);
+            }
+        }
+        Ok(hasher.finish())
+    }
+
+    /// Describes how the process would be run, for people debugging why cargo
+    /// ran it the way it did. Nothing is run.
+    ///
+    /// The report lists the program as it would be found on `PATH`, each
+    /// argument on a line of its own, the working directory, and the
+    /// variables from `env_diff`: added ones are marked with `+`, changed ones
+    /// with `~` and removed ones with `-`.
+    pub fn explain(&self) -> String {
+        let program = if self.fallbacks.len() > 0 {
+            self.choose_program()
+        } else {
+            self.find_program()
+        };
+        let mut out = match program {
+            Ok(program) => format!("program: {}\n", program.to_string_lossy()),
+            Err(..) => format!("program: {} (not found)\n",
+                               self.program.to_string_lossy()),
+        };
+        out.push_str("arguments:\n");
+        for arg in self.final_args().iter() {
+            out.push_str(&format!("    {}\n", arg.to_string_lossy()));
+        }
+        match self.get_cwd() {
+            Some(cwd) => out.push_str(&format!("cwd: {}\n", cwd.display())),
+            None => out.push_str("cwd: (inherited)\n"),
+        }
+
+        let (added, changed, removed) = self.env_diff();
+        out.push_str("environment:\n");
+        for &(ref key, ref val) in added.iter() {
+            out.push_str(&format!("    + {}={}\n", key, val.to_string_lossy()));
+        }
+        for &(ref key, ref val) in changed.iter() {
+            out.push_str(&format!("    ~ {}={}\n", key, val.to_string_lossy()));
+        }
+        for key in removed.iter() {
+            out.push_str(&format!("    - {} (unset)\n", key));
+        }
+        out
+    }
+
+    /// Sets a deadline for the process to finish by.
+    ///
+    /// If the process is still running once `dur` has elapsed it is killed and
+    /// the exec methods return an error saying that it timed out. By default
+    /// there is no timeout.
+    pub fn timeout(&mut self, dur: Duration) -> &mut ProcessBuilder {
+        self.timeout = Some(dur);
+        self
+    }
+
+    /// Makes the process cancellable through `flag`.
+    ///
+    /// Once `flag` is set the process is killed, and the exec methods return
+    /// an error with its `cancelled` field set rather than one about the way
+    /// it exited. The flag is checked every `CANCEL_POLL_MS` milliseconds
+    /// until the process exits, and just like timeouts it's ignored for
+    /// processes started with `spawn`.
+    pub fn cancel_on(&mut self, flag: Arc<AtomicBool>) -> &mut ProcessBuilder {
+        self.cancel = Some(Ignored(flag));
+        self
+    }
+
+    /// Sets the signal sent to the process once its timeout has passed.
+    ///
+    /// The default is `SIGKILL`. Any other signal gives the process a chance
+    /// to clean up after itself, and if it hasn't exited a few seconds later
+    /// it's sent `SIGKILL` after all. This is only available on Unix.
+    #[cfg(unix)]
+    pub fn timeout_signal(&mut self, sig: i32) -> &mut ProcessBuilder {
+        self.timeout_signal = Some(sig);
+        self
+    }
+
+    /// Configures whether a timeout kills the entire process group of the
+    /// child rather than just the child itself.
+    ///
+    /// When enabled the child is started in a new process group of its own,
+    /// as if by `new_process_group`, so any processes it creates are killed
+    /// along with it. This only has an effect on Unix.
+    pub fn kill_process_group(&mut self, kill: bool) -> &mut ProcessBuilder {
+        self.kill_process_group = kill;
+        self
+    }
+
+    /// Configures whether the child is started as the leader of a new process
+    /// group.
+    ///
+    /// Signals sent to the process group of cargo, such as the one generated
+    /// by Ctrl-C in a terminal, are then no longer delivered to the child. On
+    /// Windows this creates the process with `CREATE_NEW_PROCESS_GROUP`.
+    pub fn new_process_group(&mut self, yes: bool) -> &mut ProcessBuilder {
+        self.new_process_group = yes;
+        self
+    }
+
+    /// Configures whether the process is started as a daemon, which isn't tied
+    /// to cargo and keeps running after cargo exits.
+    ///
+    /// `exec` then returns as soon as the process is started, without waiting
+    /// for it or checking how it exits, and `exec_detached` also returns its
+    /// pid. The stdio of the process is connected to the null device, except
+    /// for input given through `stdin_data` or `stdin_from_file` and output
+    /// redirected elsewhere. On Unix the process is started in a new session,
+    /// without a controlling terminal, while on Windows it's created with
+    /// `DETACHED_PROCESS` so that it doesn't share the console of cargo.
+    pub fn detach(&mut self, yes: bool) -> &mut ProcessBuilder {
+        self.detach = yes;
+        self
+    }
+
+    /// Sets how many more times `exec_with_retries` may run the process after
+    /// its first attempt fails. Defaults to zero.
+    pub fn retries(&mut self, retries: u32) -> &mut ProcessBuilder {
+        self.retries = retries;
+        self
+    }
+
+    /// Sets the exit codes which the exec methods consider successful, for
+    /// tools which use some non-zero codes to report benign outcomes.
+    ///
+    /// A process exiting with any other code, or terminated by a signal,
+    /// fails as usual. By default only an exit code of zero is successful.
+    pub fn success_codes(&mut self, codes: &[i32]) -> &mut ProcessBuilder {
+        self.success_codes = codes.to_vec();
+        self
+    }
+
+    /// Sets how long `exec_with_retries` waits between attempts. By default
+    /// the process is retried immediately.
+    pub fn retry_backoff(&mut self, dur: Duration) -> &mut ProcessBuilder {
+        self.retry_backoff = Some(dur);
+        self
+    }
+
+    /// Runs the process as the user with the given id.
+    ///
+    /// This is only available on Unix, and generally requires cargo itself to
+    /// be running with elevated privileges.
+    #[cfg(unix)]
+    pub fn uid(&mut self, uid: u32) -> &mut ProcessBuilder {
+        self.uid = Some(uid);
+        self
+    }
+
+    /// Runs the process as a member of the group with the given id.
+    ///
+    /// This is only available on Unix, and generally requires cargo itself to
+    /// be running with elevated privileges.
+    #[cfg(unix)]
+    pub fn gid(&mut self, gid: u32) -> &mut ProcessBuilder {
+        self.gid = Some(gid);
+        self
+    }
+
+    /// Adds `increment` to the niceness of the process, as if by `nice(1)`,
+    /// so positive values lower its scheduling priority.
+    ///
+    /// This is only available on Unix. Adjusting the priority is best-effort,
+    /// the process is still run if it fails.
+    #[cfg(unix)]
+    pub fn nice(&mut self, increment: i32) -> &mut ProcessBuilder {
+        self.nice = Some(increment);
+        self
+    }
+
+    /// Sets the file mode creation mask of the process, as if by `umask(1)`.
+    ///
+    /// The mask only applies to the process and the processes it starts in
+    /// turn, the umask of cargo itself is left alone. This is only available
+    /// on Unix. By default the mask is inherited from cargo.
+    #[cfg(unix)]
+    pub fn umask(&mut self, mask: u32) -> &mut ProcessBuilder {
+        self.umask = Some(mask);
+        self
+    }
+
+    /// Configures whether the process ignores `SIGINT`, so that a Ctrl-C in
+    /// the terminal only reaches cargo and the process can finish what it's
+    /// doing.
+    ///
+    /// Processes the child starts in turn inherit this. This is only
+    /// available on Unix. By default `SIGINT` is handled as usual.
+    #[cfg(unix)]
+    pub fn ignore_sigint(&mut self, yes: bool) -> &mut ProcessBuilder {
+        self.ignore_sigint = yes;
+        self
+    }
+
+    /// Limits the CPU time the process may use to `secs` seconds, after which
+    /// the kernel terminates it with `SIGXCPU`.
+    ///
+    /// Unlike `timeout` this counts the time the process spends running
+    /// rather than waiting, and is enforced by the kernel itself. Processes
+    /// the child starts in turn get the same limit each. The limit can't be
+    /// raised above the hard limit cargo runs under. This is only available
+    /// on Unix. By default the limit is inherited from cargo.
+    #[cfg(unix)]
+    pub fn cpu_time_limit(&mut self, secs: u64) -> &mut ProcessBuilder {
+        self.cpu_time_limit = Some(secs);
+        self
+    }
+
+    /// Limits the size of the files the process may write to `bytes`, beyond
+    /// which the kernel terminates it with `SIGXFSZ`.
+    ///
+    /// This guards against processes such as build scripts filling up the
+    /// disk. The limit applies to each file on its own, and to the processes
+    /// the child starts in turn. As with `cpu_time_limit` it can't be raised
+    /// above the hard limit cargo runs under, and it's only available on
+    /// Unix. By default the limit is inherited from cargo.
+    #[cfg(unix)]
+    pub fn file_size_limit(&mut self, bytes: u64) -> &mut ProcessBuilder {
+        self.file_size_limit = Some(bytes);
+        self
+    }
+
+    /// Confines the process to `root`, which it then sees as `/`.
+    ///
+    /// The root directory is changed right before the program is executed, so
+    /// the program itself, the libraries it needs and the working directory
+    /// are all looked up inside of `root`, with a relative working directory
+    /// taken relative to `root`. Changing the root directory requires
+    /// privileges, `CAP_SYS_CHROOT` in particular, which the process no longer
+    /// has when it's also given a `uid`. Failing to change it makes spawning
+    /// the process fail. This is only available on Linux. By default the
+    /// process isn't confined.
+    #[cfg(target_os = "linux")]
+    pub fn chroot(&mut self, root: &Path) -> &mut ProcessBuilder {
+        self.chroot = Some(root.to_path_buf());
+        self
+    }
+
+    /// Sets the name the process shows up as in `ps` and `top`, such as
+    /// `rustc:serde`, rather than the name of its program.
+    ///
+    /// The kernel keeps at most 15 bytes of the name, so `name` is truncated
+    /// to that, and any `/` in it is replaced by `_`. The kernel resets the
+    /// name from the program on exec, so the program is run through a
+    /// temporary symlink called `name`, while still seeing its usual `argv[0]`.
+    /// The name is left alone when the process is also confined by `chroot`.
+    /// This is only available on Linux. By default the process is named after
+    /// its program.
+    #[cfg(target_os = "linux")]
+    pub fn set_comm(&mut self, name: &str) -> &mut ProcessBuilder {
+        let mut end = cmp::min(name.len(), 15);
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.comm = Some(name[..end].replace("/", "_"));
+        self
+    }
+
+    /// Restricts the system calls the process may make to those allowed by
+    /// `profile`, through a seccomp filter installed right before the program
+    /// is executed.
+    ///
+    /// Processes the child starts in turn are bound by the same filter, and
+    /// can't lift it. This is only available on Linux, for the architectures
+    /// whose system call numbers are known. By default the process is
+    /// unconstrained.
+    #[cfg(all(target_os = "linux",
+                any(target_arch = "x86_64", target_arch = "aarch64")))]
+    pub fn seccomp_profile(&mut self, profile: SeccompProfile)
+                           -> &mut ProcessBuilder {
+        self.seccomp_profile = Some(profile);
+        self
+    }
+
+    /// Configures whether methods which capture the output of the process
+    /// connect it to a pseudo-terminal rather than to pipes.
+    ///
+    /// Tools which check whether they're attached to a terminal then behave as
+    /// they would interactively. Everything written to the terminal, with its
+    /// line endings translated to `\r\n`, is captured as stdout, and streams
+    /// redirected to a file are left alone. This is only available on Unix.
+    #[cfg(unix)]
+    pub fn allocate_pty(&mut self, yes: bool) -> &mut ProcessBuilder {
+        self.pty = yes;
+        self
+    }
+
+    /// Presents `name` to the process as its `argv[0]`, while still running
+    /// the program configured on the builder.
+    ///
+    /// This is for tools which behave differently depending on the name they
+    /// are invoked as. It's only available on Unix, as Windows has no notion
+    /// of `argv[0]` separate from the command line.
+    #[cfg(unix)]
+    pub fn arg0(&mut self, name: OsString) -> &mut ProcessBuilder {
+        self.arg0 = Some(name);
+        self
+    }
+
+    /// Passes `flags` to `CreateProcess` when the process is spawned, such as
+    /// `CREATE_NO_WINDOW` (`0x08000000`) to keep a console application from
+    /// opening a window, `CREATE_NEW_CONSOLE` (`0x00000010`) to give it a
+    /// console of its own, or `BELOW_NORMAL_PRIORITY_CLASS` (`0x00004000`) and
+    /// `ABOVE_NORMAL_PRIORITY_CLASS` (`0x00008000`) to change its priority.
+    ///
+    /// The flags implied by `new_process_group` and `detach` are added to
+    /// these. This is only available on Windows. By default no flags are
+    /// passed beyond those.
+    #[cfg(windows)]
+    pub fn creation_flags(&mut self, flags: u32) -> &mut ProcessBuilder {
+        self.creation_flags = flags;
+        self
+    }
+
+    /// Makes the descriptor `source` available to the process as `child_fd`.
+    ///
+    /// The descriptor is duplicated onto `child_fd` right before the program
+    /// is executed, so `source` has to stay open until the process has been
+    /// spawned. Every other descriptor keeps its close-on-exec flag as is.
+    /// Descriptors are installed in the order they were added, so a
+    /// `child_fd` shouldn't also be the `source` of a later call. This is
+    /// only available on Unix, where descriptor numbers are part of the
+    /// protocol between processes.
+    #[cfg(unix)]
+    pub fn inherit_fd(&mut self, child_fd: i32, source: RawFd)
+                      -> &mut ProcessBuilder {
+        self.inherit_fds.push((child_fd, source));
+        self
+    }
+
+    /// Relays the given signals to the process for as long as it runs.
+    ///
+    /// While one of the exec methods waits for the process, receiving one of
+    /// `signals` sends it on to the process, or to its whole group if it was
+    /// started in a `new_process_group` or with `kill_process_group`. The
+    /// handlers the signals had before are put back once the process has
+    /// exited. Handlers are global, so only one process forwards signals at a
+    /// time, and a process started while another one is forwarding them runs
+    /// without. This is only available on Unix. By default no signals are
+    /// forwarded.
+    #[cfg(unix)]
+    pub fn forward_signals(&mut self, signals: &[i32]) -> &mut ProcessBuilder {
+        self.forward_signals = signals.to_vec();
+        self
+    }
+
+    /// Limits how much output is captured from the process.
+    ///
+    /// Once the stdout and stderr of the process together exceed `limit` bytes
+    /// the process is killed, and the error returned by the exec methods has
+    /// its `truncated` field set to the number of bytes that were dropped.
+    /// By default all output is captured.
+    pub fn max_output_bytes(&mut self, limit: usize) -> &mut ProcessBuilder {
+        self.max_output_bytes = Some(limit);
+        self
+    }
+
+    /// Configures whether the methods which capture the output of the process
+    /// return its stdout as stderr and its stderr as stdout.
+    ///
+    /// This is for tools which print their actual output on stderr and their
+    /// diagnostics on stdout, so that callers can read what they're after
+    /// from `Output::stdout` as with any other tool. Errors include the
+    /// swapped streams as well, while callbacks which are given the output as
+    /// it's read see the streams as they are. By default nothing is swapped.
+    pub fn swap_stdio(&mut self, yes: bool) -> &mut ProcessBuilder {
+        self.swap_stdio = yes;
+        self
+    }
+
+    /// Configures whether the stderr of the process is the same as its stdout,
+    /// as with `2>&1` in a shell.
+    ///
+    /// Unlike `exec_with_combined_output`, this happens at the level of the
+    /// operating system, so the process itself only has a single stream to
+    /// write to, which matters to tools that behave differently when stderr
+    /// is a separate pipe. Methods which capture output return all of it as
+    /// stdout, and an empty stderr. On Windows this only applies to methods
+    /// which capture output. By default the streams are kept apart.
+    pub fn redirect_stderr_to_stdout(&mut self, yes: bool)
+                                     -> &mut ProcessBuilder {
+        self.redirect_stderr_to_stdout = yes;
+        self
+    }
+
+    /// Sets how the methods returning the output of the process as strings,
+    /// such as `exec_with_output_trimmed`, decode it.
+    ///
+    /// This is for Windows tools which print in the active code page or in
+    /// UTF-16 rather than in UTF-8. Unix has no code pages, so there those
+    /// encodings are decoded as UTF-8. Output which doesn't decode is still an
+    /// error. The default is `Encoding::Utf8`.
+    pub fn output_encoding(&mut self, enc: Encoding) -> &mut ProcessBuilder {
+        self.output_encoding = enc;
+        self
+    }
+
+    /// Configures whether the stdout and stderr of the process are discarded.
+    ///
+    /// Silent processes write their output to the null device, so it neither
+    /// reaches the terminal nor gets captured, and methods which capture
+    /// output return it empty. Failures are still reported as usual. Streams
+    /// redirected to a file through `stdout_to_file` or `stderr_to_file` are
+    /// left alone. By default output isn't discarded.
+    pub fn silent(&mut self, yes: bool) -> &mut ProcessBuilder {
+        self.silent = yes;
+        self
+    }
+
+    /// Configures whether long argument lists are passed to the process
+    /// through an argfile.
+    ///
+    /// When enabled and the arguments are longer than `ARGFILE_THRESHOLD`
+    /// bytes, the exec methods and `spawn` write them to a temporary file, one
+    /// per line, and pass the process a single `@path` argument instead, as
+    /// understood by tools such as `rustc` and linkers. The file is removed
+    /// once the process exits. This doesn't affect `build_command`. By
+    /// default arguments are always passed directly.
+    pub fn use_argfile(&mut self, yes: bool) -> &mut ProcessBuilder {
+        self.use_argfile = yes;
+        self
+    }
+
+    /// Makes the exec methods and `spawn` check that the arguments at the
+    /// given positions name paths which exist, before starting the process.
+    ///
+    /// Positions count from zero and don't include the program. Relative paths
+    /// are taken relative to the working directory of the process. A missing
+    /// path is an error naming it, rather than whatever the process would make
+    /// of it, and positions past the end of the arguments are ignored. By
+    /// default arguments aren't checked.
+    pub fn validate_path_args(&mut self, indices: &[usize])
+                              -> &mut ProcessBuilder {
+        self.path_args.push_all(indices);
+        self
+    }
+
+    /// Passes `secret` to the process through a file, as the arguments `flag`
+    /// and the path to the file, for tools with options like
+    /// `--password-file`.
+    ///
+    /// This keeps secrets out of the command line, where anyone can see them,
+    /// and out of the environment, which is inherited further. Each time the
+    /// process is spawned the secret is written to a new temporary file,
+    /// which only the current user can read on Unix, and the file is removed
+    /// again once the process exits or fails to start. These arguments come
+    /// after all the others, and aren't included in the description of the
+    /// process. This doesn't affect `build_command`.
+    pub fn secret_arg(&mut self, flag: &str, secret: &[u8])
+                      -> &mut ProcessBuilder {
+        self.secret_args.push((OsString::from_str(flag), secret.to_vec()));
+        self
+    }
+
+    /// Configures whether the process is only pretended to be run.
+    ///
+    /// In dry run mode the exec methods log the command instead of running
+    /// it, and then behave as if it exited successfully without producing any
+    /// output. By default processes are run.
+    pub fn dry_run(&mut self, yes: bool) -> &mut ProcessBuilder {
+        self.dry_run = yes;
+        self
+    }
+
+    /// Registers a callback which is handed the builder right before the
+    /// process is spawned by any of the exec methods or `spawn`.
+    ///
+    /// This replaces any callback registered previously. It's intended for
+    /// logging or recording every command which is run.
+    pub fn on_exec(&mut self, cb: Box<Fn(&ProcessBuilder) + Send + Sync>)
+                   -> &mut ProcessBuilder {
+        self.on_exec = Some(Ignored(Arc::new(cb)));
+        self
+    }
+
+    /// Registers a callback which is handed the pid of the process right
+    /// after it has been spawned by any of the exec methods or `spawn`.
+    ///
+    /// This replaces any callback registered previously, and the callback
+    /// isn't called for processes which fail to spawn.
+    pub fn on_spawn(&mut self, cb: Box<Fn(u32) + Send + Sync>)
+                    -> &mut ProcessBuilder {
+        self.on_spawn = Some(Ignored(Arc::new(cb)));
+        self
+    }
+
+    /// Makes the exec methods add an entry describing the process to
+    /// `recorder` each time it has run.
+    ///
+    /// The entry is a `RecordedCommand`, holding the program, arguments,
+    /// working directory, the changes to the environment as reported by
+    /// `env_diff`, and how the process exited and how long it took. Processes
+    /// which fail to spawn, run in dry run mode or are started through
+    /// `spawn` aren't recorded, just like with `last_status`. The same
+    /// recorder may be shared by any number of builders, including ones
+    /// running at the same time.
+    pub fn record_to(&mut self, recorder: Arc<CommandRecorder>)
+                     -> &mut ProcessBuilder {
+        self.recorder = Some(Ignored(recorder));
+        self
+    }
+
+    /// Registers a callback which gets to adjust the `Command` built for the
+    /// process, for settings which the builder doesn't expose itself.
+    ///
+    /// The callback runs last, once `build_command` has applied the program,
+    /// arguments, environment, working directory and every other setting, so
+    /// it can override any of them. The only exception is stdio, which the
+    /// exec methods may still redirect afterwards to capture output. Anything
+    /// the callback changes is invisible to the builder, and so isn't
+    /// reflected by methods such as `get_env`. This replaces any callback
+    /// registered previously.
+    pub fn configure_command(&mut self, f: Box<Fn(&mut Command) + Send + Sync>)
+                             -> &mut ProcessBuilder {
+        self.command_hook = Some(Ignored(Arc::new(f)));
+        self
+    }
+
+    /// Registers a callback which is called every so often while one of the
+    /// exec methods waits for the process, with how long it has been running.
+    ///
+    /// This is meant for showing progress for processes which run for a long
+    /// time without printing anything. The callback is called from another
+    /// thread, every `progress_interval`, and never again once the process has
+    /// exited. This replaces any callback registered previously, and isn't
+    /// used by `spawn`.
+    pub fn progress_tick(&mut self, cb: Box<Fn(Duration) + Send + Sync>)
+                         -> &mut ProcessBuilder {
+        self.progress_tick = Some(Ignored(Arc::new(cb)));
+        self
+    }
+
+    /// Sets how often the callback given to `progress_tick` is called.
+    /// Defaults to every `PROGRESS_INTERVAL_MS` milliseconds.
+    pub fn progress_interval(&mut self, dur: Duration) -> &mut ProcessBuilder {
+        self.progress_interval = Some(dur);
+        self
+    }
+
+    /// Limits the callbacks of `exec_with_streaming` to `max_per_sec` lines
+    /// of each stream a second, so that tools printing endless progress don't
+    /// flood the log.
+    ///
+    /// Lines past the limit are dropped, and the callback is instead told how
+    /// many were once the second is over, with a line reading `N lines
+    /// suppressed`. The returned `Output` still has every line. A limit of
+    /// zero is treated as one. By default lines aren't limited.
+    pub fn throttle_lines(&mut self, max_per_sec: u32) -> &mut ProcessBuilder {
+        self.line_rate = Some(cmp::max(max_per_sec, 1));
+        self
+    }
+
+    /// Makes the exec methods describe failures with the message returned by
+    /// `f`, instead of the default one.
+    ///
+    /// The message is used as is, without the exit status or captured output
+    /// that are appended by default, although they're still recorded on the
+    /// error and available to `f` through the context. This replaces any
+    /// formatter registered previously.
+    pub fn error_formatter(&mut self,
+                           f: Box<Fn(&ProcessErrorContext) -> String
+                                  + Send + Sync>)
+                           -> &mut ProcessBuilder {
+        self.error_formatter = Some(Ignored(Arc::new(f)));
+        self
+    }
+
+    /// Makes the exec methods and `spawn` write `Running <command>` to `sink`
+    /// right before the process is spawned.
+    ///
+    /// The command is rendered as in error messages, so a description given
+    /// to `describe` is used instead if there is one. Errors writing to
+    /// `sink` are ignored. By default nothing is written.
+    pub fn verbose(&mut self, sink: Box<Write + Send>) -> &mut ProcessBuilder {
+        self.verbose = Some(Ignored(Arc::new(Mutex::new(sink))));
+        self
+    }
+
+    /// Sets a description of what the process does, such as "fetching from
+    /// the registry", which error messages use instead of the command itself.
+    ///
+    /// The full command is still reported as the cause of such errors, so it
+    /// is shown when running with `--verbose`.
+    pub fn describe(&mut self, text: String) -> &mut ProcessBuilder {
+        self.description = Some(text);
+        self
+    }
+
+    /// Attaches `value` to the builder under `key`, replacing any earlier
+    /// value, for whoever ends up handling the result of the process.
+    ///
+    /// Metadata has no effect on the process itself. It's kept when the
+    /// builder is cloned, but ignored when comparing or fingerprinting it.
+    pub fn set_metadata(&mut self, key: &str, value: String)
+                        -> &mut ProcessBuilder {
+        self.metadata.0.insert(key.to_string(), value);
+        self
+    }
+
+    /// Returns the metadata attached under `key` through `set_metadata`.
+    pub fn get_metadata(&self, key: &str) -> Option<&str> {
+        self.metadata.0.get(key).map(|s| &s[..])
+    }
+
+    /// Only keeps the last `bytes` of each of stdout and stderr when capturing
+    /// the output of the process.
+    ///
+    /// This bounds the memory used for chatty processes whose output is only
+    /// of interest when they fail, which is usually explained at the end. If
+    /// anything was dropped, an error returned by the exec methods has its
+    /// `truncated` field include the number of bytes lost from the start of
+    /// the output. Unlike `max_output_bytes` the process is left to run to
+    /// completion. By default all output is kept.
+    pub fn capture_tail(&mut self, bytes: usize) -> &mut ProcessBuilder {
+        self.capture_tail = Some(bytes);
+        self
+    }
+
+    /// Sets the size of the buffers output is read into when it's captured or
+    /// streamed, trading the number of reads for memory.
+    ///
+    /// Setting this makes `exec_with_output` read the output the same way as
+    /// the streaming methods. Sizes below one byte are treated as one. By
+    /// default the buffers are 8KiB.
+    pub fn read_buffer_size(&mut self, bytes: usize) -> &mut ProcessBuilder {
+        self.read_buffer_size = Some(cmp::max(bytes, 1));
+        self
+    }
+
+    /// Sets a label to prefix each line of output with, as in `[label] line`,
+    /// to tell apart the output of processes running concurrently.
+    ///
+    /// The prefix is added to the lines handed to the callbacks of
+    /// `exec_with_streaming` and to those written by `exec_to_writer`, but
+    /// not to output which is captured. By default lines aren't prefixed.
+    pub fn tag(&mut self, label: String) -> &mut ProcessBuilder {
+        self.tag = Some(label);
+        self
+    }
+
+    /// Sets how many lines from the end of the captured stderr are included
+    /// in the message of errors about the process. Defaults to 20.
+    pub fn stderr_tail_lines(&mut self, lines: usize) -> &mut ProcessBuilder {
+        self.stderr_tail_lines = lines;
+        self
+    }
+
+    /// Configures data to be written to the standard input of the process.
+    ///
+    /// The data is written from a separate thread once the process has been
+    /// spawned, after which its stdin is closed. By default the process
+    /// inherits the stdin of cargo itself.
+    ///
+    /// This replaces any file given to `stdin_from_file`.
+    pub fn stdin_data(&mut self, data: Vec<u8>) -> &mut ProcessBuilder {
+        self.stdin_data = Some(data);
+        self.stdin_file = None;
+        self
+    }
+
+    /// Connects the standard input of the process to the file at `path`, so
+    /// that large inputs don't have to be read into memory for `stdin_data`.
+    ///
+    /// The file is opened when the process is spawned, and failing to open it
+    /// is an error. This replaces any data given to `stdin_data`.
+    pub fn stdin_from_file(&mut self, path: &Path) -> &mut ProcessBuilder {
+        self.stdin_file = Some(path.to_path_buf());
+        self.stdin_data = None;
+        self
+    }
+
+    /// Configures whether the stdin of the process is connected to the null
+    /// device, so that it reads EOF right away instead of waiting for input
+    /// that never comes.
+    ///
+    /// This has no effect if stdin is given through `stdin_data` or
+    /// `stdin_from_file`. By default the process inherits the stdin of cargo
+    /// itself.
+    pub fn close_stdin(&mut self, yes: bool) -> &mut ProcessBuilder {
+        self.close_stdin = yes;
+        self
+    }
+
+    /// Configures whether `exec` also captures the output it passes on to the
+    /// terminal, to include it in the error if the process fails.
+    ///
+    /// The output is forwarded to the stdout and stderr of cargo a line at a
+    /// time as it's read, and is discarded once the process succeeds. Use
+    /// `capture_tail` to bound how much of it is kept in the meantime. By
+    /// default the output of `exec` isn't captured.
+    pub fn capture_on_failure(&mut self, yes: bool) -> &mut ProcessBuilder {
+        self.capture_on_failure = yes;
+        self
+    }
+
+    /// Configures whether `exec` holds back the output of the process until it
+    /// has exited, only passing it on to the terminal if the process fails.
+    ///
+    /// This keeps the console quiet while processes succeed, such as when
+    /// many of them run in parallel, while still showing everything when one
+    /// of them fails. The output is replayed to the stdout and stderr of cargo
+    /// line by line in the order it was read, which is a best-effort
+    /// approximation of the order in which it was written, before the error
+    /// is returned. The error includes the output as with
+    /// `capture_on_failure`, which this takes precedence over. By default the
+    /// output of `exec` is passed on as it's written.
+    pub fn replay_on_failure(&mut self, yes: bool) -> &mut ProcessBuilder {
+        self.replay_on_failure = yes;
+        self
+    }
+}
+
+/// An iterator over the arguments of a process, as returned by
+/// `ProcessBuilder::args_lossy`.
+pub struct ArgsLossy<'a> {
+    iter: slice::Iter<'a, OsString>,
+}
+
+impl<'a> Iterator for ArgsLossy<'a> {
+    type Item = Cow<'a, str>;
+    fn next(&mut self) -> Option<Cow<'a, str>> {
+        self.iter.next().map(|arg| arg.to_string_lossy())
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.iter.size_hint() }
+}
+
+/// A set of system calls to deny a process, as applied by `seccomp_profile`.
+///
+/// Denied calls fail with `EPERM` rather than killing the process, so that it
+/// can report what went wrong.
+#[cfg(all(target_os = "linux",
+            any(target_arch = "x86_64", target_arch = "aarch64")))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SeccompProfile {
+    /// Denies opening sockets other than Unix domain sockets, which cuts the
+    /// process off from the network.
+    DenyNetwork,
+    /// Denies creating new processes through `fork`, `vfork` and `clone`, so
+    /// that the process can't run anything alongside it. Threads can still be
+    /// created.
+    ///
+    /// This doesn't stop the process from replacing itself with another
+    /// program through `execve`, as the filter has to be installed before the
+    /// program is executed and would otherwise deny that too.
+    DenyNewProcesses,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::ffi::{OsStr, OsString};
+    use std::fs::File;
+    use std::io::prelude::*;
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+
+    use tempdir::TempDir;
+
+    use util::process::tests::shell;
+    use util::process::{Encoding, ProcessBuilder, process, run_parallel};
+
+    #[cfg(unix)]
+    #[test]
+    fn errors_show_the_end_of_stderr() {
+        let script = "for i in $(seq 1 30); do echo \"line $i.\" >&2; done; \
+                      exit 1";
+        let err = shell(script).exec_with_output().unwrap_err();
+        assert!(err.desc.contains("(10 earlier lines omitted)"), "{}", err);
+        assert!(err.desc.contains("line 11.\n"), "{}", err);
+        assert!(err.desc.contains("line 30.\n"), "{}", err);
+        assert!(!err.desc.contains("line 10."), "{}", err);
+
+        let mut p = shell(script);
+        p.stderr_tail_lines(5);
+        let err = p.exec_with_output().unwrap_err();
+        assert!(err.desc.contains("line 26.\n"), "{}", err);
+        assert!(!err.desc.contains("line 25."), "{}", err);
+    }
+
+    #[test]
+    fn describe_replaces_the_command_in_errors() {
+        use std::error::Error;
+
+        let err = shell("exit 1").exec().unwrap_err();
+        assert!(err.desc.starts_with("Process didn't exit successfully: `"),
+                "{}", err);
+        assert!(err.cause().is_none());
+
+        let mut p = shell("exit 1");
+        p.describe("doing something".to_string());
+        let err = p.exec().unwrap_err();
+        assert!(err.desc.starts_with("Process didn't exit successfully: \
+                                      doing something ("), "{}", err);
+        let cause = err.cause().unwrap().to_string();
+        assert_eq!(cause, format!("`{}`", p.debug_string()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn uid_is_dropped() {
+        use libc;
+
+        // Switching users requires root, so there's nothing to test otherwise.
+        if unsafe { libc::getuid() } != 0 { return }
+
+        let mut p = process("id");
+        p.arg("-u").uid(65534).gid(65534);
+        let output = p.exec_with_output().unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap().trim(), "65534");
+    }
+
+    #[test]
+    fn tag_prefixes_every_line() {
+        let mut p = shell("echo out1; echo err1 >&2; echo out2");
+        p.tag("member-a".to_string());
+
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        p.exec_to_writer(&mut out, &mut err).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(),
+                   "[member-a] out1\n[member-a] out2\n");
+        assert_eq!(String::from_utf8(err).unwrap(), "[member-a] err1\n");
+
+        let mut lines = Vec::new();
+        let output = p.exec_with_streaming(|line| lines.push(line.to_string()),
+                                           |_| {}).unwrap();
+        assert_eq!(lines, ["[member-a] out1", "[member-a] out2"]);
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "out1\nout2\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn arg0_is_seen_by_the_process() {
+        // A shell reading commands from stdin reports its argv[0] as `$0`.
+        let mut p = process("sh");
+        p.arg0(OsString::from_str("my-shell")).stdin_data(b"echo $0".to_vec());
+        let output = p.exec_with_output().unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "my-shell\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn allocate_pty_looks_like_a_terminal() {
+        let script = "test -t 0 && test -t 1 && test -t 2 && echo tty";
+        assert!(shell(script).exec_with_output().is_err());
+
+        let mut p = shell(script);
+        p.allocate_pty(true);
+        let output = p.exec_with_output().unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap().trim(), "tty");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn timeout_signal_lets_the_process_clean_up() {
+        use libc;
+        use std::time::Duration;
+
+        let mut p = shell("trap 'echo cleaned up; exit 0' TERM; \
+                           sleep 10 & wait");
+        p.timeout(Duration::milliseconds(500)).kill_process_group(true)
+         .timeout_signal(libc::SIGTERM);
+        let err = p.exec_with_output().unwrap_err();
+        assert!(err.desc.contains("timed out"), "{}", err);
+        assert_eq!(err.code(), Some(0));
+        let output = err.output.unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "cleaned up\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cancel_on_kills_the_process() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::thread;
+        use std::time::Duration;
+        use util::process::watchdog::sleep;
+        use time;
+
+        let flag = Arc::new(AtomicBool::new(false));
+        let flag2 = flag.clone();
+        thread::spawn(move || {
+            sleep(Duration::milliseconds(200));
+            flag2.store(true, Ordering::SeqCst);
+        });
+
+        let start = time::precise_time_ns();
+        let err = shell("sleep 10").cancel_on(flag).exec().unwrap_err();
+        let elapsed = time::precise_time_ns() - start;
+        assert!(err.cancelled, "{}", err);
+        assert!(err.desc.contains("cancelled"), "{}", err);
+        assert!(elapsed < 5_000_000_000, "took {}ns", elapsed);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn inherit_fd_passes_a_pipe() {
+        use libc;
+
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        let msg = b"through the pipe";
+        unsafe {
+            let ptr = msg.as_ptr() as *const libc::c_void;
+            libc::write(fds[1], ptr, msg.len() as libc::size_t);
+            libc::close(fds[1]);
+        }
+
+        let mut p = shell("cat <&7");
+        p.inherit_fd(7, fds[0]);
+        let output = p.exec_with_output();
+        unsafe { libc::close(fds[0]); }
+        assert_eq!(output.unwrap().stdout, msg);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn umask_applies_to_created_files() {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let td = TempDir::new("cargo").unwrap();
+        let mut p = shell("touch file");
+        p.cwd(td.path()).umask(0o077);
+        p.exec().unwrap();
+        let mode = fs::metadata(&td.path().join("file")).unwrap()
+                      .permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn chroot_confines_the_process() {
+        use libc;
+        use std::fs;
+
+        let td = TempDir::new("cargo").unwrap();
+        let mut p = shell("pwd; [ -e /proc/self ] || echo confined");
+        p.chroot(td.path()).cwd("sub");
+        if unsafe { libc::geteuid() } != 0 {
+            let err = p.exec_with_output().unwrap_err();
+            assert!(err.did_not_start());
+            assert!(err.desc.contains("inside of"), "{}", err);
+            return
+        }
+
+        // Give the root a copy of `sh` along with the libraries it needs.
+        let ldd = process("ldd").arg("/bin/sh").exec_with_output().unwrap();
+        let ldd = String::from_utf8(ldd.stdout).unwrap();
+        let libs = ldd.words().filter(|w| w.starts_with("/"));
+        for file in Some("/bin/sh").into_iter().chain(libs) {
+            let dst = td.path().join(&file[1..]);
+            fs::create_dir_all(dst.parent().unwrap()).unwrap();
+            fs::copy(file, &dst).unwrap();
+        }
+        fs::create_dir(&td.path().join("sub")).unwrap();
+
+        let output = p.exec_with_output().unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "/sub\nconfined\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn secret_arg_passes_a_private_file() {
+        use std::fs;
+
+        let mut p = shell("ls -l \"$1\"; echo \"$0 $1\"; cat \"$1\"");
+        p.secret_arg("--password-file", b"hunter2");
+        let output = p.exec_with_output().unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let lines = stdout.lines_any().collect::<Vec<_>>();
+        assert!(lines[0].starts_with("-rw-------"), "{}", lines[0]);
+        let (flag, path) = lines[1].split_at(lines[1].find(' ').unwrap());
+        assert_eq!(flag, "--password-file");
+        assert_eq!(lines[2], "hunter2");
+        assert!(fs::metadata(path.trim()).is_err());
+        assert!(!p.to_string().contains("--password-file"));
+
+        // The file doesn't stick around if the process can't be started.
+        let mut p = process("cargo-no-such-program");
+        p.secret_arg("--password-file", b"hunter2");
+        let count = || {
+            fs::read_dir(&env::temp_dir()).unwrap().filter(|e| {
+                e.as_ref().unwrap().path().to_string_lossy()
+                 .contains("cargo-secret-")
+            }).count()
+        };
+        let before = count();
+        assert!(p.exec().is_err());
+        assert_eq!(count(), before);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn detached_processes_outlive_exec() {
+        use libc;
+        use std::fs;
+        use std::time::Duration;
+        use util::process::watchdog::sleep;
+
+        let td = TempDir::new("cargo").unwrap();
+        let marker = td.path().join("done");
+        let mut p = shell("sleep 1; touch done");
+        p.cwd(td.path()).detach(true);
+        p.exec().unwrap();
+        assert!(fs::metadata(&marker).is_err());
+        for _ in 0..100 {
+            if fs::metadata(&marker).is_ok() { break }
+            sleep(Duration::milliseconds(50));
+        }
+        assert!(fs::metadata(&marker).is_ok());
+
+        let pid = shell("sleep 10").exec_detached().unwrap() as libc::pid_t;
+        assert_eq!(unsafe { libc::kill(pid, 0) }, 0);
+        unsafe { libc::kill(pid, libc::SIGKILL); }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn detached_processes_read_stdin_from_file() {
+        use std::time::Duration;
+        use util::process::watchdog::sleep;
+
+        let td = TempDir::new("cargo").unwrap();
+        let input = td.path().join("input");
+        let copy = td.path().join("copy");
+        File::create(&input).unwrap().write_all(b"hello\n").unwrap();
+        let mut p = shell("cat > copy.tmp && mv copy.tmp copy");
+        p.cwd(td.path()).stdin_from_file(&input);
+        p.exec_detached().unwrap();
+
+        let mut contents = String::new();
+        for _ in 0..100 {
+            if let Ok(mut file) = File::open(&copy) {
+                file.read_to_string(&mut contents).unwrap();
+                break
+            }
+            sleep(Duration::milliseconds(50));
+        }
+        assert_eq!(contents, "hello\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ignore_sigint_survives_ctrl_c() {
+        use libc;
+
+        let interrupt = |p: &ProcessBuilder| {
+            let mut handle = p.spawn().unwrap();
+            unsafe { libc::kill(handle.id() as libc::pid_t, libc::SIGINT); }
+            handle.wait().unwrap()
+        };
+
+        let mut p = shell("sleep 1");
+        assert!(!interrupt(&p).success());
+        p.ignore_sigint(true);
+        assert!(interrupt(&p).success());
+    }
+
+    #[cfg(all(target_os = "linux",
+                any(target_arch = "x86_64", target_arch = "aarch64")))]
+    #[test]
+    fn seccomp_deny_network_blocks_sockets() {
+        use std::net::TcpListener;
+        use util::SeccompProfile;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut p = process("bash");
+        p.arg("-c").arg(&format!("exec 3<>/dev/tcp/127.0.0.1/{}", port));
+        p.exec_with_output().unwrap();
+
+        p.seccomp_profile(SeccompProfile::DenyNetwork);
+        let err = p.exec_with_output().unwrap_err();
+        let stderr = String::from_utf8_lossy(err.raw_stderr().unwrap());
+        assert!(stderr.contains("Operation not permitted"), "{}", stderr);
+    }
+
+    #[cfg(all(target_os = "linux",
+                any(target_arch = "x86_64", target_arch = "aarch64")))]
+    #[test]
+    fn seccomp_deny_new_processes_blocks_forks() {
+        use util::SeccompProfile;
+
+        let mut p = shell("true | true");
+        p.exec_with_output().unwrap();
+        p.seccomp_profile(SeccompProfile::DenyNewProcesses);
+        let err = p.exec_with_output().unwrap_err();
+        let stderr = String::from_utf8_lossy(err.raw_stderr().unwrap());
+        assert!(stderr.contains("fork"), "{}", stderr);
+
+        // Replacing the process with another program is still allowed.
+        let mut p = shell("exec true");
+        p.seccomp_profile(SeccompProfile::DenyNewProcesses);
+        p.exec_with_output().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cpu_time_limit_stops_busy_processes() {
+        use libc;
+        use std::time::Duration;
+
+        let mut p = shell("while :; do :; done");
+        p.cpu_time_limit(1).timeout(Duration::seconds(30));
+        let err = p.exec().unwrap_err();
+        assert_eq!(err.signal(), Some(libc::SIGXCPU));
+        assert!(err.desc.starts_with("Process exceeded its CPU time limit"),
+                "{}", err.desc);
+        assert!(err.desc.contains("SIGXCPU"), "{}", err.desc);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_size_limit_stops_large_writes() {
+        use libc;
+
+        let td = TempDir::new("cargo-file-size-limit").unwrap();
+        let mut p = shell("exec head -c 100000 /dev/zero > big");
+        p.cwd(td.path()).file_size_limit(4096);
+        let err = p.exec().unwrap_err();
+        assert_eq!(err.signal(), Some(libc::SIGXFSZ));
+        assert!(err.desc.starts_with("Process wrote a file exceeding its file \
+                                      size limit"), "{}", err.desc);
+        assert!(err.desc.contains("SIGXFSZ"), "{}", err.desc);
+
+        let mut p = shell("exec head -c 1000 /dev/zero > small");
+        p.cwd(td.path()).file_size_limit(4096);
+        p.exec().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn use_argfile_passes_long_argument_lists() {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let td = TempDir::new("cargo").unwrap();
+        let script = td.path().join("print-argfile");
+        File::create(&script).unwrap()
+            .write_all(b"#!/bin/sh\ntest $# -eq 1 && cat \"${1#@}\"\n").unwrap();
+        let mut perms = fs::metadata(&script).unwrap().permissions();
+        perms.set_mode(0o755);
+        fs::set_permissions(&script, perms).unwrap();
+
+        let args = (0..5000).map(|i| format!("--arg{}", i)).collect::<Vec<_>>();
+        let mut p = process(&script);
+        p.args(&args).use_argfile(true);
+        let output = p.exec_with_output().unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(stdout.lines().collect::<Vec<_>>(), args);
+
+        // Short argument lists are still passed directly.
+        let mut p = process(&script);
+        p.arg("--short").use_argfile(true);
+        assert!(p.exec_with_output().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn nice_still_runs_the_process() {
+        let mut p = shell("echo hello");
+        p.nice(5);
+        let output = p.exec_with_output().unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "hello\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn max_output_bytes_truncates_output() {
+        let mut p = shell("echo 0123456789; echo abcdefghij >&2");
+        p.max_output_bytes(15);
+        let err = p.exec_with_output().unwrap_err();
+
+        let output = err.output.unwrap();
+        assert_eq!(output.stdout.len() + output.stderr.len(), 15);
+        assert_eq!(err.truncated, Some(7));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn capture_tail_keeps_the_end_of_the_output() {
+        let script = "i=0; while [ $i -lt 1000 ]; do echo line$i; i=$((i+1)); \
+                      done";
+        let mut p = shell(script);
+        p.capture_tail(16);
+        let output = p.exec_with_output().unwrap();
+        assert_eq!(output.stdout, b"line998\nline999\n");
+
+        let mut p = shell(&format!("{}; exit 1", script));
+        p.capture_tail(16);
+        let err = p.exec_with_output().unwrap_err();
+        assert_eq!(err.truncated, Some(7890 - 16));
+        assert_eq!(err.output.unwrap().stdout, b"line998\nline999\n");
+    }
+
+    #[test]
+    fn max_output_bytes_allows_small_output() {
+        let mut p = shell("echo hello");
+        p.max_output_bytes(1024);
+        let output = p.exec_with_output().unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap().trim(), "hello");
+    }
+
+    #[test]
+    fn dry_run_does_not_execute() {
+        let mut p = process("cargo-no-such-program");
+        assert!(p.exec().is_err());
+
+        p.dry_run(true);
+        assert!(p.exec().is_ok());
+        let output = p.exec_with_output().unwrap();
+        assert!(output.status.success());
+        assert!(output.stdout.is_empty() && output.stderr.is_empty());
+        assert!(p.spawn().is_err());
+    }
+
+    #[test]
+    fn on_exec_sees_every_command() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen2 = seen.clone();
+
+        let mut p = shell("exit 0");
+        p.on_exec(Box::new(move |p: &ProcessBuilder| {
+            seen2.lock().unwrap().push(p.to_string());
+        }));
+        p.exec().unwrap();
+        p.exec_with_output().unwrap();
+        p.spawn().unwrap().wait().unwrap();
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 3);
+        assert!(seen.iter().all(|s| *s == p.to_string()));
+    }
+
+    #[test]
+    fn configure_command_runs_last() {
+        let mut p = shell(if cfg!(windows) {"echo %FOO%"} else {"echo $FOO"});
+        p.env("FOO", "builder");
+        p.configure_command(Box::new(|cmd| { cmd.env("FOO", "hook"); }));
+        let output = p.exec_with_output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "hook");
+        assert_eq!(p.get_env("FOO"), Some(OsString::from_str("builder")));
+    }
+
+    #[test]
+    fn progress_tick_fires_until_exit() {
+        use std::time::Duration;
+        use util::process::watchdog::sleep;
+
+        let ticks = Arc::new(Mutex::new(Vec::new()));
+        let ticks2 = ticks.clone();
+        let mut p = shell(if cfg!(windows) {"ping -n 2 127.0.0.1"}
+                          else {"sleep 1"});
+        p.progress_interval(Duration::milliseconds(100))
+         .progress_tick(Box::new(move |dur| ticks2.lock().unwrap().push(dur)));
+        p.exec_with_output().unwrap();
+
+        let fired = ticks.lock().unwrap().clone();
+        assert!(fired.len() >= 2, "{:?}", fired);
+        assert!(fired.windows(2).all(|w| w[0] < w[1]), "{:?}", fired);
+        sleep(Duration::milliseconds(300));
+        assert_eq!(ticks.lock().unwrap().len(), fired.len());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn error_formatter_replaces_the_message() {
+        let mut p = shell("echo oops >&2; exit 3");
+        p.error_formatter(Box::new(|cx| {
+            format!("[{}] {} exited with {:?}: {}", cx.message,
+                    cx.program.to_string_lossy(),
+                    cx.exit.and_then(|s| s.code()),
+                    String::from_utf8_lossy(&cx.output.unwrap().stderr))
+        }));
+        let err = p.exec_with_output().unwrap_err();
+        assert_eq!(err.desc, "[Process didn't exit successfully: \
+                              `sh -c \"echo oops >&2; exit 3\"`] sh exited \
+                              with Some(3): oops\n");
+        assert_eq!(err.code(), Some(3));
+    }
+
+    #[test]
+    fn on_spawn_sees_the_pid() {
+        let pids = Arc::new(Mutex::new(Vec::new()));
+        let pids2 = pids.clone();
+
+        let mut p = shell("exit 0");
+        p.on_spawn(Box::new(move |pid| pids2.lock().unwrap().push(pid)));
+        p.exec_with_output().unwrap();
+        assert_eq!(pids.lock().unwrap().len(), 1);
+        let first = pids.lock().unwrap()[0];
+        assert!(first != 0);
+
+        let mut handle = p.spawn().unwrap();
+        assert_eq!(*pids.lock().unwrap(), [first, handle.id()]);
+        handle.wait().unwrap();
+
+        assert!(process("cargo-no-such-program").on_spawn(Box::new(|_| {
+            panic!("called for a process which didn't spawn")
+        })).exec().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn close_stdin_gives_eof() {
+        use std::time::Duration;
+
+        let mut p = shell("cat; echo done");
+        p.close_stdin(true).timeout(Duration::seconds(10));
+        let output = p.exec_with_output().unwrap();
+        assert_eq!(output.stdout, b"done\n".to_vec());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn capture_on_failure_keeps_output_for_errors() {
+        let mut p = shell("echo out; echo err >&2; exit 1");
+        p.capture_on_failure(true);
+        let err = p.exec().unwrap_err();
+        let output = err.output.unwrap();
+        assert_eq!(output.stdout, b"out\n".to_vec());
+        assert_eq!(output.stderr, b"err\n".to_vec());
+        assert!(err.desc.contains("--- stderr\nerr\n"), "{}", err.desc);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn read_buffer_size_does_not_change_output() {
+        use std::iter::repeat;
+
+        let p = shell("head -c 100000 /dev/zero | tr '\\0' x; echo; \
+                       i=0; while [ $i -lt 2000 ]; do echo line $i; \
+                       i=$((i + 1)); done; printf end >&2");
+        let mut expected = repeat(b'x').take(100000).collect::<Vec<u8>>();
+        expected.push(b'\n');
+        for i in 0..2000 {
+            expected.push_all(format!("line {}\n", i).as_bytes());
+        }
+
+        for &size in [1, 7, 4096, 1 << 20].iter() {
+            let output = p.clone().read_buffer_size(size).exec_with_output()
+                          .unwrap();
+            assert!(output.stdout == expected, "buffer size {}", size);
+            assert_eq!(output.stderr, b"end".to_vec());
+        }
+    }
+
+    #[test]
+    fn record_to_writes_an_entry_per_run() {
+        use rustc_serialize::json;
+        use util::process::{CommandRecorder, RecordedCommand};
+
+        let td = TempDir::new("cargo").unwrap();
+        let path = td.path().join("commands.json");
+        let recorder = Arc::new(CommandRecorder::to_file(&path).unwrap());
+        let mut ok = shell("exit 0");
+        ok.env("__CARGO_TEST_RECORDED", "1").record_to(recorder.clone());
+        let mut failing = shell("exit 3");
+        failing.record_to(recorder.clone());
+        let results = run_parallel(vec![ok, failing], 2);
+        assert!(results[0].is_ok() && results[1].is_err());
+
+        let mut contents = String::new();
+        File::open(&path).unwrap().read_to_string(&mut contents).unwrap();
+        let mut entries = contents.lines().map(|line| {
+            json::decode::<RecordedCommand>(line).unwrap()
+        }).collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.code.cmp(&b.code));
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].code, Some(0));
+        assert_eq!(entries[0].args.last().unwrap(), "exit 0");
+        assert_eq!(entries[0].env_added.get("__CARGO_TEST_RECORDED"),
+                   Some(&"1".to_string()));
+        assert_eq!(entries[1].code, Some(3));
+        assert_eq!(entries[1].args.last().unwrap(), "exit 3");
+    }
+
+    #[test]
+    fn success_codes_are_not_errors() {
+        let mut p = shell("exit 1");
+        assert!(p.exec().is_err());
+
+        p.success_codes(&[0, 1]);
+        assert!(p.exec().is_ok());
+        assert_eq!(p.exec_with_output().unwrap().status.code(), Some(1));
+        assert!(shell("exit 2").success_codes(&[0, 1]).exec().is_err());
+    }
+
+    #[test]
+    fn stdin_data_is_written() {
+        let mut p = if cfg!(windows) {
+            let mut p = process("findstr");
+            p.arg("^");
+            p
+        } else {
+            process("cat")
+        };
+        p.stdin_data(b"hello\nworld\n".to_vec());
+
+        let output = p.exec_with_output().unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(stdout.replace("\r\n", "\n"), "hello\nworld\n");
+    }
+
+    #[test]
+    fn explain_reports_program_and_environment() {
+        env::set_var("__CARGO_TEST_EXPLAIN_CHANGED", "old");
+        env::set_var("__CARGO_TEST_EXPLAIN_REMOVED", "old");
+
+        let mut p = process("cargo-no-such-program");
+        p.arg("--flag").arg("value")
+         .env("__CARGO_TEST_EXPLAIN_CHANGED", "new")
+         .env_remove("__CARGO_TEST_EXPLAIN_REMOVED");
+        let report = p.explain();
+        assert!(report.contains("program: cargo-no-such-program (not found)\n"),
+                "{}", report);
+        assert!(report.contains("\n    --flag\n    value\n"), "{}", report);
+        assert!(report.contains("~ __CARGO_TEST_EXPLAIN_CHANGED=new\n"),
+                "{}", report);
+        assert!(report.contains("- __CARGO_TEST_EXPLAIN_REMOVED (unset)\n"),
+                "{}", report);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn creation_flags_are_passed_on() {
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+        let mut p = shell("echo windowless");
+        p.creation_flags(CREATE_NO_WINDOW);
+        let output = p.exec_with_output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "windowless");
+    }
+
+    #[test]
+    fn same_command_ignores_cwd() {
+        env::set_var("__CARGO_TEST_SAME_COMMAND", "inherited");
+
+        let mut a = process("rustc");
+        a.arg("-V").cwd("/a").env("__CARGO_TEST_SAME_COMMAND", "inherited");
+        let mut b = process("rustc");
+        b.arg("-V").cwd("/b");
+        assert!(a.same_command_as(&b));
+        assert_eq!(a.command_fingerprint(), b.command_fingerprint());
+
+        b.env("__CARGO_TEST_SAME_COMMAND", "changed");
+        assert!(!a.same_command_as(&b));
+        assert!(a.command_fingerprint() != b.command_fingerprint());
+    }
+
+    #[test]
+    fn fingerprint_with_inputs_covers_args_and_contents() {
+        let td = TempDir::new("cargo").unwrap();
+        let input = td.path().join("input");
+        File::create(&input).unwrap().write_all(b"one").unwrap();
+        let inputs = [input.clone()];
+
+        let mut p = process("rustc");
+        p.arg("-V");
+        let first = p.fingerprint_with_inputs(&inputs).unwrap();
+        assert_eq!(p.fingerprint_with_inputs(&inputs).unwrap(), first);
+
+        p.arg("-v");
+        let with_arg = p.fingerprint_with_inputs(&inputs).unwrap();
+        assert!(with_arg != first);
+
+        File::create(&input).unwrap().write_all(b"two").unwrap();
+        assert!(p.fingerprint_with_inputs(&inputs).unwrap() != with_arg);
+
+        let missing = [td.path().join("missing")];
+        assert!(p.fingerprint_with_inputs(&missing).is_err());
+        assert!(p.fingerprint_with_input_mtimes(&missing).is_err());
+    }
+
+    #[test]
+    fn metadata_travels_with_the_builder() {
+        let mut p = process("rustc");
+        p.arg("-V").set_metadata("crate", "foo".to_string())
+         .set_metadata("phase", "build".to_string())
+         .set_metadata("phase", "doc".to_string());
+        assert_eq!(p.get_metadata("crate"), Some("foo"));
+        assert_eq!(p.get_metadata("phase"), Some("doc"));
+        assert_eq!(p.get_metadata("missing"), None);
+
+        let q = p.clone();
+        assert_eq!(q.get_metadata("crate"), Some("foo"));
+
+        let mut plain = process("rustc");
+        plain.arg("-V");
+        assert!(p == plain);
+        assert_eq!(p.command_fingerprint(), plain.command_fingerprint());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_program_freezes_the_path() {
+        let mut p = process("sh");
+        p.arg("-c").arg("exit 0").env("PATH", "/bin:/usr/bin");
+        p.resolve_program().unwrap();
+        let resolved = p.get_program().clone();
+        assert!(Path::new(&resolved).is_absolute(), "{:?}", resolved);
+        assert!(resolved.to_str().unwrap().ends_with("/sh"));
+
+        p.resolve_program().unwrap();
+        assert_eq!(*p.get_program(), resolved);
+        p.env("PATH", "/nonexistent");
+        p.exec().unwrap();
+
+        let err = process("cargo-no-such-program").resolve_program()
+                                                  .err().unwrap().to_string();
+        assert!(err.contains("could not find executable \
+                              `cargo-no-such-program`"), "{}", err);
+    }
+
+    #[test]
+    fn fallback_program_runs_when_primary_is_missing() {
+        let mut p = shell("echo fallback");
+        let sh = p.get_program().clone();
+        p.set_program(OsString::from_str("cargo-no-such-program"));
+        p.fallback_program(sh);
+        let output = p.exec_with_output().unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap().trim(), "fallback");
+    }
+
+    #[test]
+    fn fallback_program_lists_every_missing_program() {
+        let mut p = process("cargo-no-such-program");
+        p.fallback_program(OsString::from_str("cargo-no-such-fallback"));
+        let err = p.exec().unwrap_err();
+        assert!(err.desc.contains("`cargo-no-such-program`, \
+                                   `cargo-no-such-fallback`"), "{}", err.desc);
+    }
+
+    #[test]
+    fn search_path_reports_missing_programs() {
+        let td = TempDir::new("cargo").unwrap();
+        let paths = env::join_paths([td.path()].iter()).unwrap();
+
+        let mut p = process("cargo-no-such-program");
+        p.search_path(true).env("PATH", &paths);
+        let err = p.exec().unwrap_err();
+        assert!(err.desc.contains("could not find executable"), "{}", err.desc);
+        assert!(err.desc.contains(&td.path().display().to_string()),
+                "{}", err.desc);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn set_comm_names_the_process() {
+        use std::time::Duration;
+        use util::process::watchdog::sleep;
+
+        let mut p = process("sleep");
+        p.arg("10").set_comm("rustc:serde-and-then-some");
+        let handle = p.spawn().unwrap();
+        sleep(Duration::milliseconds(200));
+        let read = |file: &str| {
+            let mut contents = String::new();
+            File::open(&format!("/proc/{}/{}", handle.id(), file)).unwrap()
+                 .read_to_string(&mut contents).unwrap();
+            contents
+        };
+        assert_eq!(read("comm").trim_right(), "rustc:serde-and");
+        assert_eq!(read("cmdline"), "sleep\010\0");
+    }
+
+    #[test]
+    fn stdin_from_file_is_read() {
+        let td = TempDir::new("cargo").unwrap();
+        let path = td.path().join("in");
+        File::create(&path).unwrap().write_all(b"hello\nworld\n").unwrap();
+
+        let mut p = if cfg!(windows) {
+            let mut p = process("findstr");
+            p.arg("^");
+            p
+        } else {
+            process("cat")
+        };
+        p.stdin_data(b"replaced".to_vec()).stdin_from_file(&path);
+        let output = p.exec_with_output().unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(stdout.lines_any().collect::<Vec<_>>(), ["hello", "world"]);
+
+        p.stdin_from_file(&td.path().join("missing"));
+        let err = p.exec_with_output().unwrap_err();
+        assert!(err.to_string().contains("for the stdin of process"), "{}", err);
+    }
+
+    #[test]
+    fn stdout_to_file_writes_output() {
+        let td = TempDir::new("cargo").unwrap();
+        let path = td.path().join("out");
+        File::create(&path).unwrap().write_all(b"old contents").unwrap();
+
+        let mut p = shell("echo hello");
+        p.stdout_to_file(path.clone());
+        let output = p.exec_with_output().unwrap();
+        assert!(output.stdout.is_empty());
+
+        let mut contents = String::new();
+        File::open(&path).unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!(contents.trim(), "hello");
+    }
+
+    #[test]
+    fn silent_discards_output() {
+        let mut p = shell("echo out && echo err >&2");
+        p.silent(true);
+        let output = p.exec_with_output().unwrap();
+        assert!(output.stdout.is_empty());
+        assert!(output.stderr.is_empty());
+
+        p.stdin_data(Vec::new());
+        let output = p.exec_with_output().unwrap();
+        assert!(output.stdout.is_empty());
+        assert!(output.stderr.is_empty());
+
+        let err = shell("echo out && exit 3").silent(true).exec().unwrap_err();
+        assert_eq!(err.code(), Some(3));
+    }
+
+    #[test]
+    fn wrap_with_puts_the_wrapper_first() {
+        let mut wrapper = process("qemu-aarch64");
+        wrapper.arg("-L").arg("/sysroot").cwd("/wrapper")
+               .env("QEMU_LOG", "wrapper").env("SHARED", "wrapper");
+
+        let mut p = process("foo");
+        p.arg("bar").env("SHARED", "inner");
+        p.wrap_with(wrapper);
+        let argv = p.get_argv().iter().map(|s| {
+            s.to_str().unwrap().to_string()
+        }).collect::<Vec<_>>();
+        assert_eq!(argv, ["qemu-aarch64", "-L", "/sysroot", "foo", "bar"]);
+        assert_eq!(p.get_env("SHARED"), Some(OsString::from_str("inner")));
+        assert_eq!(p.get_env("QEMU_LOG"), Some(OsString::from_str("wrapper")));
+        assert_eq!(p.get_cwd(), Some(Path::new("/wrapper")));
+    }
+
+    #[test]
+    fn arg_front_goes_before_appended_args() {
+        let mut p = process("git");
+        p.arg("status").arg_front("/repo").arg("--short").arg_front("-C");
+        let args = p.get_args().iter().map(|s| {
+            s.to_str().unwrap()
+        }).collect::<Vec<_>>();
+        assert_eq!(args, ["-C", "/repo", "status", "--short"]);
+    }
+
+    #[test]
+    fn arg_if_only_appends_when_true() {
+        let mut p = process("foo");
+        p.arg("a").arg_if(true, "b").arg_if(false, "c")
+         .args_if(true, &["d", "e"]).args_if(false, &["f"]);
+        let args = p.get_args().iter().map(|s| {
+            s.to_str().unwrap()
+        }).collect::<Vec<_>>();
+        assert_eq!(args, ["a", "b", "d", "e"]);
+    }
+
+    #[test]
+    fn arg_if_env_checks_the_environment_of_the_process() {
+        let mut p = process("foo");
+        p.env("CARGO_TEST_SET", "1").env("CARGO_TEST_EMPTY", "")
+         .env_remove("PATH")
+         .arg_if_env("CARGO_TEST_SET", "set")
+         .arg_if_env("CARGO_TEST_EMPTY", "empty")
+         .arg_if_env("CARGO_TEST_NEVER_SET_ANYWHERE", "unset")
+         .arg_if_env("PATH", "removed");
+        assert_eq!(p.get_args(), [OsString::from_str("set")]);
+
+        let mut p = process("foo");
+        p.arg_if_env("PATH", "inherited");
+        assert_eq!(p.get_args(), [OsString::from_str("inherited")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn arg_transform_rewrites_spawned_args() {
+        let mut p = process("sh");
+        p.arg("-c").arg("echo $0").arg("shout");
+        p.arg_transform(Box::new(|arg: &OsStr| {
+            let arg = arg.to_str().unwrap();
+            if arg != "shout" { return OsString::from_str(arg) }
+            let upper = arg.chars().flat_map(|c| c.to_uppercase())
+                           .collect::<String>();
+            OsString::from_str(&upper)
+        }));
+        let output = p.exec_with_output().unwrap();
+        assert_eq!(output.stdout, b"SHOUT\n".to_vec());
+        assert_eq!(p.get_args()[2], OsString::from_str("shout"));
+    }
+
+    #[test]
+    fn append_args_of_keeps_order() {
+        let mut flags = process("unused");
+        flags.arg("-c").arg("-d").arg("-e");
+        let mut p = process("foo");
+        p.arg("a").arg("b").append_args_of(&flags);
+        assert_eq!(p.args_lossy().collect::<Vec<_>>(),
+                   ["a", "b", "-c", "-d", "-e"]);
+        assert_eq!(flags.get_args().len(), 3);
+    }
+
+    #[test]
+    fn args_lossy_converts_each_arg() {
+        let mut p = process("foo");
+        p.arg("a").arg("b c");
+        assert_eq!(p.args_lossy().collect::<Vec<_>>(), ["a", "b c"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn args_lossy_replaces_invalid_unicode() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let mut p = process("foo");
+        p.arg("ok").arg(&OsString::from_vec(vec![b'a', 0xff]));
+        assert_eq!(p.args_lossy().collect::<Vec<_>>(), ["ok", "a\u{fffd}"]);
+    }
+
+    #[test]
+    fn restore_undoes_changes_since_snapshot() {
+        let mut p = process("foo");
+        p.arg("a").env("A", "1").env_remove("B");
+        let before = p.clone();
+
+        let state = p.snapshot();
+        p.arg("b").env("A", "2").env("C", "3").cwd("/");
+        assert!(p != before);
+        p.restore(state);
+        assert_eq!(p, before);
+    }
+
+    #[test]
+    fn clear_args_removes_previous_args() {
+        let mut p = process("foo");
+        p.arg("a").arg("b").clear_args().arg("c");
+        assert_eq!(p.get_args(), &[OsString::from_str("c")][..]);
+    }
+
+    #[test]
+    fn get_argv_starts_with_program() {
+        let mut p = process("foo");
+        p.arg("a").arg("b");
+        let argv = p.get_argv();
+        assert_eq!(&argv[0], p.get_program());
+        assert_eq!(&argv[1..], p.get_args());
+        assert_eq!(argv.len(), 3);
+    }
+
+    #[test]
+    fn extend_args_preserves_order() {
+        let first = vec![OsString::from_str("a"), OsString::from_str("b")];
+        let computed = ["c", "d"].iter().map(|s| OsString::from_str(s));
+        let mut p = process("foo");
+        p.arg("start").extend_args(first.into_iter().chain(computed)).arg("end");
+        let args = p.get_args().iter().map(|s| {
+            s.to_str().unwrap()
+        }).collect::<Vec<_>>();
+        assert_eq!(args, ["start", "a", "b", "c", "d", "end"]);
+    }
+
+    #[test]
+    fn verbose_echoes_each_run() {
+        use std::io;
+
+        struct Sink(Arc<Mutex<Vec<u8>>>);
+        impl Write for Sink {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> io::Result<()> { Ok(()) }
+        }
+
+        let buf = Arc::new(Mutex::new(Vec::new()));
+        let mut p = shell("exit 0");
+        p.verbose(Box::new(Sink(buf.clone())));
+        p.exec().unwrap();
+        p.exec_with_output().unwrap();
+        p.describe("doing nothing".to_string());
+        p.exec().unwrap();
+
+        let text = String::from_utf8(buf.lock().unwrap().clone()).unwrap();
+        let expected = format!("Running {}", shell("exit 0"));
+        assert_eq!(text.lines().collect::<Vec<_>>(),
+                   [&expected[..], &expected[..], "Running doing nothing"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn forward_signals_relays_to_the_child() {
+        use std::thread;
+        use std::time::Duration;
+        use util::process::watchdog::sleep;
+        use libc;
+
+        let mut p = shell("trap 'echo got TERM; kill $!; exit 0' TERM; \
+                           sleep 10 & wait");
+        p.forward_signals(&[libc::SIGTERM]);
+        // Give the shell time to set up its trap before signalling ourselves,
+        // which only survives because the signal is forwarded.
+        let signaller = thread::spawn(|| {
+            sleep(Duration::milliseconds(500));
+            unsafe { libc::kill(libc::getpid(), libc::SIGTERM); }
+        });
+        let output = p.exec_with_output().unwrap();
+        signaller.join().unwrap();
+        assert_eq!(output.stdout, b"got TERM\n");
+    }
+
+    #[test]
+    fn dedup_args_only_drops_repeated_assignments() {
+        let mut p = process("rustc");
+        p.args(&["-C", "opt-level=2", "--cfg=unix", "-l", "foo", "-v",
+                 "lib.rs", "-C", "opt-level=2", "-C", "opt-level=3",
+                 "--cfg=unix", "-l", "foo", "-v", "lib.rs", "--",
+                 "--cfg=unix"]);
+        assert_eq!(p.final_args().len(), 18);
+
+        p.dedup_args(true);
+        let args = p.final_args().iter().map(|arg| {
+            arg.to_str().unwrap().to_string()
+        }).collect::<Vec<_>>();
+        assert_eq!(args, ["-C", "opt-level=2", "--cfg=unix", "-l", "foo", "-v",
+                          "lib.rs", "-C", "opt-level=3", "-l", "foo", "-v",
+                          "lib.rs", "--", "--cfg=unix"]);
+        assert_eq!(p.get_args().len(), 18);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn swap_stdio_swaps_captured_streams() {
+        let mut p = shell("echo result >&2; echo progress");
+        p.swap_stdio(true);
+        let output = p.exec_with_output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "result");
+        assert_eq!(String::from_utf8_lossy(&output.stderr).trim(), "progress");
+
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        p.exec_into(&mut stdout, &mut stderr).unwrap();
+        assert_eq!(String::from_utf8_lossy(&stdout).trim(), "result");
+
+        p.swap_stdio(false);
+        let output = p.exec_with_output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "progress");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stdout_fd_writes_to_the_descriptor() {
+        use libc;
+        use util::spawn::FileDesc;
+
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        // Keep processes spawned by other tests from holding the pipe open.
+        for &fd in fds.iter() {
+            unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC); }
+        }
+        let mut read = unsafe { FileDesc::new(fds[0]) };
+        let write = unsafe { FileDesc::new(fds[1]) };
+
+        let mut p = shell("echo out; echo err >&2");
+        p.stdout_fd(fds[1]).stderr_fd(fds[1]);
+        let output = p.exec_with_output().unwrap();
+        assert!(output.stdout.is_empty());
+        assert!(output.stderr.is_empty());
+        // The builder only closed its own copies, so this is the last one.
+        drop(write);
+
+        let mut written = String::new();
+        read.read_to_string(&mut written).unwrap();
+        assert_eq!(written, "out\nerr\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn redirect_stderr_to_stdout_shares_the_descriptor() {
+        let mut p = shell("if [ /dev/fd/1 -ef /dev/fd/2 ]; then echo same; \
+                           else echo different; fi; echo err >&2");
+        let output = p.exec_with_output().unwrap();
+        assert_eq!(output.stdout, b"different\n");
+        assert_eq!(output.stderr, b"err\n");
+
+        p.redirect_stderr_to_stdout(true);
+        let output = p.exec_with_output().unwrap();
+        assert_eq!(output.stdout, b"same\nerr\n");
+        assert_eq!(output.stderr, b"");
+
+        let mut lines = Vec::new();
+        p.exec_with_streaming(|line| lines.push(line.to_string()),
+                              |line| panic!("unexpected stderr: {}", line))
+         .unwrap();
+        assert_eq!(lines, ["same", "err"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn output_encoding_defaults_to_utf8() {
+        let mut p = shell("printf '\\377\\376h\\000i\\000'");
+        let err = p.exec_with_output_trimmed().unwrap_err().to_string();
+        assert!(err.contains("was not valid UTF-8"), "{}", err);
+
+        p.output_encoding(Encoding::Utf16);
+        assert_eq!(p.exec_with_output_trimmed().unwrap(), "hi");
+
+        // Unix has no code pages, so they decode as UTF-8.
+        let mut p = shell("printf 'caf\\303\\251'");
+        p.output_encoding(Encoding::OemCodePage);
+        assert_eq!(p.exec_with_output_trimmed().unwrap(), "caf\u{e9}");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn output_encoding_decodes_windows_output() {
+        let mut p = process("cmd");
+        p.arg("/U").arg("/C").arg("echo caf\u{e9}");
+        p.output_encoding(Encoding::Utf16);
+        assert_eq!(p.exec_with_output_trimmed().unwrap(), "caf\u{e9}");
+
+        // Batch files print in the OEM code page.
+        let td = TempDir::new("cargo-output-encoding").unwrap();
+        let script = td.path().join("hello.bat");
+        File::create(&script).unwrap().write_all(b"@echo hello").unwrap();
+        let mut p = process(&script);
+        p.output_encoding(Encoding::OemCodePage);
+        assert_eq!(p.exec_with_output_trimmed().unwrap(), "hello");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn throttle_lines_summarizes_bursts() {
+        let mut p = shell("i=0; while [ $i -lt 100 ]; do echo line $i; \
+                           i=$((i + 1)); done");
+        p.throttle_lines(10);
+        let mut lines = Vec::new();
+        let output = p.exec_with_streaming(|line| lines.push(line.to_string()),
+                                           |_| {}).unwrap();
+        assert_eq!(lines.len(), 11, "{:?}", lines);
+        assert_eq!(lines[0], "line 0");
+        assert_eq!(lines[9], "line 9");
+        assert_eq!(lines[10], "90 lines suppressed");
+        assert_eq!(String::from_utf8_lossy(&output.stdout).lines_any().count(),
+                   100);
+    }
+
+    #[test]
+    fn validate_path_args_names_missing_paths() {
+        use std::fs;
+
+        let td = TempDir::new("cargo").unwrap();
+        File::create(&td.path().join("lib.rs")).unwrap();
+        fs::create_dir(&td.path().join("out")).unwrap();
+
+        let mut p = shell("exit 0");
+        p.cwd(td.path()).arg("lib.rs").arg(td.path().join("out"))
+         .validate_path_args(&[2, 3]);
+        p.exec().unwrap();
+
+        p.validate_path_args(&[1]);
+        let err = p.exec().unwrap_err();
+        assert!(err.did_not_start());
+        assert!(err.desc.contains("the path `exit 0` given as argument 1 \
+                                   does not exist"), "{}", err);
+    }
+}
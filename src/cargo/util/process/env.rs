@@ -0,0 +1,1100 @@
+//! The environment variables a `ProcessBuilder` passes to its process.
+
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::ffi::{OsString, AsOsStr};
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::str::Str;
+use std::sync::Arc;
+
+use util::{CargoResult, human, caused_human};
+use util::join_paths;
+use util::jobserver::Client;
+use super::{Ignored, ProcessBuilder, env_key, trim_eol, imp};
+
+impl ProcessBuilder {
+    pub fn env<T: AsOsStr + ?Sized>(&mut self, key: &str,
+                                    val: &T) -> &mut ProcessBuilder {
+        let key = env_key(key);
+        self.lazy_env.remove(&key);
+        self.env.insert(key, Some(val.as_os_str().to_os_string()));
+        self
+    }
+
+    /// Sets `key` to the value returned by `f`, which is only called once the
+    /// value is actually needed.
+    ///
+    /// The value is computed anew each time the process is spawned, and each
+    /// time it's inspected through methods such as `get_env`. When `f`
+    /// returns `None` the variable is removed as if by `env_remove`. A later
+    /// call to `env` or `env_remove` for the same variable replaces `f`.
+    pub fn env_fn(&mut self, key: &str,
+                  f: Box<Fn() -> Option<OsString> + Send + Sync>)
+                  -> &mut ProcessBuilder {
+        let key = env_key(key);
+        self.env.remove(&key);
+        self.lazy_env.insert(key, Ignored(Arc::new(f)));
+        self
+    }
+
+    /// Sets each of the given environment variables, as if by calling `env`
+    /// for every pair in turn.
+    pub fn envs<I, K, V>(&mut self, vars: I) -> &mut ProcessBuilder
+        where I: IntoIterator<Item=(K, V)>, K: Str, V: AsOsStr
+    {
+        for (key, val) in vars {
+            self.env(key.as_slice(), &val);
+        }
+        self
+    }
+
+    /// Replaces the value of `key` with the result of passing its current
+    /// value to `f`, with `None` standing for a variable which isn't set.
+    ///
+    /// The current value is the one `get_env` returns, so after `env_clear`
+    /// only variables set explicitly are seen. Returning `None` removes the
+    /// variable as `env_remove` does.
+    pub fn env_modify<F>(&mut self, key: &str, f: F) -> &mut ProcessBuilder
+        where F: FnOnce(Option<OsString>) -> Option<OsString>
+    {
+        match f(self.get_env(key)) {
+            Some(val) => self.env(key, &val),
+            None => self.env_remove(key),
+        }
+    }
+
+    /// Adds `dir` to the front of the `PATH` of the process.
+    ///
+    /// This builds on the value `get_env` returns for `PATH`, so both a `PATH`
+    /// set through `env` and the inherited one are preserved. If `PATH` isn't
+    /// set at all `dir` becomes its only entry.
+    pub fn prepend_path(&mut self, dir: &Path)
+                        -> CargoResult<&mut ProcessBuilder> {
+        self.splice_path(dir, true)
+    }
+
+    /// Adds `dir` to the end of the `PATH` of the process, otherwise behaving
+    /// just like `prepend_path`.
+    pub fn append_path(&mut self, dir: &Path)
+                       -> CargoResult<&mut ProcessBuilder> {
+        self.splice_path(dir, false)
+    }
+
+    fn splice_path(&mut self, dir: &Path, front: bool)
+                   -> CargoResult<&mut ProcessBuilder> {
+        let mut dirs = match self.get_env("PATH") {
+            Some(path) => env::split_paths(&path).collect::<Vec<_>>(),
+            None => Vec::new(),
+        };
+        if front {
+            dirs.insert(0, dir.to_path_buf());
+        } else {
+            dirs.push(dir.to_path_buf());
+        }
+        let path = try!(join_paths(&dirs, "PATH"));
+        Ok(self.env("PATH", &path))
+    }
+
+    /// Sets the environment variables listed in the file at `path`, as if by
+    /// calling `env` for each of them in turn.
+    ///
+    /// Each line of the file is either blank, a comment starting with `#`, or
+    /// a `KEY=VALUE` assignment which may be preceded by `export`. Values may
+    /// be wrapped in single quotes, which are taken literally, or in double
+    /// quotes, within which `\n`, `\"` and `\\` are unescaped. Anything
+    /// following an unquoted value from ` #` onwards is a comment.
+    ///
+    /// Nothing is set if any line is malformed, and the error names the first
+    /// such line.
+    pub fn load_env_file(&mut self, path: &Path)
+                         -> CargoResult<&mut ProcessBuilder> {
+        let mut contents = String::new();
+        try!(File::open(path).and_then(|mut f| {
+            f.read_to_string(&mut contents)
+        }).map_err(|e| {
+            caused_human(format!("failed to read `{}`", path.display()), e)
+        }));
+
+        let mut vars = Vec::new();
+        for (i, line) in contents.lines_any().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("#") { continue }
+            match parse_env_line(line) {
+                Some(var) => vars.push(var),
+                None => {
+                    return Err(human(format!("invalid line {} in `{}`: {}",
+                                             i + 1, path.display(), line)))
+                }
+            }
+        }
+        Ok(self.envs(vars))
+    }
+
+    /// Sets the variables of an `[env]` table from the configuration.
+    ///
+    /// Variables the process would inherit from cargo are only replaced by
+    /// entries which `force` it, while variables set on the builder itself
+    /// always take precedence over the configuration. What gets inherited is
+    /// resolved as for `get_env`, so variables kept back by `env_clear` or
+    /// `env_remove_matching` are set from the configuration. Entries with a
+    /// `relative_to` directory have their value joined onto it.
+    pub fn apply_env_config(&mut self, cfg: &EnvConfig) -> &mut ProcessBuilder {
+        for (key, entry) in cfg.iter() {
+            if self.is_overridden(key) { continue }
+            if !entry.force && self.get_env(key).is_some() { continue }
+            match entry.relative_to {
+                Some(ref dir) => self.env(key, &dir.join(&entry.value)),
+                None => self.env(key, &entry.value),
+            };
+        }
+        self
+    }
+
+    /// Sets `key` to the contents of the file at `path`, which is read right
+    /// away.
+    ///
+    /// A single trailing newline is removed from the contents, as files
+    /// holding tokens and the like usually end in one. On Windows the contents
+    /// have to be valid UTF-8.
+    pub fn env_from_file(&mut self, key: &str, path: &Path)
+                         -> CargoResult<&mut ProcessBuilder> {
+        let mut contents = Vec::new();
+        try!(File::open(path).and_then(|mut f| {
+            f.read_to_end(&mut contents)
+        }).map_err(|e| {
+            caused_human(format!("failed to read `{}`", path.display()), e)
+        }));
+        let len = trim_eol(&contents).len();
+        contents.truncate(len);
+        let val = try!(imp::os_string_from_bytes(contents).ok_or_else(|| {
+            human(format!("the contents of `{}` are not valid unicode",
+                          path.display()))
+        }));
+        Ok(self.env(key, &val))
+    }
+
+    pub fn env_remove(&mut self, key: &str) -> &mut ProcessBuilder {
+        let key = env_key(key);
+        self.lazy_env.remove(&key);
+        self.env.insert(key, None);
+        self
+    }
+
+    /// Keeps any inherited environment variable whose name matches `pred` from
+    /// reaching the process.
+    ///
+    /// The inherited variables are only matched against `pred` once the
+    /// process is spawned. Variables set explicitly through `env` are passed
+    /// on regardless. Every predicate registered this way is applied.
+    pub fn env_remove_matching(&mut self,
+                               pred: Box<Fn(&str) -> bool + Send + Sync>)
+                               -> &mut ProcessBuilder {
+        self.env_filters.push(Ignored(Arc::new(pred)));
+        self
+    }
+
+    /// Copies the environment overrides of `other` into this builder.
+    ///
+    /// Variables already set or removed on this builder are left untouched,
+    /// and variables removed on `other` are removed here as well. Whether
+    /// `other` clears its environment is not carried over.
+    pub fn inherit_env_from(&mut self, other: &ProcessBuilder)
+                            -> &mut ProcessBuilder {
+        for (key, val) in other.env.iter() {
+            if !self.is_overridden(key) {
+                self.env.insert(key.clone(), val.clone());
+            }
+        }
+        for (key, val) in other.lazy_env.iter() {
+            if !self.is_overridden(key) {
+                self.lazy_env.insert(key.clone(), val.clone());
+            }
+        }
+        self
+    }
+
+    /// Makes the jobserver `client` available to the process.
+    ///
+    /// This sets `MAKEFLAGS` and `CARGO_MAKEFLAGS` to describe the jobserver
+    /// and arranges for the process to inherit whatever it needs to connect
+    /// to it, so that tools which understand the jobserver protocol limit
+    /// their own parallelism accordingly.
+    pub fn inherit_jobserver(&mut self, client: &Client) -> &mut ProcessBuilder {
+        let makeflags = client.makeflags();
+        self.env("CARGO_MAKEFLAGS", &makeflags).env("MAKEFLAGS", &makeflags);
+        self.jobserver = Some(client.clone());
+        self
+    }
+
+    /// Tells the process whether the output it produces ends up on a terminal.
+    ///
+    /// Processes whose output is captured or forwarded by cargo can't detect
+    /// the terminal themselves. When `is_tty` is true this sets
+    /// `CARGO_TERM_COLOR=always` and `CLICOLOR_FORCE=1` so tools keep their
+    /// colors, and otherwise it removes both variables. Without a call to this
+    /// the process is left to decide on its own.
+    pub fn inherit_tty_hint(&mut self, is_tty: bool) -> &mut ProcessBuilder {
+        if is_tty {
+            self.env("CARGO_TERM_COLOR", "always").env("CLICOLOR_FORCE", "1")
+        } else {
+            self.env_remove("CARGO_TERM_COLOR").env_remove("CLICOLOR_FORCE")
+        }
+    }
+
+    /// Passes `--color=always` or `--color=never` to the process, as rustc and
+    /// cargo understand, according to `mode`.
+    ///
+    /// `ColorMode::Auto` picks `always` if the stdout of cargo is a terminal,
+    /// as it is when the output of the process will be forwarded to one, and
+    /// `never` otherwise.
+    pub fn apply_color_mode(&mut self, mode: ColorMode) -> &mut ProcessBuilder {
+        self.apply_color_mode_with(mode, imp::stdout_is_tty)
+    }
+
+    /// Like `apply_color_mode`, but asks `is_tty` whether the output ends up
+    /// on a terminal in `ColorMode::Auto`.
+    pub fn apply_color_mode_with<F>(&mut self, mode: ColorMode, is_tty: F)
+                                    -> &mut ProcessBuilder
+        where F: FnOnce() -> bool
+    {
+        let always = match mode {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => is_tty(),
+        };
+        self.arg(if always {"--color=always"} else {"--color=never"})
+    }
+
+    /// Keeps the process from producing colored output because cargo itself
+    /// was told to, for when its output is captured rather than shown.
+    ///
+    /// This removes `CLICOLOR_FORCE` and `FORCE_COLOR` from the environment of
+    /// the process and sets `NO_COLOR=1` instead.
+    pub fn strip_color_env(&mut self) -> &mut ProcessBuilder {
+        self.env_remove("CLICOLOR_FORCE").env_remove("FORCE_COLOR")
+            .env("NO_COLOR", "1")
+    }
+
+    /// Starts the process with an empty environment instead of inheriting the
+    /// environment of cargo.
+    ///
+    /// Only variables set explicitly through `env` or passed on through
+    /// `env_forward` will be visible to the process.
+    pub fn env_clear(&mut self) -> &mut ProcessBuilder {
+        self.env_clear = true;
+        self
+    }
+
+    /// Always passes the variables in `keys` on from the environment of cargo,
+    /// even after `env_clear`.
+    ///
+    /// Variables which cargo doesn't have set are skipped. The environment is
+    /// cleared first, then the forwarded variables are copied over, and then
+    /// the variables set or removed explicitly are applied, so `env` and
+    /// `env_remove` still take precedence. Forwarded variables aren't subject
+    /// to `env_remove_matching` either.
+    pub fn env_forward(&mut self, keys: &[&str]) -> &mut ProcessBuilder {
+        self.env_forward.extend(keys.iter().map(|key| env_key(key)));
+        self
+    }
+
+    /// Configures whether references to other variables in the values set
+    /// through `env` and `env_fn` are expanded.
+    ///
+    /// When enabled, `${NAME}` in a value is replaced with the value `NAME`
+    /// has in the environment of the process, or with nothing if `NAME` isn't
+    /// set, and `$$` is replaced with a single `$`. Any other `$`, including
+    /// one starting an unterminated `${`, is kept as is. References are only
+    /// expanded one level deep: the value substituted for `${NAME}` is the
+    /// one `NAME` has before expansion, so variables referring to each other
+    /// can't expand endlessly. Values which aren't unicode are never
+    /// expanded. By default values are used verbatim.
+    pub fn env_expand(&mut self, yes: bool) -> &mut ProcessBuilder {
+        self.env_expand = yes;
+        self
+    }
+
+    /// Returns the value `var` will have in the environment of the process.
+    ///
+    /// A value set through `env` always takes precedence, and a variable
+    /// removed through `env_remove` is never present. Any other variable is
+    /// inherited from the environment of cargo, unless `env_clear` was called
+    /// or it matches a predicate given to `env_remove_matching`, in which
+    /// case it isn't present either. Variables set through `env_fn` are
+    /// computed by calling their function, and values set explicitly are
+    /// expanded if `env_expand` was enabled.
+    pub fn get_env(&self, var: &str) -> Option<OsString> {
+        let val = self.unexpanded_env(var);
+        if self.is_overridden(var) {
+            val.map(|val| self.expand(val))
+        } else {
+            val
+        }
+    }
+
+    /// Returns the complete environment the process will start with.
+    ///
+    /// This is the environment of cargo, unless `env_clear` was called, minus
+    /// the variables matching a predicate given to `env_remove_matching`, plus
+    /// the variables given to `env_forward`, with every variable set or
+    /// removed explicitly applied on top.
+    pub fn resolved_env(&self) -> HashMap<OsString, OsString> {
+        let mut vars = HashMap::new();
+        for (key, val) in env::vars_os() {
+            // As when spawning, only variables with unicode names can be
+            // matched by predicates or forwarded. Their names are normalized
+            // like those of overrides, which then replace them.
+            let (filtered, forwarded) = key.to_str().map(|key| {
+                (self.is_filtered(key), self.is_forwarded(key))
+            }).unwrap_or((false, false));
+            let key = match key.into_string() {
+                Ok(key) => OsString::from_str(&env_key(&key)),
+                Err(key) => key,
+            };
+            if forwarded || (!self.env_clear && !filtered) {
+                vars.insert(key, val);
+            }
+        }
+        for (key, val) in self.env_overrides() {
+            let key = OsString::from_str(&key);
+            match val {
+                Some(val) => { vars.insert(key, val); }
+                None => { vars.remove(&key); }
+            }
+        }
+        vars
+    }
+
+    /// Checks that the environment the process will start with, as returned by
+    /// `resolved_env`, is exactly `expected`.
+    ///
+    /// The error describes the first mismatch by name: a variable which is
+    /// missing, one which isn't expected, or one with a different value.
+    pub fn assert_env_matches(&self, expected: &HashMap<OsString, OsString>)
+                              -> CargoResult<()> {
+        let actual = self.resolved_env();
+        let mut keys = actual.keys().chain(expected.keys()).collect::<Vec<_>>();
+        keys.sort();
+        keys.dedup();
+        for key in keys.into_iter() {
+            let name = key.to_string_lossy();
+            match (actual.get(key), expected.get(key)) {
+                (Some(a), Some(e)) if a != e => {
+                    return Err(human(format!("environment variable `{}` of {} \
+                                              is `{}`, expected `{}`", name,
+                                             self.error_name(),
+                                             a.to_string_lossy(),
+                                             e.to_string_lossy())))
+                }
+                (Some(..), None) => {
+                    return Err(human(format!("unexpected environment variable \
+                                              `{}` set for {}", name,
+                                             self.error_name())))
+                }
+                (None, Some(..)) => {
+                    return Err(human(format!("environment variable `{}` is \
+                                              missing for {}", name,
+                                             self.error_name())))
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the variables set or removed through `env` and `env_remove`,
+    /// which doesn't include those set through `env_fn`.
+    pub fn get_envs(&self) -> &HashMap<String, Option<OsString>> { &self.env }
+
+    /// Like `get_envs`, but sorted by variable name so that the order doesn't
+    /// depend on how the variables were added.
+    pub fn get_envs_sorted(&self) -> BTreeMap<String, Option<OsString>> {
+        self.env.iter().map(|(key, val)| (key.clone(), val.clone())).collect()
+    }
+
+    /// Compares the environment of the process with that of cargo, returning
+    /// the variables which are added, changed and removed, in that order.
+    ///
+    /// Added and changed variables come with the value the process sees. Each
+    /// list is sorted by variable name, and inherited variables whose names
+    /// aren't valid unicode are ignored.
+    pub fn env_diff(&self) -> (Vec<(String, OsString)>, Vec<(String, OsString)>,
+                               Vec<String>) {
+        let mut added = Vec::new();
+        let mut changed = Vec::new();
+        let mut removed = Vec::new();
+        for (key, val) in self.env_overrides() {
+            match (env::var_os(&key), val) {
+                (None, Some(val)) => added.push((key, val)),
+                (Some(ref old), Some(ref val)) if old != val => {
+                    changed.push((key, val.clone()))
+                }
+                (Some(..), None) => removed.push(key),
+                _ => {}
+            }
+        }
+        // Inherited variables can also disappear through `env_clear` or
+        // `env_remove_matching`.
+        for (key, _) in env::vars_os() {
+            if let Ok(key) = key.into_string() {
+                if !self.is_overridden(&key) && self.get_env(&key).is_none() {
+                    removed.push(key);
+                }
+            }
+        }
+        added.sort_by(|a, b| a.0.cmp(&b.0));
+        changed.sort_by(|a, b| a.0.cmp(&b.0));
+        removed.sort();
+        (added, changed, removed)
+    }
+
+    /// Makes the exec methods and `spawn` check that each of `keys` is set to
+    /// a non-empty value in the environment of the process, before starting
+    /// it.
+    ///
+    /// Variables are looked up as `get_env` does, so both those set on the
+    /// builder and those inherited from cargo count. Missing variables are an
+    /// error naming all of them, rather than whatever the process would make
+    /// of their absence. By default no variables are required.
+    pub fn require_env(&mut self, keys: &[&str]) -> &mut ProcessBuilder {
+        self.required_env.extend(keys.iter().map(|key| key.to_string()));
+        self
+    }
+}
+
+/// Whether a process is told to color its output, as passed to
+/// `ProcessBuilder::apply_color_mode`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColorMode {
+    /// Color the output if it ends up on a terminal.
+    Auto,
+    Always,
+    Never,
+}
+
+/// The variables of an `[env]` table in the configuration, as given to
+/// `ProcessBuilder::apply_env_config`.
+pub type EnvConfig = BTreeMap<String, EnvConfigEntry>;
+
+/// A variable of an `EnvConfig`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct EnvConfigEntry {
+    pub value: String,
+    /// Whether the value replaces that of the variable in the environment of
+    /// cargo, rather than only being used when it isn't set there.
+    pub force: bool,
+    /// The directory the value is a path relative to, if it's a path.
+    pub relative_to: Option<PathBuf>,
+}
+
+/// Parses a `KEY=VALUE` line of a file given to `load_env_file`.
+fn parse_env_line(line: &str) -> Option<(String, String)> {
+    let line = if line.starts_with("export ") {
+        line[7..].trim_left()
+    } else {
+        line
+    };
+    let eq = match line.find('=') {
+        Some(eq) => eq,
+        None => return None,
+    };
+    let key = line[..eq].trim_right();
+    let valid_key = !key.is_empty() &&
+                    !key.chars().next().unwrap().is_digit(10) &&
+                    key.chars().all(|c| c.is_alphanumeric() || c == '_');
+    if !valid_key { return None }
+
+    let val = line[eq + 1..].trim_left();
+    let mut chars = val.chars();
+    let (val, rest) = match chars.next() {
+        Some('\'') => {
+            let end = match val[1..].find('\'') {
+                Some(end) => end + 1,
+                None => return None,
+            };
+            (val[1..end].to_string(), &val[end + 1..])
+        }
+        Some('"') => {
+            let mut unescaped = String::new();
+            let mut end = None;
+            let mut pos = 1;
+            while let Some(c) = chars.next() {
+                pos += c.len_utf8();
+                match c {
+                    '"' => { end = Some(pos); break }
+                    '\\' => {
+                        let c = match chars.next() {
+                            Some(c) => c,
+                            None => return None,
+                        };
+                        pos += c.len_utf8();
+                        unescaped.push(if c == 'n' { '\n' } else { c });
+                    }
+                    c => unescaped.push(c),
+                }
+            }
+            match end {
+                Some(end) => (unescaped, &val[end..]),
+                None => return None,
+            }
+        }
+        _ => {
+            let val = match val.find(" #") {
+                Some(comment) => &val[..comment],
+                None => val,
+            };
+            (val.trim_right().to_string(), "")
+        }
+    };
+
+    // Only a comment may follow a quoted value.
+    let rest = rest.trim_left();
+    if rest.is_empty() || rest.starts_with("#") {
+        Some((key.to_string(), val))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::env;
+    use std::ffi::OsString;
+    use std::fs::File;
+    use std::io::prelude::*;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use tempdir::TempDir;
+
+    use util::jobserver::Client;
+    use util::process::tests::shell;
+    use util::process::process;
+    use super::{ColorMode, EnvConfig, EnvConfigEntry};
+
+    #[test]
+    fn env_is_inherited_by_default() {
+        let mut p = process("foo");
+        p.env("FOO", "bar");
+        assert_eq!(p.get_env("FOO"), Some(OsString::from_str("bar")));
+        assert_eq!(p.get_env("PATH"), env::var_os("PATH"));
+    }
+
+    #[test]
+    fn env_clear_drops_inherited_env() {
+        let mut p = process("foo");
+        p.env_clear().env("FOO", "bar");
+        assert_eq!(p.get_env("FOO"), Some(OsString::from_str("bar")));
+        assert_eq!(p.get_env("PATH"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn env_forward_survives_env_clear() {
+        env::set_var("__CARGO_TEST_FORWARD_DROPPED", "dropped");
+
+        let mut p = process("env");
+        p.env_clear().env_forward(&["PATH", "__CARGO_TEST_FORWARD_UNSET"])
+         .env("FOO", "bar");
+        assert_eq!(p.get_env("PATH"), env::var_os("PATH"));
+        assert_eq!(p.get_env("__CARGO_TEST_FORWARD_DROPPED"), None);
+
+        let output = p.exec_with_output().unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let mut vars = stdout.lines_any().collect::<Vec<_>>();
+        vars.sort();
+        let path = format!("PATH={}", env::var("PATH").unwrap());
+        assert_eq!(vars, ["FOO=bar", &path[..]]);
+
+        p.env("PATH", "/nowhere");
+        assert_eq!(p.get_env("PATH"), Some(OsString::from_str("/nowhere")));
+    }
+
+    #[test]
+    fn get_env_matches_child_env() {
+        env::set_var("__CARGO_TEST_GET_ENV", "inherited");
+        env::set_var("__CARGO_TEST_GET_ENV_REMOVED", "inherited");
+
+        let mut p = process("foo");
+        p.env("__CARGO_TEST_GET_ENV_SET", "set")
+         .env_remove("__CARGO_TEST_GET_ENV_REMOVED");
+        assert_eq!(p.get_env("__CARGO_TEST_GET_ENV_SET"),
+                   Some(OsString::from_str("set")));
+        assert_eq!(p.get_env("__CARGO_TEST_GET_ENV_REMOVED"), None);
+        assert_eq!(p.get_env("__CARGO_TEST_GET_ENV"),
+                   Some(OsString::from_str("inherited")));
+
+        p.env_clear();
+        assert_eq!(p.get_env("__CARGO_TEST_GET_ENV_SET"),
+                   Some(OsString::from_str("set")));
+        assert_eq!(p.get_env("__CARGO_TEST_GET_ENV_REMOVED"), None);
+        assert_eq!(p.get_env("__CARGO_TEST_GET_ENV"), None);
+    }
+
+    #[test]
+    fn inherit_jobserver_sets_makeflags() {
+        let client = Client::new(1).unwrap();
+        let mut p = process("foo");
+        p.inherit_jobserver(&client);
+
+        let makeflags = client.makeflags();
+        assert!(makeflags.contains("--jobserver-auth="), "{}", makeflags);
+        assert_eq!(p.get_env("CARGO_MAKEFLAGS"),
+                   Some(OsString::from_str(&makeflags)));
+        assert_eq!(p.get_env("MAKEFLAGS"), Some(OsString::from_str(&makeflags)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn inherit_jobserver_passes_fds() {
+        let client = Client::new(1).unwrap();
+        let mut p = shell("fds=${MAKEFLAGS##*=}; \
+                           test -e /dev/fd/${fds%,*} && test -e /dev/fd/${fds#*,}");
+        p.inherit_jobserver(&client);
+        p.exec().unwrap();
+    }
+
+    #[test]
+    fn env_remove_matching_removes_inherited_vars() {
+        env::set_var("__CARGO_TEST_SECRET_A", "a");
+        env::set_var("__CARGO_TEST_SECRET_B", "b");
+        env::set_var("__CARGO_TEST_PUBLIC", "c");
+
+        let mut p = process("foo");
+        p.env("__CARGO_TEST_SECRET_B", "explicit");
+        p.env_remove_matching(Box::new(|var: &str| {
+            var.starts_with("__CARGO_TEST_SECRET_")
+        }));
+        assert_eq!(p.get_env("__CARGO_TEST_SECRET_A"), None);
+        assert_eq!(p.get_env("__CARGO_TEST_SECRET_B"),
+                   Some(OsString::from_str("explicit")));
+        assert_eq!(p.get_env("__CARGO_TEST_PUBLIC"),
+                   Some(OsString::from_str("c")));
+    }
+
+    #[test]
+    fn env_diff_buckets_changes() {
+        env::set_var("__CARGO_TEST_DIFF_CHANGED", "old");
+        env::set_var("__CARGO_TEST_DIFF_SAME", "same");
+        env::set_var("__CARGO_TEST_DIFF_REMOVED", "old");
+        env::remove_var("__CARGO_TEST_DIFF_ADDED");
+
+        let mut p = process("foo");
+        p.env("__CARGO_TEST_DIFF_ADDED", "new")
+         .env("__CARGO_TEST_DIFF_CHANGED", "new")
+         .env("__CARGO_TEST_DIFF_SAME", "same")
+         .env_remove("__CARGO_TEST_DIFF_REMOVED");
+        let (added, changed, removed) = p.env_diff();
+        assert_eq!(added, [("__CARGO_TEST_DIFF_ADDED".to_string(),
+                            OsString::from_str("new"))]);
+        assert_eq!(changed, [("__CARGO_TEST_DIFF_CHANGED".to_string(),
+                              OsString::from_str("new"))]);
+        assert_eq!(removed, ["__CARGO_TEST_DIFF_REMOVED"]);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn env_names_ignore_case_on_windows() {
+        let mut p = shell("echo %__CARGO_TEST_CASE%");
+        p.env("__Cargo_Test_Case", "a").env("__CARGO_TEST_CASE", "b");
+        assert_eq!(p.get_envs().len(), 1);
+        assert_eq!(p.get_env("__cargo_test_case"),
+                   Some(OsString::from_str("b")));
+
+        let output = p.exec_with_output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "b");
+    }
+
+    #[test]
+    fn inherit_tty_hint_forces_color() {
+        let mut p = process("foo");
+        p.inherit_tty_hint(true);
+        assert_eq!(p.get_env("CARGO_TERM_COLOR"),
+                   Some(OsString::from_str("always")));
+        assert_eq!(p.get_env("CLICOLOR_FORCE"), Some(OsString::from_str("1")));
+
+        p.inherit_tty_hint(false);
+        assert_eq!(p.get_env("CARGO_TERM_COLOR"), None);
+        assert_eq!(p.get_env("CLICOLOR_FORCE"), None);
+    }
+
+    #[test]
+    fn apply_color_mode_passes_the_color_flag() {
+        let color_arg = |mode: ColorMode, tty: bool| {
+            let mut p = process("rustc");
+            p.apply_color_mode_with(mode, || tty);
+            p.get_args()[0].to_str().unwrap().to_string()
+        };
+        assert_eq!(color_arg(ColorMode::Always, false), "--color=always");
+        assert_eq!(color_arg(ColorMode::Never, true), "--color=never");
+        assert_eq!(color_arg(ColorMode::Auto, true), "--color=always");
+        assert_eq!(color_arg(ColorMode::Auto, false), "--color=never");
+
+        let mut p = process("rustc");
+        p.apply_color_mode(ColorMode::Never);
+        assert_eq!(p.get_args(), [OsString::from_str("--color=never")]);
+    }
+
+    #[test]
+    fn strip_color_env_removes_forced_color() {
+        env::set_var("__CARGO_TEST_STRIP_COLOR", "1");
+        let mut p = process("foo");
+        p.env("CLICOLOR_FORCE", "1").env("FORCE_COLOR", "3")
+         .env("__CARGO_TEST_OTHER", "1").strip_color_env();
+        let vars = p.resolved_env();
+        let get = |key: &str| vars.get(&OsString::from_str(key)).cloned();
+        assert_eq!(get("CLICOLOR_FORCE"), None);
+        assert_eq!(get("FORCE_COLOR"), None);
+        assert_eq!(get("NO_COLOR"), Some(OsString::from_str("1")));
+        assert_eq!(get("__CARGO_TEST_OTHER"), Some(OsString::from_str("1")));
+        assert_eq!(get("__CARGO_TEST_STRIP_COLOR"),
+                   Some(OsString::from_str("1")));
+    }
+
+    #[test]
+    fn env_order_does_not_change_the_command() {
+        let vars = (0..20).map(|i| {
+            (format!("__CARGO_TEST_ENV_ORDER_{}", i), i.to_string())
+        }).collect::<Vec<_>>();
+        let mut a = process("foo");
+        let mut b = process("foo");
+        for &(ref key, ref val) in vars.iter() {
+            a.env(key, val);
+        }
+        for &(ref key, ref val) in vars.iter().rev() {
+            b.env(key, val);
+        }
+        a.env_remove("__CARGO_TEST_ENV_ORDER_GONE");
+        b.env_remove("__CARGO_TEST_ENV_ORDER_GONE");
+
+        assert_eq!(a.command_fingerprint(), b.command_fingerprint());
+        assert_eq!(a.explain(), b.explain());
+        assert_eq!(a.get_envs_sorted(), b.get_envs_sorted());
+        let keys = a.get_envs_sorted().keys().cloned().collect::<Vec<_>>();
+        let mut sorted = keys.clone();
+        sorted.sort();
+        assert_eq!(keys, sorted);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn env_fn_is_evaluated_on_each_spawn() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = count.clone();
+        let mut p = shell("echo $__CARGO_TEST_LAZY");
+        p.env_fn("__CARGO_TEST_LAZY", Box::new(move || {
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            Some(OsString::from_str(&format!("value{}", n)))
+        }));
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+
+        let first = p.exec_with_output().unwrap();
+        let second = p.exec_with_output().unwrap();
+        assert_eq!(String::from_utf8(first.stdout).unwrap().trim(), "value0");
+        assert_eq!(String::from_utf8(second.stdout).unwrap().trim(), "value1");
+        assert_eq!(p.get_env("__CARGO_TEST_LAZY"),
+                   Some(OsString::from_str("value2")));
+
+        p.env("__CARGO_TEST_LAZY", "fixed");
+        assert_eq!(p.get_env("__CARGO_TEST_LAZY"),
+                   Some(OsString::from_str("fixed")));
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn env_expand_substitutes_references() {
+        env::set_var("__CARGO_TEST_EXPAND_BASE", "/opt");
+        env::remove_var("__CARGO_TEST_EXPAND_MISSING");
+
+        let mut p = process("foo");
+        p.env("DEFINED", "${__CARGO_TEST_EXPAND_BASE}/bin")
+         .env("UNDEFINED", "${__CARGO_TEST_EXPAND_MISSING}/bin")
+         .env("LITERAL", "$$HOME costs $5 ${unterminated")
+         .env("SELF", "${SELF}x");
+        assert_eq!(p.get_env("DEFINED"),
+                   Some(OsString::from_str("${__CARGO_TEST_EXPAND_BASE}/bin")));
+
+        p.env_expand(true);
+        assert_eq!(p.get_env("DEFINED"), Some(OsString::from_str("/opt/bin")));
+        assert_eq!(p.get_env("UNDEFINED"), Some(OsString::from_str("/bin")));
+        assert_eq!(p.get_env("LITERAL"),
+                   Some(OsString::from_str("$HOME costs $5 ${unterminated")));
+        assert_eq!(p.get_env("SELF"), Some(OsString::from_str("${SELF}xx")));
+    }
+
+    #[test]
+    fn resolved_env_applies_overrides() {
+        env::set_var("__CARGO_TEST_RESOLVED_CHANGED", "old");
+        env::set_var("__CARGO_TEST_RESOLVED_REMOVED", "old");
+        env::set_var("__CARGO_TEST_RESOLVED_KEPT", "old");
+
+        let mut p = process("foo");
+        p.env("__CARGO_TEST_RESOLVED_CHANGED", "new")
+         .env_remove("__CARGO_TEST_RESOLVED_REMOVED");
+        let vars = p.resolved_env();
+        let get = |key: &str| vars.get(&OsString::from_str(key)).cloned();
+        assert_eq!(get("__CARGO_TEST_RESOLVED_CHANGED"),
+                   Some(OsString::from_str("new")));
+        assert_eq!(get("__CARGO_TEST_RESOLVED_REMOVED"), None);
+        assert_eq!(get("__CARGO_TEST_RESOLVED_KEPT"),
+                   Some(OsString::from_str("old")));
+
+        p.env_clear();
+        let vars = p.resolved_env();
+        assert_eq!(vars.len(), 1);
+    }
+
+    #[test]
+    fn assert_env_matches_reports_the_mismatch() {
+        let mut p = process("foo");
+        p.env_clear().env("A", "1").env("B", "2");
+        let mut expected = HashMap::new();
+        expected.insert(OsString::from_str("A"), OsString::from_str("1"));
+        expected.insert(OsString::from_str("B"), OsString::from_str("2"));
+        assert!(p.assert_env_matches(&expected).is_ok());
+
+        p.env_remove("B");
+        let err = p.assert_env_matches(&expected).unwrap_err();
+        assert!(err.to_string().contains("`B` is missing"), "{}", err);
+
+        p.env("B", "2").env("C", "3");
+        let err = p.assert_env_matches(&expected).unwrap_err();
+        assert!(err.to_string().contains("unexpected environment variable \
+                                          `C`"), "{}", err);
+
+        p.env_remove("C").env("A", "0");
+        let err = p.assert_env_matches(&expected).unwrap_err();
+        assert!(err.to_string().contains("`A` of `foo` is `0`, expected `1`"),
+                "{}", err);
+    }
+
+    #[test]
+    fn env_modify_sees_current_value() {
+        env::set_var("__CARGO_TEST_MODIFY", "-O");
+
+        let mut p = process("foo");
+        p.env_modify("__CARGO_TEST_MODIFY", |val| {
+            let val = format!("{} -g", val.unwrap().to_str().unwrap());
+            Some(OsString::from_str(&val))
+        });
+        assert_eq!(p.get_env("__CARGO_TEST_MODIFY"),
+                   Some(OsString::from_str("-O -g")));
+
+        p.env_modify("__CARGO_TEST_MODIFY", |_| None);
+        assert_eq!(p.get_env("__CARGO_TEST_MODIFY"), None);
+        assert_eq!(p.get_envs()["__CARGO_TEST_MODIFY"], None);
+    }
+
+    #[test]
+    fn envs_sets_every_var() {
+        let mut vars = HashMap::new();
+        vars.insert("A".to_string(), "1".to_string());
+        vars.insert("B".to_string(), "2".to_string());
+        vars.insert("C".to_string(), "3".to_string());
+
+        let mut p = process("foo");
+        p.env("A", "0").envs(vars);
+        assert_eq!(p.get_env("A"), Some(OsString::from_str("1")));
+        assert_eq!(p.get_env("B"), Some(OsString::from_str("2")));
+        assert_eq!(p.get_env("C"), Some(OsString::from_str("3")));
+    }
+
+    #[test]
+    fn prepend_path_adds_dir_first() {
+        let mut p = process("foo");
+        p.env("PATH", &env::join_paths(["a", "b"].iter()).unwrap());
+        p.prepend_path(Path::new("c")).unwrap();
+        assert_eq!(p.get_env("PATH"),
+                   Some(env::join_paths(["c", "a", "b"].iter()).unwrap()));
+    }
+
+    #[test]
+    fn append_path_adds_dir_last() {
+        let mut p = process("foo");
+        p.env("PATH", &env::join_paths(["a", "b"].iter()).unwrap());
+        p.append_path(Path::new("c")).unwrap();
+        assert_eq!(p.get_env("PATH"),
+                   Some(env::join_paths(["a", "b", "c"].iter()).unwrap()));
+    }
+
+    #[test]
+    fn prepend_path_without_path_set() {
+        let mut p = process("foo");
+        p.env_remove("PATH").prepend_path(Path::new("c")).unwrap();
+        assert_eq!(p.get_env("PATH"), Some(OsString::from_str("c")));
+    }
+
+    #[test]
+    fn inherit_env_from_keeps_own_overrides() {
+        let mut parent = process("parent");
+        parent.env("SHARED", "parent").env("PARENT", "parent")
+              .env_remove("REMOVED").env("CONFLICT", "parent");
+
+        let mut child = process("child");
+        child.env("SHARED", "child").env_remove("CONFLICT");
+        child.inherit_env_from(&parent);
+
+        let envs = child.get_envs();
+        assert_eq!(envs["SHARED"], Some(OsString::from_str("child")));
+        assert_eq!(envs["PARENT"], Some(OsString::from_str("parent")));
+        assert_eq!(envs["REMOVED"], None);
+        assert_eq!(envs["CONFLICT"], None);
+        assert_eq!(envs.len(), 4);
+    }
+
+    #[test]
+    fn load_env_file_parses_assignments() {
+        let td = TempDir::new("cargo").unwrap();
+        let path = td.path().join(".env");
+        File::create(&path).unwrap().write_all(b"\
+# a comment
+PLAIN=value # trailing comment
+
+export EXPORTED=yes
+SINGLE='a # literal \\n'
+DOUBLE=\"line\\nbreak \\\"quoted\\\"\" # comment
+EMPTY=
+").unwrap();
+
+        let mut p = process("foo");
+        p.env("PLAIN", "overridden").load_env_file(&path).unwrap();
+        p.env("EXPORTED", "later");
+        assert_eq!(p.get_env("PLAIN"), Some(OsString::from_str("value")));
+        assert_eq!(p.get_env("EXPORTED"), Some(OsString::from_str("later")));
+        assert_eq!(p.get_env("SINGLE"),
+                   Some(OsString::from_str("a # literal \\n")));
+        assert_eq!(p.get_env("DOUBLE"),
+                   Some(OsString::from_str("line\nbreak \"quoted\"")));
+        assert_eq!(p.get_env("EMPTY"), Some(OsString::from_str("")));
+    }
+
+    #[test]
+    fn load_env_file_rejects_malformed_lines() {
+        let td = TempDir::new("cargo").unwrap();
+        let path = td.path().join(".env");
+        File::create(&path).unwrap()
+            .write_all(b"GOOD=1\n# fine\nNOT AN ASSIGNMENT\n").unwrap();
+
+        let mut p = process("foo");
+        let err = p.load_env_file(&path).unwrap_err().to_string();
+        assert!(err.contains("invalid line 3"), "{}", err);
+        assert!(err.contains("NOT AN ASSIGNMENT"), "{}", err);
+        assert_eq!(p.get_envs().get("GOOD"), None);
+
+        File::create(&path).unwrap().write_all(b"OPEN=\"unterminated\n")
+            .unwrap();
+        assert!(p.load_env_file(&path).is_err());
+    }
+
+    #[test]
+    fn require_env_names_missing_variables() {
+        env::set_var("__CARGO_TEST_REQUIRED_INHERITED", "1");
+        env::remove_var("__CARGO_TEST_REQUIRED_MISSING");
+        let mut p = shell("exit 0");
+        p.env("__CARGO_TEST_REQUIRED_SET", "/sdk")
+         .require_env(&["__CARGO_TEST_REQUIRED_SET",
+                        "__CARGO_TEST_REQUIRED_INHERITED"]);
+        p.exec().unwrap();
+
+        p.require_env(&["__CARGO_TEST_REQUIRED_MISSING"]);
+        let err = p.exec().unwrap_err();
+        assert!(err.did_not_start());
+        assert!(err.desc.contains("the environment variables \
+                                   `__CARGO_TEST_REQUIRED_MISSING` it \
+                                   requires are not set"), "{}", err);
+
+        // An empty value counts as missing too.
+        p.env("__CARGO_TEST_REQUIRED_MISSING", "1")
+         .env("__CARGO_TEST_REQUIRED_SET", "");
+        let err = p.exec().unwrap_err();
+        assert!(err.desc.contains("`__CARGO_TEST_REQUIRED_SET`"), "{}", err);
+        assert!(!err.desc.contains("`__CARGO_TEST_REQUIRED_MISSING`"),
+                "{}", err);
+    }
+
+    #[test]
+    fn apply_env_config_respects_flags() {
+        env::set_var("__CARGO_TEST_CONFIG_FORCED", "inherited");
+        env::set_var("__CARGO_TEST_CONFIG_DEFAULT", "inherited");
+        env::remove_var("__CARGO_TEST_CONFIG_UNSET");
+        env::remove_var("__CARGO_TEST_CONFIG_PATH");
+
+        let entry = |value: &str, force: bool, relative_to: Option<&Path>| {
+            EnvConfigEntry {
+                value: value.to_string(),
+                force: force,
+                relative_to: relative_to.map(|p| p.to_path_buf()),
+            }
+        };
+        let mut cfg = EnvConfig::new();
+        cfg.insert("__CARGO_TEST_CONFIG_FORCED".to_string(),
+                   entry("config", true, None));
+        cfg.insert("__CARGO_TEST_CONFIG_DEFAULT".to_string(),
+                   entry("config", false, None));
+        cfg.insert("__CARGO_TEST_CONFIG_UNSET".to_string(),
+                   entry("config", false, None));
+        cfg.insert("__CARGO_TEST_CONFIG_PATH".to_string(),
+                   entry("data", false, Some(Path::new("/manifest"))));
+        cfg.insert("__CARGO_TEST_CONFIG_BUILDER".to_string(),
+                   entry("config", true, None));
+
+        let mut p = process("foo");
+        p.env("__CARGO_TEST_CONFIG_BUILDER", "builder").apply_env_config(&cfg);
+        let get = |key: &str| p.get_env(key).unwrap();
+        assert_eq!(get("__CARGO_TEST_CONFIG_FORCED"),
+                   OsString::from_str("config"));
+        assert_eq!(get("__CARGO_TEST_CONFIG_DEFAULT"),
+                   OsString::from_str("inherited"));
+        assert_eq!(get("__CARGO_TEST_CONFIG_UNSET"),
+                   OsString::from_str("config"));
+        assert_eq!(Path::new(&get("__CARGO_TEST_CONFIG_PATH")),
+                   Path::new("/manifest").join("data"));
+        assert_eq!(get("__CARGO_TEST_CONFIG_BUILDER"),
+                   OsString::from_str("builder"));
+    }
+
+    #[test]
+    fn apply_env_config_fills_in_cleared_variables() {
+        env::set_var("__CARGO_TEST_CONFIG_CLEARED", "inherited");
+
+        let mut cfg = EnvConfig::new();
+        cfg.insert("__CARGO_TEST_CONFIG_CLEARED".to_string(), EnvConfigEntry {
+            value: "config".to_string(),
+            force: false,
+            relative_to: None,
+        });
+        let mut p = process("foo");
+        p.env_clear().apply_env_config(&cfg);
+        assert_eq!(p.get_env("__CARGO_TEST_CONFIG_CLEARED"),
+                   Some(OsString::from_str("config")));
+    }
+
+    #[test]
+    fn env_from_file_trims_one_newline() {
+        let td = TempDir::new("cargo").unwrap();
+        let path = td.path().join("token");
+        let mut p = process("foo");
+
+        File::create(&path).unwrap().write_all(b"s3cret\n").unwrap();
+        p.env_from_file("TOKEN", &path).unwrap();
+        assert_eq!(p.get_env("TOKEN"), Some(OsString::from_str("s3cret")));
+
+        File::create(&path).unwrap().write_all(b"s3cret").unwrap();
+        p.env_from_file("TOKEN", &path).unwrap();
+        assert_eq!(p.get_env("TOKEN"), Some(OsString::from_str("s3cret")));
+
+        File::create(&path).unwrap().write_all(b"two\n\n").unwrap();
+        p.env_from_file("TOKEN", &path).unwrap();
+        assert_eq!(p.get_env("TOKEN"), Some(OsString::from_str("two\n")));
+
+        let missing = td.path().join("missing");
+        let err = p.env_from_file("OTHER", &missing).unwrap_err();
+        assert!(err.to_string().contains("failed to read"), "{}", err);
+        assert_eq!(p.get_env("OTHER"), None);
+    }
+}
@@ -0,0 +1,2136 @@
+//! Running the process of a `ProcessBuilder` and collecting its output.
+
+use std::cmp;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs;
+use std::io::prelude::*;
+use std::io::{self, BufReader};
+use std::iter::repeat;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::process::{Output, ExitStatus};
+use std::sync::mpsc::{channel, sync_channel, Sender, SyncSender, Receiver};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use rustc_serialize::Decodable;
+use rustc_serialize::json;
+use time;
+
+use util::{CargoResult, ProcessError, human, caused_human};
+use util::diagnostics::{parse_json_message, JsonMessage};
+use util::errors::exit_signal;
+use util::spawn::{Command, Child, Stdio};
+use super::{Ignored, ProcessBuilder, RecordedCommand, TempFile, trim_eol, imp};
+use super::watchdog::{Fired, Throttle, Ticker, Watchdog, disarm, elapsed_since};
+use super::watchdog::{retry_interrupted, sleep};
+
+/// How often the callback given to `progress_tick` is called, unless
+/// `progress_interval` says otherwise.
+const PROGRESS_INTERVAL_MS: i64 = 500;
+
+/// How many bytes `stream_bounded` reads from a pipe at a time, unless
+/// `read_buffer_size` says otherwise.
+const STREAM_CHUNK_SIZE: usize = 8 * 1024;
+
+/// How much of the output of a process `exec_with_json` quotes when it can't be
+/// decoded.
+const JSON_SNIPPET_CHARS: usize = 200;
+
+impl ProcessBuilder {
+    pub fn exec(&self) -> Result<(), ProcessError> {
+        if self.dry_run {
+            info!("dry run, not executing {}", self);
+            return Ok(())
+        }
+
+        if self.detach {
+            return self.spawn_detached().map(|_| ())
+        }
+        if self.replay_on_failure {
+            return self.exec_replaying_to(&mut io::stdout(), &mut io::stderr())
+        }
+        if self.capture_on_failure {
+            return self.exec_capturing_to(&mut io::stdout(), &mut io::stderr())
+        }
+
+        let (exit, elapsed) = try!(self.run());
+        if self.is_success(&exit) {
+            Ok(())
+        } else {
+            Err(self.exit_error(&exit, None, elapsed))
+        }
+    }
+
+    /// Executes the process and returns its exit status, whatever it is.
+    ///
+    /// Unlike `exec` an unsuccessful exit status isn't an error, which is
+    /// left for callers with their own idea of success to decide. Only
+    /// failing to run the process is an error, as is it being killed because
+    /// it timed out or was cancelled.
+    pub fn exec_status(&self) -> Result<ExitStatus, ProcessError> {
+        if self.dry_run {
+            info!("dry run, not executing {}", self);
+            return Ok(imp::success_status())
+        }
+        self.run().map(|(exit, _)| exit)
+    }
+
+    /// Runs the process the way `exec` does, returning its exit status and how
+    /// long it ran for.
+    fn run(&self) -> Result<(ExitStatus, Duration), ProcessError> {
+        let (mut command, _temp_files) = try!(self.command());
+        let start = time::precise_time_ns();
+        let mut child = try!(self.spawn_child(&mut command));
+        let watchdog = self.watch(&child);
+        let ticker = self.start_ticker();
+        let forwarder = imp::forward_signals(self, &child);
+        let fired = disarm(&child, watchdog, forwarder);
+        let exit = try!(retry_interrupted(|| child.wait()).map_err(|e| {
+            self.spawn_error(e)
+        }));
+        drop(ticker);
+        self.record_status(&exit, start);
+        let elapsed = elapsed_since(start);
+
+        match fired {
+            Some(Fired::TimedOut) => {
+                Err(self.timeout_error(self.timeout.unwrap(), &exit, None))
+            }
+            Some(Fired::Cancelled) => Err(self.cancelled_error(&exit, None)),
+            None => Ok((exit, elapsed)),
+        }
+    }
+
+    pub fn exec_with_output(&self) -> Result<Output, ProcessError> {
+        self.exec_with_output_timed().map(|(output, _)| output)
+    }
+
+    /// Like `exec_with_output`, but kills the process if it's still running
+    /// after `dur`, whatever `timeout` was set to.
+    ///
+    /// The error for a process which timed out carries the output it produced
+    /// until then, so callers can show what it printed before it hung.
+    pub fn exec_with_output_timeout(&self, dur: Duration)
+                                    -> Result<Output, ProcessError> {
+        let mut p = self.clone();
+        p.timeout(dur);
+        p.exec_with_output()
+    }
+
+    /// Runs the process capturing its output, as `exec_with_output` does, and
+    /// returns whether it succeeded, failed, or was killed for running longer
+    /// than `timeout`.
+    ///
+    /// Unlike with `exec_with_output`, neither an unsuccessful exit status nor
+    /// a timeout is an error, so callers can match on the outcome instead,
+    /// with the output captured until then in each case. A `timeout` of
+    /// `None` keeps whatever `timeout` was configured. Failing to run the
+    /// process, cancelling it or exceeding `max_output_bytes` are still
+    /// errors.
+    pub fn exec_outcome(&self, timeout: Option<Duration>)
+                        -> CargoResult<CommandOutcome> {
+        let mut p = self.clone();
+        if let Some(dur) = timeout {
+            p.timeout(dur);
+        }
+        let (run, output) = try!(p.exec_capture(Capture::Both));
+        if run.fired == Some(Fired::TimedOut) {
+            return Ok(CommandOutcome::TimedOut(output))
+        }
+        let output = try!(p.check_limits(&run, output));
+        if p.is_success(&output.status) {
+            Ok(CommandOutcome::Success(output))
+        } else {
+            let status = output.status;
+            Ok(CommandOutcome::Failure(output, status))
+        }
+    }
+
+    /// Like `exec`, but runs the process in `dir` instead of the working
+    /// directory configured on the builder, which is left untouched.
+    pub fn exec_in(&self, dir: &Path) -> Result<(), ProcessError> {
+        let mut p = self.clone();
+        p.cwd(dir);
+        p.exec()
+    }
+
+    /// Like `exec_with_output`, but runs the process in `dir` as `exec_in`
+    /// does.
+    pub fn exec_with_output_in(&self, dir: &Path)
+                               -> Result<Output, ProcessError> {
+        let mut p = self.clone();
+        p.cwd(dir);
+        p.exec_with_output()
+    }
+
+    /// Starts running the process in the background, sending its output over a
+    /// channel which holds at most `capacity` chunks.
+    ///
+    /// Once the channel is full the threads reading the output wait for the
+    /// receiver to catch up, and once the pipes fill up in turn the process
+    /// has to wait as well. However slowly the output is consumed, only a
+    /// bounded amount of it is ever held in memory. Each chunk is what a
+    /// single read of a pipe returned, of at most `read_buffer_size` bytes.
+    /// Should the receiver be dropped, the rest of the output is discarded.
+    ///
+    /// The returned thread finishes once all of the output has been sent and
+    /// the process has exited, with the result `exec` would have had. Just as
+    /// with `spawn` this doesn't update `last_status`, and in dry run mode
+    /// this always returns an error.
+    pub fn stream_bounded(&self, capacity: usize)
+                          -> CargoResult<(Receiver<OutputChunk>,
+                                          JoinHandle<Result<(), ProcessError>>)> {
+        if self.dry_run {
+            return Err(human(format!("cannot spawn {} in dry run mode", self)))
+        }
+        let (mut command, temp_files) = try!(self.command());
+        if !self.stdout_redirected() {
+            command.stdout(Stdio::piped());
+        }
+        if !self.stderr_redirected() {
+            command.stderr(Stdio::piped());
+        }
+        let start = time::precise_time_ns();
+        let mut child = try!(self.spawn_child(&mut command));
+        let watchdog = self.watch(&child);
+
+        let (tx, rx) = sync_channel(capacity);
+        let size = self.read_buffer_size.unwrap_or(STREAM_CHUNK_SIZE);
+        let mut readers = Vec::new();
+        if let Some(stdout) = child.stdout.take() {
+            readers.push(read_chunks(stdout, size, tx.clone(),
+                                     OutputChunk::Stdout));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            readers.push(read_chunks(stderr, size, tx.clone(),
+                                     OutputChunk::Stderr));
+        }
+        drop(tx);
+
+        let p = self.clone();
+        let handle = thread::spawn(move || {
+            let _temp_files = temp_files;
+            for reader in readers.into_iter() {
+                let _ = reader.join();
+            }
+            let fired = disarm(&child, watchdog, None);
+            let status = try!(retry_interrupted(|| child.wait()).map_err(|e| {
+                p.spawn_error(e)
+            }));
+            match fired {
+                Some(Fired::TimedOut) => {
+                    Err(p.timeout_error(p.timeout.unwrap(), &status, None))
+                }
+                Some(Fired::Cancelled) => Err(p.cancelled_error(&status, None)),
+                None if p.is_success(&status) => Ok(()),
+                None => Err(p.exit_error(&status, None, elapsed_since(start))),
+            }
+        });
+        Ok((rx, handle))
+    }
+
+    /// Like `exec_with_output`, but returns the lines of stdout decoded as
+    /// `output_encoding`, without their terminators.
+    ///
+    /// Empty lines at the end of the output are dropped, so a process which
+    /// prints nothing yields no lines. Output which doesn't decode is an
+    /// error.
+    pub fn exec_with_output_lines(&self) -> CargoResult<Vec<String>> {
+        let output = try!(self.exec_with_output());
+        let stdout = try!(self.decode(output.stdout, "output"));
+        let mut lines = stdout.lines_any().map(|s| s.to_string())
+                              .collect::<Vec<_>>();
+        while lines.last().map(|s| s.is_empty()).unwrap_or(false) {
+            lines.pop();
+        }
+        Ok(lines)
+    }
+
+    /// Like `exec_with_output`, but returns stdout decoded as
+    /// `output_encoding` with any trailing whitespace removed, for commands
+    /// which print a single value.
+    ///
+    /// Output which doesn't decode is an error.
+    pub fn exec_with_output_trimmed(&self) -> CargoResult<String> {
+        let output = try!(self.exec_with_output());
+        let stdout = try!(self.decode(output.stdout, "output"));
+        Ok(stdout.trim_right().to_string())
+    }
+
+    /// Like `exec_with_output`, but returns stdout and stderr decoded as
+    /// `output_encoding`, in that order.
+    ///
+    /// Output which doesn't decode is an error naming the offending stream.
+    pub fn exec_with_strings(&self) -> CargoResult<(String, String)> {
+        let output = try!(self.exec_with_output());
+        let stdout = try!(self.decode(output.stdout, "stdout"));
+        let stderr = try!(self.decode(output.stderr, "stderr"));
+        Ok((stdout, stderr))
+    }
+
+    /// Decodes the `stream` of the process as `output_encoding`.
+    fn decode(&self, bytes: Vec<u8>, stream: &str) -> CargoResult<String> {
+        let decoded = match self.output_encoding {
+            Encoding::Utf8 => String::from_utf8(bytes).ok(),
+            Encoding::Utf16 => decode_utf16_le(&bytes),
+            Encoding::OemCodePage => imp::decode_code_page(&bytes, true),
+            Encoding::AnsiCodePage => imp::decode_code_page(&bytes, false),
+        };
+        decoded.ok_or_else(|| {
+            human(format!("{} of {} was not valid {}", stream,
+                          self.error_name(), self.output_encoding))
+        })
+    }
+
+    /// Like `exec_with_output`, but decodes stdout as JSON into a `T`, for
+    /// tools which print structured output.
+    ///
+    /// Output which doesn't decode is an error quoting the start of it.
+    pub fn exec_with_json<T: Decodable>(&self) -> CargoResult<T> {
+        let output = try!(self.exec_with_output());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        json::decode(&stdout).map_err(|e| {
+            let mut snippet = stdout.chars().take(JSON_SNIPPET_CHARS)
+                                    .collect::<String>();
+            if stdout.chars().count() > JSON_SNIPPET_CHARS {
+                snippet.push_str("...");
+            }
+            human(format!("could not decode the output of {} as JSON: {}\n\
+                           output: {}", self.error_name(), e, snippet.trim()))
+        })
+    }
+
+    /// Like `exec_with_output`, but also returns how long the process took to
+    /// run.
+    pub fn exec_with_output_timed(&self)
+                                  -> Result<(Output, Duration), ProcessError> {
+        let start = time::precise_time_ns();
+        if self.dry_run || self.needs_piped_exec() {
+            let output = try!(self.exec_with_streaming(|_| {}, |_| {}));
+            return Ok((output, elapsed_since(start)))
+        }
+
+        let (mut command, _temp_files) = try!(self.command());
+        self.run_exec_hook();
+        let ticker = self.start_ticker();
+        let output = try!(command.output().map_err(|e| self.spawn_error(e)));
+        let output = self.captured(output);
+        drop(ticker);
+        self.record_status(&output.status, start);
+        let elapsed = elapsed_since(start);
+
+        if self.is_success(&output.status) {
+            Ok((output, elapsed))
+        } else {
+            Err(self.exit_error(&output.status, Some(&output), elapsed))
+        }
+    }
+
+    /// Like `exec_with_output`, but also returns the resources used by the
+    /// process.
+    ///
+    /// The usage is collected when the process is reaped, so it covers the
+    /// process itself and any of its descendants which it waited for, but
+    /// nothing else running concurrently. In dry run mode the usage is all
+    /// zeroes.
+    #[cfg(unix)]
+    pub fn exec_with_output_rusage(&self)
+                                   -> Result<(Output, ResourceUsage), ProcessError> {
+        let (output, _, usage) = try!(self.exec_with_usage());
+        Ok((output, usage))
+    }
+
+    /// Like `exec_with_output`, but also measures how long the process ran for
+    /// and, where the platform reports them, the resources it used.
+    ///
+    /// On Unix the resources are those `exec_with_output_rusage` reports, and
+    /// on Windows they are all `None`.
+    #[cfg(unix)]
+    pub fn exec_instrumented(&self) -> CargoResult<InstrumentedOutput> {
+        let (output, duration, usage) = try!(self.exec_with_usage());
+        Ok(InstrumentedOutput {
+            output: output,
+            duration: duration,
+            user_time: Some(usage.user_time),
+            system_time: Some(usage.system_time),
+            max_rss: Some(usage.max_rss),
+        })
+    }
+
+    /// Like `exec_with_output`, but also measures how long the process ran for.
+    ///
+    /// Resource usage isn't reported on Windows, so those fields of the result
+    /// are always `None`.
+    #[cfg(windows)]
+    pub fn exec_instrumented(&self) -> CargoResult<InstrumentedOutput> {
+        let (output, duration) = try!(self.exec_with_output_timed());
+        Ok(InstrumentedOutput {
+            output: output,
+            duration: duration,
+            user_time: None,
+            system_time: None,
+            max_rss: None,
+        })
+    }
+
+    #[cfg(unix)]
+    fn exec_with_usage(&self)
+                       -> Result<(Output, Duration, ResourceUsage), ProcessError> {
+        let mut usage = ResourceUsage {
+            user_time: Duration::zero(),
+            system_time: Duration::zero(),
+            max_rss: 0,
+        };
+        let mut stdout = self.capture_buf();
+        let mut stderr = self.capture_buf();
+        let mut run = try!(self.exec_piped_with(Capture::Both, |line| {
+            match line {
+                Line::Stdout(line) => stdout.push(&line),
+                Line::Stderr(line) => stderr.push(&line),
+            }
+            true
+        }, |child| {
+            let (status, u) = try!(imp::wait_with_usage(child));
+            usage = u;
+            Ok(status)
+        }));
+        let output = self.captured(piped_output(&mut run, stdout, stderr));
+        let output = try!(self.check_piped(&run, output));
+        Ok((output, run.elapsed, usage))
+    }
+
+    /// Like `exec_with_output`, but runs the process again if it fails to
+    /// spawn or exits unsuccessfully, up to the number of times configured
+    /// through `retries`.
+    ///
+    /// This is meant for commands which wrap flaky operations, such as network
+    /// access. The error returned once every attempt has failed is that of the
+    /// last attempt, noting how many attempts were made.
+    pub fn exec_with_retries(&self) -> Result<Output, ProcessError> {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let err = match self.exec_with_output() {
+                Ok(output) => return Ok(output),
+                Err(err) => err,
+            };
+            if attempts > self.retries {
+                return Err(self.retries_error(err, attempts))
+            }
+            debug!("attempt {} of `{}` failed, retrying: {}", attempts,
+                   self.debug_string(), err);
+            if let Some(dur) = self.retry_backoff {
+                sleep(dur);
+            }
+        }
+    }
+
+    /// Executes the process, handing each line of its stdout and stderr to the
+    /// respective callback as soon as it is read.
+    ///
+    /// Both pipes are drained concurrently so a chatty stderr can't block a
+    /// quiet stdout (or vice versa). The lines passed to the callbacks have
+    /// their line terminator stripped, but the returned `Output` contains the
+    /// unmodified contents of both streams, or their tails if `capture_tail`
+    /// was called.
+    pub fn exec_with_streaming<F, G>(&self, mut on_stdout_line: F,
+                                     mut on_stderr_line: G)
+                                     -> Result<Output, ProcessError>
+        where F: FnMut(&str), G: FnMut(&str)
+    {
+        let mut stdout = self.capture_buf();
+        let mut stderr = self.capture_buf();
+        let mut stdout_throttle = Throttle::new(self.line_rate);
+        let mut stderr_throttle = Throttle::new(self.line_rate);
+        let mut run = try!(self.exec_piped(Capture::Both, |line| {
+            match line {
+                Line::Stdout(line) => {
+                    let (suppressed, pass) = stdout_throttle.admit();
+                    if let Some(n) = suppressed {
+                        on_stdout_line(&self.suppressed_lines(n));
+                    }
+                    if pass {
+                        on_stdout_line(&self.tagged(trim_eol(&line)));
+                    }
+                    stdout.push(&line);
+                }
+                Line::Stderr(line) => {
+                    let (suppressed, pass) = stderr_throttle.admit();
+                    if let Some(n) = suppressed {
+                        on_stderr_line(&self.suppressed_lines(n));
+                    }
+                    if pass {
+                        on_stderr_line(&self.tagged(trim_eol(&line)));
+                    }
+                    stderr.push(&line);
+                }
+            }
+            true
+        }));
+        if let Some(n) = stdout_throttle.finish() {
+            on_stdout_line(&self.suppressed_lines(n));
+        }
+        if let Some(n) = stderr_throttle.finish() {
+            on_stderr_line(&self.suppressed_lines(n));
+        }
+        let output = self.captured(piped_output(&mut run, stdout, stderr));
+        self.check_piped(&run, output)
+    }
+
+    /// Returns the line `throttle_lines` passes on in place of `n` lines.
+    fn suppressed_lines(&self, n: usize) -> String {
+        self.tagged(format!("{} lines suppressed", n).as_bytes())
+    }
+
+    /// Executes the process with its stdout inherited from cargo, returning
+    /// its exit status along with everything it wrote to stderr.
+    ///
+    /// This suits processes whose regular output is meant for the user but
+    /// whose diagnostics we want to parse. The error for an unsuccessful exit
+    /// status includes the captured stderr.
+    pub fn exec_capture_stderr(&self) -> CargoResult<(ExitStatus, Vec<u8>)> {
+        let (run, output) = try!(self.exec_capture(Capture::Stderr));
+        let output = try!(self.check_piped(&run, output));
+        Ok((output.status, output.stderr))
+    }
+
+    /// Like `exec_capture_stderr`, but captures stdout and lets the process
+    /// inherit stderr from cargo instead.
+    pub fn exec_capture_stdout(&self) -> CargoResult<(ExitStatus, Vec<u8>)> {
+        let (run, output) = try!(self.exec_capture(Capture::Stdout));
+        let output = try!(self.check_piped(&run, output));
+        Ok((output.status, output.stdout))
+    }
+
+    /// Like `exec_with_output`, but returns the output of the process whatever
+    /// its exit status is.
+    ///
+    /// This is meant for probing commands whose failure is information rather
+    /// than an error. Failing to spawn the process is still an error, as is
+    /// exceeding the timeout or `max_output_bytes`.
+    pub fn exec_with_output_unchecked(&self) -> CargoResult<Output> {
+        if self.dry_run || self.needs_piped_exec() {
+            let (run, output) = try!(self.exec_capture(Capture::Both));
+            return Ok(try!(self.check_limits(&run, output)))
+        }
+
+        let (mut command, _temp_files) = try!(self.command());
+        self.run_exec_hook();
+        let start = time::precise_time_ns();
+        let ticker = self.start_ticker();
+        let output = try!(command.output().map_err(|e| self.spawn_error(e)));
+        let output = self.captured(output);
+        drop(ticker);
+        self.record_status(&output.status, start);
+        Ok(output)
+    }
+
+    /// Runs the process as `exec_with_output_unchecked` does, returning
+    /// whether its stdout is exactly `expected`.
+    ///
+    /// The exit status of the process is ignored, so only failing to run it
+    /// at all is an error.
+    pub fn output_equals(&self, expected: &[u8]) -> CargoResult<bool> {
+        let output = try!(self.exec_with_output_unchecked());
+        Ok(output.stdout == expected)
+    }
+
+    /// Runs the process with each of `variants` appended to its arguments in
+    /// turn, until one of them exits successfully.
+    ///
+    /// This is for probing tools which may not support every flag, by trying
+    /// progressively simpler sets of arguments. The output of the successful
+    /// run is returned along with the index of its variant. If every variant
+    /// fails the error lists how each of them exited, and failing to run the
+    /// process at all is an error right away.
+    pub fn try_arg_variants(&self, variants: Vec<Vec<OsString>>)
+                            -> CargoResult<(Output, usize)> {
+        let mut attempts = Vec::new();
+        for (i, variant) in variants.into_iter().enumerate() {
+            let mut p = self.clone();
+            p.args(&variant);
+            let output = try!(p.exec_with_output_unchecked());
+            if self.is_success(&output.status) {
+                return Ok((output, i))
+            }
+            attempts.push(format!("{} ({})", p, output.status));
+        }
+        if attempts.is_empty() {
+            return Err(human(format!("no arguments to try for process {}",
+                                     self.error_name())))
+        }
+        Err(human(format!("none of the arguments tried for process {} \
+                           succeeded:\n  {}", self.error_name(),
+                          attempts.connect("\n  "))))
+    }
+
+    /// Runs the process as `exec_with_output_unchecked` does, but reads its
+    /// output into buffers owned by the caller, returning the exit status.
+    ///
+    /// Both buffers are cleared first. Reusing them across many small commands
+    /// saves allocating fresh ones every time.
+    pub fn exec_into(&self, stdout: &mut Vec<u8>, stderr: &mut Vec<u8>)
+                     -> CargoResult<ExitStatus> {
+        stdout.truncate(0);
+        stderr.truncate(0);
+        if self.dry_run || self.needs_piped_exec() {
+            let output = try!(self.exec_with_output_unchecked());
+            stdout.push_all(&output.stdout);
+            stderr.push_all(&output.stderr);
+            return Ok(output.status)
+        }
+
+        let (mut command, _temp_files) = try!(self.command());
+        if !self.stdout_redirected() {
+            command.stdout(Stdio::piped());
+        }
+        if !self.stderr_redirected() {
+            command.stderr(Stdio::piped());
+        }
+        let start = time::precise_time_ns();
+        let ticker = self.start_ticker();
+        let mut child = try!(self.spawn_child(&mut command));
+
+        // Read stderr from another thread so neither pipe can fill up while
+        // we're blocked on the other. The buffer is moved there and back, so
+        // its allocation is still reused. A stream which was redirected
+        // elsewhere leaves its buffer empty.
+        if let Some(mut pipe) = child.stderr.take() {
+            let mut buf = mem::replace(stderr, Vec::new());
+            let reader = thread::spawn(move || {
+                let _ = retry_interrupted(|| pipe.read_to_end(&mut buf));
+                buf
+            });
+            if let Some(ref mut pipe) = child.stdout {
+                let _ = retry_interrupted(|| pipe.read_to_end(stdout));
+            }
+            *stderr = reader.join().unwrap_or(Vec::new());
+        } else if let Some(ref mut pipe) = child.stdout {
+            let _ = retry_interrupted(|| pipe.read_to_end(stdout));
+        }
+        if self.swap_stdio {
+            mem::swap(stdout, stderr);
+        }
+
+        let status = try!(retry_interrupted(|| child.wait()).map_err(|e| {
+            self.spawn_error(e)
+        }));
+        drop(ticker);
+        self.record_status(&status, start);
+        Ok(status)
+    }
+
+    fn exec_capture(&self, capture: Capture)
+                    -> Result<(PipedRun, Output), ProcessError> {
+        let mut stdout = self.capture_buf();
+        let mut stderr = self.capture_buf();
+        let mut run = try!(self.exec_piped(capture, |line| {
+            match line {
+                Line::Stdout(line) => stdout.push(&line),
+                Line::Stderr(line) => stderr.push(&line),
+            }
+            true
+        }));
+        let output = self.captured(piped_output(&mut run, stdout, stderr));
+        Ok((run, output))
+    }
+
+    /// Swaps the streams of `output` around if `swap_stdio` asks for it.
+    fn captured(&self, mut output: Output) -> Output {
+        if self.swap_stdio {
+            mem::swap(&mut output.stdout, &mut output.stderr);
+        }
+        output
+    }
+
+    /// Returns a buffer to capture output from `exec_piped` into.
+    fn capture_buf(&self) -> CaptureBuf {
+        CaptureBuf {
+            data: Vec::new(),
+            limit: self.capture_tail,
+            dropped: 0,
+        }
+    }
+
+    /// Turns the output collected from `exec_piped` into an error if the
+    /// process timed out, produced too much output or exited unsuccessfully.
+    fn check_piped(&self, run: &PipedRun, output: Output)
+                   -> Result<Output, ProcessError> {
+        let output = try!(self.check_limits(run, output));
+        if self.is_success(&output.status) {
+            Ok(output)
+        } else {
+            let err = self.exit_error(&output.status, Some(&output),
+                                      run.elapsed);
+            Err(mark_trimmed(run, err))
+        }
+    }
+
+    /// Like `check_piped`, but doesn't look at the exit status.
+    fn check_limits(&self, run: &PipedRun, output: Output)
+                    -> Result<Output, ProcessError> {
+        let err = if run.fired == Some(Fired::TimedOut) {
+            self.timeout_error(self.timeout.unwrap(), &output.status,
+                               Some(&output))
+        } else if run.fired == Some(Fired::Cancelled) {
+            self.cancelled_error(&output.status, Some(&output))
+        } else if let Some(dropped) = run.dropped {
+            self.truncated_error(dropped, &output)
+        } else {
+            return Ok(output)
+        };
+        Err(mark_trimmed(run, err))
+    }
+
+    /// Executes the process, writing its stdout and stderr to `out` and `err`
+    /// respectively as they are read, and returns its exit status.
+    ///
+    /// Output is written a line at a time. If writing to either of the sinks
+    /// fails the process is killed and the error is returned. As with
+    /// `exec_with_combined_output`, an unsuccessful exit status is not
+    /// considered an error.
+    pub fn exec_to_writer(&self, out: &mut Write, err: &mut Write)
+                          -> CargoResult<ExitStatus> {
+        let mut write_error = None;
+        let run = try!(self.exec_piped(Capture::Both, |line| {
+            let (sink, line): (&mut Write, _) = match line {
+                Line::Stdout(line) => (&mut *out, line),
+                Line::Stderr(line) => (&mut *err, line),
+            };
+            let res = match self.tag {
+                Some(ref tag) => write!(sink, "[{}] ", tag).and_then(|()| {
+                    sink.write_all(&line)
+                }),
+                None => sink.write_all(&line),
+            };
+            match res {
+                Ok(()) => true,
+                Err(e) => { write_error = Some(e); false }
+            }
+        }));
+        if let Some(e) = write_error {
+            return Err(caused_human(format!("failed to write the output of \
+                                             process {}", self.error_name()),
+                                    e))
+        }
+
+        let output = Output {
+            status: run.status,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        };
+        let output = try!(self.check_limits(&run, output));
+        Ok(output.status)
+    }
+
+    /// Executes the process with its stdout inherited from cargo, parsing what
+    /// it writes to stderr as the JSON diagnostics of `rustc` as it goes.
+    ///
+    /// Each diagnostic is passed to `on_message` as soon as the line holding it
+    /// is complete, in the order they were printed. Any other line is passed
+    /// to `on_other_line` without its terminator. As with `exec_to_writer`, an
+    /// unsuccessful exit status is not considered an error.
+    pub fn exec_with_json_messages<F, G>(&self, mut on_message: F,
+                                         mut on_other_line: G)
+                                         -> CargoResult<ExitStatus>
+        where F: FnMut(JsonMessage), G: FnMut(&str)
+    {
+        let run = try!(self.exec_piped(Capture::Stderr, |line| {
+            if let Line::Stderr(line) = line {
+                match parse_json_message(&line) {
+                    Some(message) => on_message(message),
+                    None => on_other_line(&String::from_utf8_lossy(
+                        trim_eol(&line))),
+                }
+            }
+            true
+        }));
+        let output = Output {
+            status: run.status,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        };
+        let output = try!(self.check_limits(&run, output));
+        Ok(output.status)
+    }
+
+    /// Runs the process as `exec` does when `capture_on_failure` is set,
+    /// forwarding its output to `out` and `err`.
+    ///
+    /// Failing to forward the output doesn't stop the process, as it's still
+    /// captured for the error.
+    fn exec_capturing_to(&self, out: &mut Write, err: &mut Write)
+                         -> Result<(), ProcessError> {
+        let mut stdout = self.capture_buf();
+        let mut stderr = self.capture_buf();
+        let mut run = try!(self.exec_piped(Capture::Both, |line| {
+            match line {
+                Line::Stdout(line) => {
+                    let _ = out.write_all(&line);
+                    stdout.push(&line);
+                }
+                Line::Stderr(line) => {
+                    let _ = err.write_all(&line);
+                    stderr.push(&line);
+                }
+            }
+            true
+        }));
+        let output = self.captured(piped_output(&mut run, stdout, stderr));
+        try!(self.check_piped(&run, output));
+        Ok(())
+    }
+
+    /// Runs the process as `exec` does when `replay_on_failure` is set,
+    /// replaying its output to `out` and `err` if it fails.
+    ///
+    /// Failing to replay the output doesn't change the error returned.
+    fn exec_replaying_to(&self, out: &mut Write, err: &mut Write)
+                         -> Result<(), ProcessError> {
+        let mut stdout = self.capture_buf();
+        let mut stderr = self.capture_buf();
+        let mut lines = Vec::new();
+        let mut run = try!(self.exec_piped(Capture::Both, |line| {
+            match line {
+                Line::Stdout(ref line) => stdout.push(line),
+                Line::Stderr(ref line) => stderr.push(line),
+            }
+            lines.push(line);
+            true
+        }));
+        let output = self.captured(piped_output(&mut run, stdout, stderr));
+        let result = self.check_piped(&run, output);
+        if result.is_err() {
+            for line in lines.into_iter() {
+                let _ = match line {
+                    Line::Stdout(line) => out.write_all(&line),
+                    Line::Stderr(line) => err.write_all(&line),
+                };
+            }
+            let _ = out.flush();
+            let _ = err.flush();
+        }
+        result.map(|_| ())
+    }
+
+    /// Executes the process, passing its stdout and stderr on to those of cargo
+    /// while also appending both of them to the file at `log`, and returns its
+    /// exit status.
+    ///
+    /// The log file is created if it doesn't exist. If writing to it fails
+    /// the process is killed and the error is returned. As with
+    /// `exec_to_writer`, an unsuccessful exit status is not considered an
+    /// error.
+    pub fn exec_tee(&self, log: &Path) -> CargoResult<ExitStatus> {
+        let opened = fs::OpenOptions::new().write(true).append(true)
+                                           .create(true).open(log);
+        let mut file = try!(opened.map_err(|e| {
+            caused_human(format!("failed to open log file `{}`",
+                                 log.display()), e)
+        }));
+        let mut write_error = None;
+        let run = try!(self.exec_piped(Capture::Both, |line| {
+            let line = match line {
+                Line::Stdout(line) => { let _ = io::stdout().write_all(&line); line }
+                Line::Stderr(line) => { let _ = io::stderr().write_all(&line); line }
+            };
+            match file.write_all(&line) {
+                Ok(()) => true,
+                Err(e) => { write_error = Some(e); false }
+            }
+        }));
+        if let Some(e) = write_error {
+            return Err(caused_human(format!("failed to write the output of \
+                                             process {} to `{}`",
+                                            self.error_name(), log.display()),
+                                    e))
+        }
+
+        let output = Output {
+            status: run.status,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        };
+        let output = try!(self.check_limits(&run, output));
+        Ok(output.status)
+    }
+
+    /// Executes the process, returning its stdout and stderr merged into a
+    /// single stream along with its exit status.
+    ///
+    /// The output of the two streams is interleaved line by line in the order
+    /// the lines are read, which is a best-effort approximation of the order
+    /// in which they were written. Unlike the other exec methods, an
+    /// unsuccessful exit status is not considered an error.
+    pub fn exec_with_combined_output(&self)
+                                     -> CargoResult<(ExitStatus, Vec<u8>)> {
+        let mut combined = self.capture_buf();
+        let mut run = try!(self.exec_piped(Capture::Both, |line| {
+            match line {
+                Line::Stdout(line) | Line::Stderr(line) => combined.push(&line),
+            }
+            true
+        }));
+        let output = piped_output(&mut run, combined, self.capture_buf());
+        let output = try!(self.check_limits(&run, output));
+        Ok((output.status, output.stdout))
+    }
+
+    /// Runs the process with the streams selected by `capture` piped back to
+    /// us, passing each line read from either of them to `on_line`.
+    ///
+    /// Streams which aren't captured are inherited from cargo, and streams
+    /// which have been redirected to a file are left alone. If the process
+    /// produces more output than `max_output_bytes` allows it is killed, and
+    /// only the lines (or part of a line) within the limit are passed on. The
+    /// process is also killed, and no more lines are passed on, once
+    /// `on_line` returns false.
+    fn exec_piped<F>(&self, capture: Capture, on_line: F)
+                     -> Result<PipedRun, ProcessError>
+        where F: FnMut(Line) -> bool
+    {
+        self.exec_piped_with(capture, on_line, |child| {
+            retry_interrupted(|| child.wait())
+        })
+    }
+
+    /// Like `exec_piped`, but reaps the process through `wait` rather than
+    /// `Child::wait`.
+    fn exec_piped_with<F, W>(&self, capture: Capture, mut on_line: F, wait: W)
+                             -> Result<PipedRun, ProcessError>
+        where F: FnMut(Line) -> bool,
+              W: FnOnce(&mut Child) -> io::Result<ExitStatus>
+    {
+        if self.dry_run {
+            info!("dry run, not executing {}", self);
+            return Ok(PipedRun {
+                status: imp::success_status(),
+                elapsed: Duration::zero(),
+                fired: None,
+                trimmed: 0,
+                dropped: None,
+            })
+        }
+
+        let (mut command, _temp_files) = try!(self.command());
+        let pty = try!(imp::attach_pty(self, &mut command).map_err(|e| {
+            let msg = format!("Could not allocate a pseudo-terminal for \
+                               process {}", self.error_name());
+            self.process_error(&msg, Some(e), None, None)
+        }));
+        let merged = match pty {
+            Some(_) => None,
+            None => try!(imp::merge_stderr(self, &mut command).map_err(|e| {
+                self.spawn_error(e)
+            })),
+        };
+        let pty = pty.or(merged);
+        if pty.is_none() && !self.stdout_redirected() {
+            command.stdout(if capture != Capture::Stderr {
+                Stdio::piped()
+            } else {
+                Stdio::inherit()
+            });
+        }
+        if pty.is_none() && !self.stderr_redirected() {
+            command.stderr(if capture != Capture::Stdout {
+                Stdio::piped()
+            } else {
+                Stdio::inherit()
+            });
+        }
+        let start = time::precise_time_ns();
+        let mut child = try!(self.spawn_child(&mut command));
+        let watchdog = self.watch(&child);
+        let ticker = self.start_ticker();
+        let forwarder = imp::forward_signals(self, &child);
+        // Reading the terminal, or the pipe of `redirect_stderr_to_stdout`,
+        // only hits EOF once every copy of its other end is closed, including
+        // the ones held by `command`.
+        drop(command);
+
+        let (tx, rx) = channel();
+        let size = self.read_buffer_size;
+        if let Some(pty) = pty {
+            read_lines(pty, size, tx.clone(), Line::Stdout);
+        }
+        if let Some(stdout) = child.stdout.take() {
+            read_lines(stdout, size, tx.clone(), Line::Stdout);
+        }
+        if let Some(stderr) = child.stderr.take() {
+            read_lines(stderr, size, tx.clone(), Line::Stderr);
+        }
+        drop(tx);
+
+        let mut captured = 0;
+        let mut dropped = None;
+        let mut stopped = false;
+        for line in rx.iter() {
+            if stopped { continue }
+            let line = match self.max_output_bytes {
+                Some(max) if captured + line.len() > max => {
+                    // Keep reading until the pipes are closed so we find out
+                    // how much output was dropped, but make sure that happens
+                    // soon.
+                    if dropped.is_none() {
+                        let _ = child.kill();
+                    }
+                    let keep = max - captured;
+                    dropped = Some(dropped.unwrap_or(0) + line.len() - keep);
+                    captured = max;
+                    if keep == 0 { continue }
+                    line.truncate(keep)
+                }
+                _ => {
+                    captured += line.len();
+                    line
+                }
+            };
+            if !on_line(line) {
+                stopped = true;
+                let _ = child.kill();
+            }
+        }
+
+        let fired = disarm(&child, watchdog, forwarder);
+        let status = try!(wait(&mut child).map_err(|e| self.spawn_error(e)));
+        drop(ticker);
+        self.record_status(&status, start);
+        Ok(PipedRun {
+            status: status,
+            elapsed: elapsed_since(start),
+            fired: fired,
+            trimmed: 0,
+            dropped: dropped,
+        })
+    }
+
+    /// Returns the exit status of the process the last time it was run by one
+    /// of the exec methods, whether it succeeded or not.
+    ///
+    /// This is `None` if the process hasn't been run yet, or if it has only
+    /// failed to spawn, been run in dry run mode or been started through
+    /// `spawn`. Clones of the builder start out without a status.
+    pub fn last_status(&self) -> Option<ExitStatus> {
+        (self.last_status.0).0.lock().unwrap().clone()
+    }
+
+    /// Remembers `status` for `last_status` and tells the recorder, if any,
+    /// that the process which was started at `start` has exited.
+    fn record_status(&self, status: &ExitStatus, start: u64) {
+        *(self.last_status.0).0.lock().unwrap() = Some(status.clone());
+        if let Some(Ignored(ref recorder)) = self.recorder {
+            recorder.record(&self.recorded_command(status, start));
+        }
+    }
+
+    fn recorded_command(&self, status: &ExitStatus, start: u64)
+                        -> RecordedCommand {
+        let lossy = |s: &OsStr| s.to_string_lossy().into_owned();
+        let (added, changed, removed) = self.env_diff();
+        RecordedCommand {
+            program: lossy(&self.program),
+            args: self.final_args().iter().map(|arg| lossy(arg)).collect(),
+            cwd: self.get_cwd().map(|cwd| cwd.display().to_string()),
+            env_added: added.into_iter().map(|(key, val)| {
+                (key, lossy(&val))
+            }).collect(),
+            env_changed: changed.into_iter().map(|(key, val)| {
+                (key, lossy(&val))
+            }).collect(),
+            env_removed: removed,
+            code: status.code(),
+            signal: exit_signal(status),
+            duration_ms: elapsed_since(start).num_milliseconds(),
+        }
+    }
+
+    /// Spawns the process without waiting for it to finish.
+    ///
+    /// The process is configured exactly as it would be by `exec`, and it's up
+    /// to the caller to wait on it through the returned handle, otherwise it
+    /// is killed once the handle is dropped. Any timeout configured is not
+    /// enforced for processes spawned this way, and in dry run mode this
+    /// always returns an error.
+    pub fn spawn(&self) -> CargoResult<ProcessHandle> {
+        if self.dry_run {
+            return Err(human(format!("cannot spawn {} in dry run mode", self)))
+        }
+        let (mut command, temp_files) = try!(self.command());
+        let child = try!(self.spawn_child(&mut command));
+        Ok(ProcessHandle {
+            child: Some(child),
+            waited: false,
+            temp_files: temp_files,
+        })
+    }
+
+    /// Spawns the process with its stderr piped, along with a thread which
+    /// sends each line the process writes to stderr over the returned channel.
+    ///
+    /// Lines are sent without their terminator, and are decoded lossily if
+    /// they aren't valid UTF-8. The channel is closed once stderr reaches EOF,
+    /// which is when the process and any children sharing its stderr exit. If
+    /// the receiver is dropped before then, the thread goes on to discard the
+    /// rest of stderr, so that the process isn't hit by a broken pipe. Unlike
+    /// with `spawn` the process is left running if the `Child` is dropped.
+    ///
+    /// As there would be no lines to send, it's an error for stderr to have
+    /// been redirected elsewhere, whether through `stderr_to_file`, `silent`
+    /// or `redirect_stderr_to_stdout`. In dry run mode this always returns an
+    /// error too.
+    pub fn spawn_with_stderr_lines(&self)
+                                   -> CargoResult<(Child, Receiver<String>)> {
+        if self.dry_run {
+            return Err(human(format!("cannot spawn {} in dry run mode", self)))
+        }
+        if self.stderr_redirected() {
+            return Err(human(format!("cannot read the stderr of {} as it \
+                                      is redirected", self)))
+        }
+        let (mut command, temp_files) = try!(self.command());
+        command.stderr(Stdio::piped());
+        let mut child = try!(self.spawn_child(&mut command));
+        let stderr = child.stderr.take().unwrap();
+        let size = self.read_buffer_size;
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            // The process is done with its temporary files by the time its
+            // stderr is closed, bar any children it left running.
+            let _temp_files = temp_files;
+            let mut pipe = match size {
+                Some(size) => BufReader::with_capacity(size, stderr),
+                None => BufReader::new(stderr),
+            };
+            let mut receiving = true;
+            loop {
+                let mut line = Vec::new();
+                match retry_interrupted(|| pipe.read_until(b'\n', &mut line)) {
+                    Ok(0) | Err(..) => break,
+                    Ok(..) => {}
+                }
+                if receiving {
+                    let line = String::from_utf8_lossy(trim_eol(&line));
+                    receiving = tx.send(line.into_owned()).is_ok();
+                }
+            }
+        });
+        Ok((child, rx))
+    }
+
+    /// Starts the process as a daemon as described by `detach`, whether or not
+    /// that was enabled, and returns its pid without waiting for it.
+    ///
+    /// Only failing to start the process is an error. In dry run mode this
+    /// always returns an error.
+    pub fn exec_detached(&self) -> CargoResult<u32> {
+        if self.dry_run {
+            return Err(human(format!("cannot spawn {} in dry run mode", self)))
+        }
+        let mut p = self.clone();
+        p.detach(true);
+        Ok(try!(p.spawn_detached()))
+    }
+
+    fn spawn_detached(&self) -> Result<u32, ProcessError> {
+        let (mut command, temp_files) = try!(self.command());
+        if self.stdin_file.is_none() && self.stdin_data.is_none() {
+            command.stdin(Stdio::null());
+        }
+        if !self.stdout_redirected() {
+            command.stdout(Stdio::null());
+        }
+        if !self.stderr_redirected() {
+            command.stderr(Stdio::null());
+        }
+        let mut child = try!(self.spawn_child(&mut command));
+        let pid = child.id();
+
+        // For as long as cargo is running, processes which exit are reaped
+        // rather than left behind as zombies, and their temporary files are
+        // removed. Otherwise the files have to be left behind, as the process
+        // may still read them at any point.
+        thread::spawn(move || {
+            let _ = retry_interrupted(|| child.wait());
+            drop(temp_files);
+        });
+        Ok(pid)
+    }
+
+    /// Spawns `command`, taking care of the parts of the configuration which
+    /// can't be expressed on the `Command` itself.
+    fn spawn_child(&self, command: &mut Command) -> Result<Child, ProcessError> {
+        if self.stdin_data.is_some() {
+            command.stdin(Stdio::piped());
+        }
+
+        self.run_exec_hook();
+        let mut child = try!(command.spawn().map_err(|e| self.spawn_error(e)));
+        if let Some(Ignored(ref hook)) = self.on_spawn {
+            hook(child.id());
+        }
+
+        // Write stdin from another thread so a child which fills up its
+        // stdout before reading all of its input can't deadlock us. Dropping
+        // the pipe once we're done gives the child EOF.
+        if let Some(ref data) = self.stdin_data {
+            let mut stdin = child.stdin.take().unwrap();
+            let data = data.clone();
+            thread::spawn(move || {
+                let _ = stdin.write_all(&data);
+            });
+        }
+
+        Ok(child)
+    }
+
+    fn run_exec_hook(&self) {
+        if let Some(Ignored(ref sink)) = self.verbose {
+            let mut sink = sink.lock().unwrap();
+            let _ = writeln!(sink, "Running {}", self.error_name());
+        }
+        if let Some(ref hook) = self.on_exec {
+            (hook.0)(self);
+        }
+    }
+
+    /// Whether the configuration requires the process to be run through
+    /// `exec_piped` to capture its output.
+    ///
+    /// This includes having an `on_spawn` callback or forwarding signals, as
+    /// `Command::output` never hands out the pid of the process.
+    fn needs_piped_exec(&self) -> bool {
+        self.timeout.is_some() || self.cancel.is_some() ||
+            self.stdin_data.is_some() || self.max_output_bytes.is_some() ||
+            self.capture_tail.is_some() || self.pty ||
+            self.on_spawn.is_some() || self.read_buffer_size.is_some() ||
+            self.forwards_signals() ||
+            (cfg!(windows) && self.redirect_stderr_to_stdout)
+    }
+
+    #[cfg(unix)]
+    fn forwards_signals(&self) -> bool { !self.forward_signals.is_empty() }
+
+    #[cfg(windows)]
+    fn forwards_signals(&self) -> bool { false }
+
+    /// Starts a watchdog for `child` if a timeout has been configured or the
+    /// process can be cancelled.
+    fn watch(&self, child: &Child) -> Option<Watchdog> {
+        if self.timeout.is_none() && self.cancel.is_none() {
+            return None
+        }
+        let cancel = self.cancel.as_ref().map(|&Ignored(ref flag)| {
+            flag.clone()
+        });
+        Some(Watchdog::start(child.id(), self.timeout, cancel,
+                             self.kill_process_group, imp::timeout_signal(self)))
+    }
+
+    /// Starts calling the callback given to `progress_tick`, if any, until the
+    /// returned ticker is dropped.
+    fn start_ticker(&self) -> Option<Ticker> {
+        self.progress_tick.as_ref().map(|&Ignored(ref cb)| {
+            let interval = self.progress_interval.unwrap_or_else(|| {
+                Duration::milliseconds(PROGRESS_INTERVAL_MS)
+            });
+            // An interval of zero would have the ticker spin.
+            let interval = cmp::max(interval, Duration::milliseconds(1));
+            Ticker::start(cb.clone(), interval)
+        })
+    }
+}
+
+/// A process started by `ProcessBuilder::spawn`.
+///
+/// The process is killed when the handle is dropped, unless it was waited on
+/// through `wait` or released with `forget`, so that an early return on error
+/// doesn't leave processes running in the background. The underlying `Child`
+/// is available through `Deref` for reading its pipes and the like.
+pub struct ProcessHandle {
+    child: Option<Child>,
+    waited: bool,
+    temp_files: Vec<TempFile>,
+}
+
+impl ProcessHandle {
+    /// Waits for the process to exit, after which dropping the handle no
+    /// longer kills it.
+    pub fn wait(&mut self) -> io::Result<ExitStatus> {
+        let child = self.child.as_mut().unwrap();
+        let status = try!(retry_interrupted(|| child.wait()));
+        self.waited = true;
+        Ok(status)
+    }
+
+    /// Releases the process so that it keeps running once the handle is
+    /// dropped.
+    ///
+    /// Any temporary files written for the process, such as an argfile, are
+    /// left behind, as there's no telling when the process is done with them.
+    pub fn forget(mut self) -> Child {
+        for file in mem::replace(&mut self.temp_files, Vec::new()).into_iter() {
+            file.keep();
+        }
+        self.child.take().unwrap()
+    }
+}
+
+impl Deref for ProcessHandle {
+    type Target = Child;
+    fn deref(&self) -> &Child { self.child.as_ref().unwrap() }
+}
+
+impl DerefMut for ProcessHandle {
+    fn deref_mut(&mut self) -> &mut Child { self.child.as_mut().unwrap() }
+}
+
+impl Drop for ProcessHandle {
+    fn drop(&mut self) {
+        if let Some(ref mut child) = self.child {
+            if !self.waited {
+                // Reap the process as well, so it doesn't linger as a zombie.
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+        }
+    }
+}
+
+/// The resources used by a process, as reported by `wait4`.
+#[cfg(unix)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ResourceUsage {
+    /// CPU time spent in user mode.
+    pub user_time: Duration,
+    /// CPU time spent in the kernel on behalf of the process.
+    pub system_time: Duration,
+    /// The peak resident set size, in bytes.
+    pub max_rss: u64,
+}
+
+/// How the output of a process is decoded, as set by `output_encoding`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Encoding {
+    /// UTF-8, which is what nearly every tool prints.
+    Utf8,
+    /// UTF-16 in little-endian byte order, as printed by Windows tools such as
+    /// `cmd /U`. A leading byte order mark is dropped.
+    Utf16,
+    /// The active OEM code page, which Windows console applications print in.
+    OemCodePage,
+    /// The active ANSI code page, which other Windows applications print in.
+    AnsiCodePage,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Encoding::Utf8 => write!(f, "UTF-8"),
+            Encoding::Utf16 => write!(f, "UTF-16"),
+            Encoding::OemCodePage => write!(f, "text in the OEM code page"),
+            Encoding::AnsiCodePage => write!(f, "text in the ANSI code page"),
+        }
+    }
+}
+
+/// A piece of the output of a process run by `stream_bounded`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum OutputChunk {
+    Stdout(Vec<u8>),
+    Stderr(Vec<u8>),
+}
+
+/// How a process run by `exec_outcome` ended, with the output it produced.
+#[derive(Clone)]
+pub enum CommandOutcome {
+    Success(Output),
+    /// The process exited with a status other than the ones counted as
+    /// success, which is repeated here for convenience.
+    Failure(Output, ExitStatus),
+    /// The process was killed for running past its timeout.
+    TimedOut(Output),
+}
+
+/// The output of a process run by `exec_instrumented`, along with measurements
+/// of the run.
+#[derive(Clone)]
+pub struct InstrumentedOutput {
+    pub output: Output,
+    /// How long the process ran for.
+    pub duration: Duration,
+    /// CPU time spent in user mode, if the platform reports it.
+    pub user_time: Option<Duration>,
+    /// CPU time spent in the kernel on behalf of the process, if the platform
+    /// reports it.
+    pub system_time: Option<Duration>,
+    /// The peak resident set size in bytes, if the platform reports it.
+    pub max_rss: Option<u64>,
+}
+
+/// Which of the output streams of a process `exec_piped` captures.
+#[derive(Clone, Copy, PartialEq)]
+enum Capture {
+    Both,
+    Stdout,
+    Stderr,
+}
+
+struct PipedRun {
+    status: ExitStatus,
+    elapsed: Duration,
+    /// Why the watchdog killed the process, if it did.
+    fired: Option<Fired>,
+    /// The number of bytes dropped from the start of the captured output to
+    /// respect `capture_tail`.
+    trimmed: usize,
+    /// The number of bytes of output which were dropped, if any, because the
+    /// process exceeded `max_output_bytes`.
+    dropped: Option<usize>,
+}
+
+/// Output captured from a process, of which only the last `limit` bytes are
+/// kept if there is a limit.
+///
+/// Up to twice the limit is held before the excess is dropped from the front
+/// in one go, so that each byte is only moved about once.
+struct CaptureBuf {
+    data: Vec<u8>,
+    limit: Option<usize>,
+    dropped: usize,
+}
+
+impl CaptureBuf {
+    fn push(&mut self, bytes: &[u8]) {
+        let limit = match self.limit {
+            Some(limit) => limit,
+            None => return self.data.push_all(bytes),
+        };
+        // Only the end of `bytes` can be kept, however much is held already.
+        let skip = if bytes.len() > limit {bytes.len() - limit} else {0};
+        self.dropped += skip;
+        self.data.push_all(&bytes[skip..]);
+        if self.data.len() > 2 * limit {
+            self.trim(limit);
+        }
+    }
+
+    /// Drops all but the last `limit` bytes held.
+    fn trim(&mut self, limit: usize) {
+        if self.data.len() <= limit {
+            return
+        }
+        let excess = self.data.len() - limit;
+        for i in 0..limit {
+            self.data[i] = self.data[excess + i];
+        }
+        self.data.truncate(limit);
+        self.dropped += excess;
+    }
+
+    /// Returns the output kept, along with how many bytes were dropped.
+    fn into_data(mut self) -> (Vec<u8>, usize) {
+        if let Some(limit) = self.limit {
+            self.trim(limit);
+        }
+        (self.data, self.dropped)
+    }
+}
+
+/// Assembles the output captured from `exec_piped`, noting in `run` how much
+/// of it was dropped.
+fn piped_output(run: &mut PipedRun, stdout: CaptureBuf, stderr: CaptureBuf)
+                -> Output {
+    let (stdout, stdout_dropped) = stdout.into_data();
+    let (stderr, stderr_dropped) = stderr.into_data();
+    run.trimmed = stdout_dropped + stderr_dropped;
+    Output {
+        status: run.status,
+        stdout: stdout,
+        stderr: stderr,
+    }
+}
+
+/// Counts the output `capture_tail` dropped towards the `truncated` field of
+/// an error about a process run by `exec_piped`.
+fn mark_trimmed(run: &PipedRun, mut err: ProcessError) -> ProcessError {
+    if run.trimmed > 0 {
+        err.truncated = Some(err.truncated.unwrap_or(0) + run.trimmed);
+    }
+    err
+}
+
+enum Line {
+    Stdout(Vec<u8>),
+    Stderr(Vec<u8>),
+}
+
+impl Line {
+    fn len(&self) -> usize {
+        match *self {
+            Line::Stdout(ref line) | Line::Stderr(ref line) => line.len(),
+        }
+    }
+
+    fn truncate(self, len: usize) -> Line {
+        match self {
+            Line::Stdout(mut line) => { line.truncate(len); Line::Stdout(line) }
+            Line::Stderr(mut line) => { line.truncate(len); Line::Stderr(line) }
+        }
+    }
+}
+
+/// Spawns a thread which reads `pipe` until EOF, sending each line (including
+/// its terminator, if any) over `tx`.
+///
+/// The final line is sent even if it isn't terminated by a newline. The pipe
+/// is read through a buffer of `size` bytes, or the default size if `None`.
+fn read_lines<R, F>(pipe: R, size: Option<usize>, tx: Sender<Line>, wrap: F)
+    where R: Read + Send + 'static, F: Fn(Vec<u8>) -> Line + Send + 'static
+{
+    thread::spawn(move || {
+        let mut pipe = match size {
+            Some(size) => BufReader::with_capacity(size, pipe),
+            None => BufReader::new(pipe),
+        };
+        loop {
+            let mut line = Vec::new();
+            // Anything read before an interruption is kept in `line`, so
+            // the rest of the line is simply appended to it.
+            match retry_interrupted(|| pipe.read_until(b'\n', &mut line)) {
+                Ok(0) | Err(..) => break,
+                Ok(..) => {}
+            }
+            if tx.send(wrap(line)).is_err() { break }
+        }
+    });
+}
+
+/// Spawns a thread which reads `pipe` until EOF, sending what each read
+/// returns over `tx`, at most `size` bytes at a time.
+///
+/// Once the receiver is gone the rest of the pipe is read and discarded, so
+/// the process writing to it isn't hit by a broken pipe.
+fn read_chunks<R, F>(mut pipe: R, size: usize, tx: SyncSender<OutputChunk>,
+                     wrap: F) -> JoinHandle<()>
+    where R: Read + Send + 'static, F: Fn(Vec<u8>) -> OutputChunk + Send + 'static
+{
+    thread::spawn(move || {
+        let mut buf = repeat(0).take(cmp::max(size, 1)).collect::<Vec<u8>>();
+        let mut receiving = true;
+        loop {
+            let n = match retry_interrupted(|| pipe.read(&mut buf)) {
+                Ok(0) | Err(..) => break,
+                Ok(n) => n,
+            };
+            if receiving {
+                receiving = tx.send(wrap(buf[..n].to_vec())).is_ok();
+            }
+        }
+    })
+}
+
+/// Decodes `bytes` as UTF-16 in little-endian byte order, dropping a leading
+/// byte order mark.
+fn decode_utf16_le(bytes: &[u8]) -> Option<String> {
+    if bytes.len() % 2 != 0 { return None }
+    let units = bytes.chunks(2).map(|pair| {
+        pair[0] as u16 | (pair[1] as u16) << 8
+    }).collect::<Vec<u16>>();
+    let units = if units.first() == Some(&0xfeff) {&units[1..]} else {&units[..]};
+    String::from_utf16(units).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+    use std::fs::File;
+    use std::io::prelude::*;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use tempdir::TempDir;
+
+    use util::process::tests::shell;
+    use util::process::process;
+
+    #[test]
+    fn exit_code_is_reported() {
+        let err = shell("exit 3").exec().unwrap_err();
+        assert_eq!(err.code(), Some(3));
+
+        let err = shell("exit 3").exec_with_output().unwrap_err();
+        assert_eq!(err.code(), Some(3));
+    }
+
+    #[test]
+    fn missing_program_did_not_start() {
+        let err = process("cargo-no-such-program").exec().unwrap_err();
+        assert!(err.did_not_start());
+        assert_eq!(err.code(), None);
+    }
+
+    #[test]
+    fn failing_program_did_start() {
+        let err = shell("exit 1").exec_with_output().unwrap_err();
+        assert!(!err.did_not_start());
+        assert_eq!(err.code(), Some(1));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_is_timed() {
+        use std::time::Duration;
+
+        let (_, dur) = shell("sleep 1").exec_with_output_timed().unwrap();
+        assert!(dur >= Duration::seconds(1));
+
+        let err = shell("sleep 1; exit 1").exec().unwrap_err();
+        assert!(err.duration.unwrap() >= Duration::seconds(1));
+        assert!(err.desc.contains("after 1."), "{}", err.desc);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_with_retries_retries_failures() {
+        let td = TempDir::new("cargo").unwrap();
+        // Fails on the first two runs, succeeding on the third.
+        let script = "n=$(cat count 2>/dev/null || echo 0); n=$((n + 1)); \
+                      echo $n > count; [ $n -ge 3 ]";
+
+        let mut p = shell(script);
+        p.cwd(td.path()).retries(1);
+        let err = p.exec_with_retries().unwrap_err();
+        assert!(err.desc.starts_with("2 attempts to run"), "{}", err.desc);
+
+        p.retries(5);
+        assert!(p.exec_with_retries().is_ok());
+    }
+
+    #[test]
+    fn exec_with_retries_reports_attempts() {
+        let mut p = shell("exit 1");
+        p.retries(2);
+        let err = p.exec_with_retries().unwrap_err();
+        assert!(err.desc.starts_with("3 attempts to run"), "{}", err.desc);
+        assert_eq!(err.code(), Some(1));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn combined_output_interleaves_streams() {
+        let p = shell("echo out1; sleep 0.2; echo err1 >&2; sleep 0.2; \
+                       echo out2; sleep 0.2; echo err2 >&2; exit 4");
+        let (status, output) = p.exec_with_combined_output().unwrap();
+        assert_eq!(status.code(), Some(4));
+        assert_eq!(String::from_utf8(output).unwrap(),
+                   "out1\nerr1\nout2\nerr2\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_capture_stderr_only_captures_stderr() {
+        let p = shell("echo out; echo err >&2");
+        let (status, stderr) = p.exec_capture_stderr().unwrap();
+        assert!(status.success());
+        assert_eq!(String::from_utf8(stderr).unwrap(), "err\n");
+
+        let (status, stdout) = p.exec_capture_stdout().unwrap();
+        assert!(status.success());
+        assert_eq!(String::from_utf8(stdout).unwrap(), "out\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_capture_stderr_reports_stderr_on_failure() {
+        let p = shell("echo oh no >&2; exit 2");
+        let err = p.exec_capture_stderr().unwrap_err();
+        assert!(err.to_string().contains("oh no"), "{}", err);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_with_output_in_overrides_cwd() {
+        let a = TempDir::new("cargo").unwrap();
+        let b = TempDir::new("cargo").unwrap();
+        let p = shell("pwd");
+        for dir in [a.path(), b.path()].iter() {
+            let output = p.exec_with_output_in(dir).unwrap();
+            let stdout = String::from_utf8(output.stdout).unwrap();
+            assert_eq!(Path::new(stdout.trim()), *dir);
+        }
+        assert!(p.exec_in(a.path()).is_ok());
+        assert_eq!(p.get_cwd(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_with_json_messages_parses_lines_as_they_complete() {
+        // The first diagnostic is split across two writes, with a pause in
+        // between so they can't end up in the same read.
+        let p = shell("{ printf '{\"message\":\"first\",'; sleep 0.2; \
+                         printf '\"level\":\"warning\",\"spans\":[]}\\n'; \
+                         echo 'not json'; \
+                         echo '{\"message\":\"second\",\"level\":\"error\",\
+                               \"spans\":[]}'; } >&2; exit 1");
+        let mut messages = Vec::new();
+        let mut other = Vec::new();
+        let status = p.exec_with_json_messages(|m| {
+            messages.push((m.level, m.message))
+        }, |line| other.push(line.to_string())).unwrap();
+        assert_eq!(status.code(), Some(1));
+        assert_eq!(messages, [("warning".to_string(), "first".to_string()),
+                              ("error".to_string(), "second".to_string())]);
+        assert_eq!(other, ["not json".to_string()]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_to_writer_writes_both_streams() {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let p = shell("echo out1; echo err1 >&2; echo out2; exit 2");
+        let status = p.exec_to_writer(&mut out, &mut err).unwrap();
+        assert_eq!(status.code(), Some(2));
+        assert_eq!(String::from_utf8(out).unwrap(), "out1\nout2\n");
+        assert_eq!(String::from_utf8(err).unwrap(), "err1\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_with_output_lines_splits_stdout() {
+        let lines = shell("printf 'a\\nb c\\n\\nd\\n\\n\\n'")
+                        .exec_with_output_lines().unwrap();
+        assert_eq!(lines, ["a", "b c", "", "d"]);
+
+        let lines = shell("true").exec_with_output_lines().unwrap();
+        assert!(lines.is_empty());
+
+        assert!(shell("printf '\\377\\n'").exec_with_output_lines().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_with_output_trimmed_trims_the_end() {
+        let value = shell("echo '  abc123'").exec_with_output_trimmed().unwrap();
+        assert_eq!(value, "  abc123");
+        let value = shell("printf value").exec_with_output_trimmed().unwrap();
+        assert_eq!(value, "value");
+        let value = shell("printf 'a\nb \n\n'").exec_with_output_trimmed()
+                                                   .unwrap();
+        assert_eq!(value, "a\nb");
+        assert_eq!(shell("true").exec_with_output_trimmed().unwrap(), "");
+
+        assert!(shell("printf '\377\n'").exec_with_output_trimmed().is_err());
+        assert!(shell("echo x; exit 1").exec_with_output_trimmed().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_with_strings_decodes_both_streams() {
+        let (stdout, stderr) = shell("printf 'out\\n'; printf 'caf\\303\\251' >&2")
+                                   .exec_with_strings().unwrap();
+        assert_eq!(stdout, "out\n");
+        assert_eq!(stderr, "caf\u{e9}");
+
+        let err = shell("printf out; printf '\\377' >&2").exec_with_strings()
+                                                        .unwrap_err();
+        assert!(err.to_string().starts_with("stderr of"), "{}", err);
+        assert!(shell("printf out; exit 1").exec_with_strings().is_err());
+    }
+
+    #[test]
+    fn output_equals_compares_stdout() {
+        let p = shell("echo hello");
+        assert!(!p.output_equals(b"goodbye\n").unwrap());
+        assert!(!p.output_equals(b"hello").unwrap());
+        assert!(p.output_equals(&p.exec_with_output().unwrap().stdout).unwrap());
+
+        // The exit status makes no difference.
+        assert!(shell("exit 1").output_equals(b"").unwrap());
+        assert!(process("cargo-no-such-program").output_equals(b"").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_with_output_unchecked_returns_failed_output() {
+        let p = shell("echo out; echo err >&2; exit 3");
+        let output = p.exec_with_output_unchecked().unwrap();
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "out\n");
+        assert_eq!(String::from_utf8(output.stderr).unwrap(), "err\n");
+
+        assert!(process("cargo-no-such-program").exec_with_output_unchecked()
+                                                .is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dropping_a_process_handle_kills_it() {
+        use libc;
+
+        let handle = shell("sleep 30").spawn().unwrap();
+        let pid = handle.id() as libc::pid_t;
+        drop(handle);
+        assert_eq!(unsafe { libc::kill(pid, 0) }, -1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stream_bounded_applies_backpressure() {
+        use std::fs;
+        use std::time::Duration;
+        use util::process::watchdog::sleep;
+        use super::OutputChunk;
+
+        let td = TempDir::new("cargo").unwrap();
+        let mut p = shell("head -c 4000000 /dev/zero; echo err >&2; touch done");
+        p.cwd(td.path());
+        let (rx, handle) = p.stream_bounded(4).unwrap();
+
+        // Nothing is consumed yet, so the process can't get far.
+        sleep(Duration::milliseconds(300));
+        assert!(fs::metadata(&td.path().join("done")).is_err());
+
+        let (mut stdout, mut stderr) = (0, Vec::new());
+        for (i, chunk) in rx.iter().enumerate() {
+            if i % 100 == 0 {
+                sleep(Duration::milliseconds(1));
+            }
+            match chunk {
+                OutputChunk::Stdout(data) => stdout += data.len(),
+                OutputChunk::Stderr(data) => stderr.push_all(&data),
+            }
+        }
+        assert_eq!(stdout, 4000000);
+        assert_eq!(stderr, b"err\n");
+        handle.join().unwrap().unwrap();
+        assert!(fs::metadata(&td.path().join("done")).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn spawn_with_stderr_lines_sends_each_line() {
+        let p = shell("echo starting >&2; printf 'listening' >&2");
+        let (mut child, rx) = p.spawn_with_stderr_lines().unwrap();
+        assert_eq!(rx.recv().unwrap(), "starting");
+        assert_eq!(rx.recv().unwrap(), "listening");
+        assert!(rx.recv().is_err());
+        assert!(child.wait().unwrap().success());
+
+        // The process keeps running normally after the receiver is gone.
+        let p = shell("echo ready >&2; sleep 1; i=0; \
+                       while [ $i -lt 1000 ]; do echo $i >&2; \
+                       i=$((i + 1)); done");
+        let (mut child, rx) = p.spawn_with_stderr_lines().unwrap();
+        assert_eq!(rx.recv().unwrap(), "ready");
+        drop(rx);
+        assert!(child.wait().unwrap().success());
+
+        let mut p = shell("echo lost >&2");
+        p.silent(true);
+        let err = p.spawn_with_stderr_lines().unwrap_err();
+        assert!(err.to_string().contains("is redirected"), "{}", err);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn relative_program_is_relative_to_cwd() {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let td = TempDir::new("cargo").unwrap();
+        let script = td.path().join("script");
+        File::create(&script).unwrap()
+            .write_all(b"#!/bin/sh\necho hello\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut p = process("./script");
+        p.cwd(td.path());
+        let output = p.exec_with_output().unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "hello\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn signals_to_cargo_do_not_interrupt_children() {
+        use libc;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::thread;
+        use std::time::Duration;
+        use util::process::watchdog::sleep;
+
+        let done = Arc::new(AtomicBool::new(false));
+        let done2 = done.clone();
+        let signaller = thread::spawn(move || {
+            while !done2.load(Ordering::SeqCst) {
+                unsafe { libc::kill(libc::getpid(), libc::SIGWINCH); }
+                sleep(Duration::milliseconds(20));
+            }
+        });
+
+        assert!(shell("sleep 0.5").exec().is_ok());
+        let p = shell("sleep 0.5; echo done");
+        let output = p.exec_with_streaming(|_| {}, |_| {}).unwrap();
+        assert_eq!(output.stdout, b"done\n".to_vec());
+
+        done.store(true, Ordering::SeqCst);
+        signaller.join().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn capture_on_failure_streams_output() {
+        let mut p = shell("echo out; echo err >&2");
+        p.capture_on_failure(true);
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        p.exec_capturing_to(&mut out, &mut err).unwrap();
+        assert_eq!(out, b"out\n".to_vec());
+        assert_eq!(err, b"err\n".to_vec());
+        assert!(p.exec().is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn replay_on_failure_is_silent_on_success() {
+        let mut p = shell("echo out; echo err >&2");
+        p.replay_on_failure(true);
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        p.exec_replaying_to(&mut out, &mut err).unwrap();
+        assert!(out.is_empty());
+        assert!(err.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn replay_on_failure_replays_output() {
+        let mut p = shell("echo one; echo two >&2; echo three; exit 1");
+        p.replay_on_failure(true);
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let e = p.exec_replaying_to(&mut out, &mut err).unwrap_err();
+        assert_eq!(out, b"one\nthree\n".to_vec());
+        assert_eq!(err, b"two\n".to_vec());
+        let output = e.output.unwrap();
+        assert_eq!(output.stdout, b"one\nthree\n".to_vec());
+        assert_eq!(output.stderr, b"two\n".to_vec());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_tee_appends_output_to_the_log() {
+        let td = TempDir::new("cargo").unwrap();
+        let log = td.path().join("build.log");
+        File::create(&log).unwrap().write_all(b"earlier\n").unwrap();
+
+        let p = shell("echo out; echo err >&2; exit 3");
+        assert_eq!(p.exec_tee(&log).unwrap().code(), Some(3));
+
+        let mut contents = String::new();
+        File::open(&log).unwrap().read_to_string(&mut contents).unwrap();
+        let mut lines = contents.lines().collect::<Vec<_>>();
+        assert_eq!(lines.remove(0), "earlier");
+        lines.sort();
+        assert_eq!(lines, ["err", "out"]);
+    }
+
+    #[test]
+    fn last_status_is_recorded() {
+        let p = shell("exit 3");
+        assert!(p.last_status().is_none());
+        assert!(p.exec().is_err());
+        assert_eq!(p.last_status().and_then(|s| s.code()), Some(3));
+        assert!(p.clone().last_status().is_none());
+
+        let q = shell("exit 0");
+        q.exec_with_output().unwrap();
+        assert!(q.last_status().unwrap().success());
+
+        let missing = process("cargo-no-such-program");
+        assert!(missing.exec().is_err());
+        assert!(missing.last_status().is_none());
+    }
+
+    #[test]
+    fn missing_cwd_is_reported() {
+        let td = TempDir::new("cargo").unwrap();
+        let missing = td.path().join("missing");
+        let err = shell("exit 0").cwd(&missing).exec().unwrap_err();
+        assert!(err.desc.contains("working directory"), "{}", err.desc);
+        assert!(err.desc.contains(&missing.display().to_string()),
+                "{}", err.desc);
+
+        shell("exit 0").cwd(td.path()).exec().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rusage_reports_cpu_time() {
+        use std::time::Duration;
+
+        let script = "i=0; while [ $i -lt 200000 ]; do i=$((i+1)); done";
+        let (output, usage) = shell(script).exec_with_output_rusage().unwrap();
+        assert!(output.status.success());
+        assert!(usage.user_time > Duration::zero());
+        assert!(usage.max_rss > 0);
+    }
+
+    #[test]
+    fn exec_instrumented_measures_the_run() {
+        use std::time::Duration;
+
+        let run = shell("echo hello").exec_instrumented().unwrap();
+        assert!(run.duration > Duration::zero());
+        assert_eq!(String::from_utf8(run.output.stdout).unwrap().trim(), "hello");
+        assert!(shell("exit 1").exec_instrumented().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_instrumented_reports_cpu_time() {
+        let script = "i=0; while [ $i -lt 200000 ]; do i=$((i+1)); done";
+        let run = shell(script).exec_instrumented().unwrap();
+        assert!(run.user_time.is_some());
+        assert!(run.max_rss.is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn try_arg_variants_stops_at_the_first_success() {
+        let p = shell("test \"$0\" = simple && echo \"ran $0\"");
+        let variants = vec![vec![OsString::from_str("fancy")],
+                            vec![OsString::from_str("simple")],
+                            vec![OsString::from_str("unused")]];
+        let (output, i) = p.try_arg_variants(variants).unwrap();
+        assert_eq!(i, 1);
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "ran simple");
+
+        let err = p.try_arg_variants(vec![vec![OsString::from_str("a")],
+                                          vec![OsString::from_str("b")]])
+                   .unwrap_err().to_string();
+        assert!(err.contains("none of the arguments tried"), "{}", err);
+        assert!(err.contains(" a`") && err.contains(" b`"), "{}", err);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_with_output_timeout_keeps_partial_output() {
+        use std::time::Duration;
+
+        let p = shell("echo started; echo waiting >&2; exec sleep 30");
+        let err = p.exec_with_output_timeout(Duration::milliseconds(300))
+                   .unwrap_err();
+        assert!(err.desc.contains("timed out after 300ms"), "{}", err);
+        let output = err.output.unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "started\n");
+        assert_eq!(String::from_utf8(output.stderr).unwrap(), "waiting\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_with_json_decodes_stdout() {
+        #[derive(RustcDecodable, PartialEq, Debug)]
+        struct Tool {
+            name: String,
+            version: u32,
+        }
+
+        let p = shell("echo '{\"name\": \"probe\", \"version\": 3}'");
+        let tool: Tool = p.exec_with_json().unwrap();
+        assert_eq!(tool, Tool { name: "probe".to_string(), version: 3 });
+
+        let p = shell("echo '{\"name\": \"probe\", oops'");
+        let err = p.exec_with_json::<Tool>().unwrap_err().to_string();
+        assert!(err.contains("could not decode the output"), "{}", err);
+        assert!(err.contains("output: {\"name\": \"probe\", oops"), "{}", err);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_outcome_tells_results_apart() {
+        use std::time::Duration;
+        use super::CommandOutcome;
+
+        match shell("echo ok").exec_outcome(None).unwrap() {
+            CommandOutcome::Success(output) => assert_eq!(output.stdout, b"ok\n"),
+            _ => panic!("expected success"),
+        }
+        match shell("echo bad >&2; exit 3").exec_outcome(None).unwrap() {
+            CommandOutcome::Failure(output, status) => {
+                assert_eq!(status.code(), Some(3));
+                assert_eq!(output.stderr, b"bad\n");
+            }
+            _ => panic!("expected failure"),
+        }
+        let p = shell("echo started; exec sleep 30");
+        match p.exec_outcome(Some(Duration::milliseconds(300))).unwrap() {
+            CommandOutcome::TimedOut(output) => {
+                assert_eq!(output.stdout, b"started\n");
+            }
+            _ => panic!("expected a timeout"),
+        }
+        assert!(process("cargo-no-such-program").exec_outcome(None).is_err());
+    }
+
+    #[test]
+    fn exec_status_only_fails_to_run() {
+        let status = shell("exit 3").exec_status().unwrap();
+        assert_eq!(status.code(), Some(3));
+        assert!(shell("exit 0").exec_status().unwrap().success());
+
+        let err = process("cargo-no-such-program").exec_status().unwrap_err();
+        assert!(err.did_not_start());
+    }
+
+    #[test]
+    fn exec_into_replaces_buffer_contents() {
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let status = shell("echo first; echo oops >&2").exec_into(&mut stdout,
+                                                                  &mut stderr)
+                                                       .unwrap();
+        assert!(status.success());
+        assert!(String::from_utf8_lossy(&stdout).contains("first"));
+        assert!(String::from_utf8_lossy(&stderr).contains("oops"));
+
+        let status = shell("echo second; exit 2").exec_into(&mut stdout,
+                                                            &mut stderr)
+                                                 .unwrap();
+        assert_eq!(status.code(), Some(2));
+        let out = String::from_utf8_lossy(&stdout).into_owned();
+        assert!(out.contains("second") && !out.contains("first"), "{}", out);
+        assert!(stderr.is_empty());
+    }
+
+    #[test]
+    fn exec_into_respects_redirected_stdout() {
+        let td = TempDir::new("cargo").unwrap();
+        let path = td.path().join("out");
+        let mut stdout = b"stale".to_vec();
+        let mut stderr = Vec::new();
+        let mut p = shell("echo hello; echo oops >&2");
+        p.stdout_to_file(path.clone());
+        let status = p.exec_into(&mut stdout, &mut stderr).unwrap();
+        assert!(status.success());
+        assert!(stdout.is_empty());
+        assert!(String::from_utf8_lossy(&stderr).contains("oops"));
+
+        let mut contents = String::new();
+        File::open(&path).unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!(contents.trim(), "hello");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn errors_name_the_terminating_signal() {
+        use libc;
+
+        let err = shell("kill -SEGV $$").exec().unwrap_err();
+        assert_eq!(err.signal(), Some(libc::SIGSEGV));
+        let expected = format!("(terminated by signal {}, SIGSEGV)",
+                               libc::SIGSEGV);
+        assert!(err.desc.contains(&expected), "{}", err.desc);
+    }
+}
@@ -0,0 +1,1323 @@
+use std::ascii::AsciiExt;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::ffi::{OsStr, OsString, AsOsStr};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process::{Output, ExitStatus};
+use std::str::Str;
+use std::sync::atomic::{AtomicBool, AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use time;
+
+use util::{CargoResult, ProcessError, human};
+use util::process_error;
+use util::errors::{process_error_with_tail, STDERR_TAIL_LINES};
+use util::jobserver::Client;
+use util::spawn::{Command, Stdio};
+use self::shell::quote;
+use self::watchdog::format_duration;
+
+pub use self::config::ArgsLossy;
+#[cfg(all(target_os = "linux",
+            any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub use self::config::SeccompProfile;
+pub use self::env::{ColorMode, EnvConfig, EnvConfigEntry};
+pub use self::exec::{ProcessHandle, Encoding, OutputChunk, CommandOutcome};
+pub use self::exec::InstrumentedOutput;
+#[cfg(unix)]
+pub use self::exec::ResourceUsage;
+pub use self::command::CommandDescription;
+pub use self::watchdog::wait_timeout;
+pub use self::shell::{process_shell, process_from_shell};
+pub use self::record::{CommandRecorder, RecordedCommand, ProbeCache, ProbeResult};
+pub use self::parallel::{BatchHandle, run_parallel, run_parallel_cancellable};
+pub use self::parallel::run_in_dirs;
+
+mod command;
+mod config;
+mod env;
+mod exec;
+mod parallel;
+mod record;
+mod shell;
+mod watchdog;
+
+#[cfg(unix)]
+#[path = "unix.rs"]
+mod imp;
+#[cfg(windows)]
+#[path = "windows.rs"]
+mod imp;
+
+/// Processes which fail after running for at least this many seconds have
+/// their running time included in the error message.
+const SLOW_PROCESS_SECS: i64 = 1;
+
+/// Arguments longer than this many bytes in total are passed through an
+/// argfile when `use_argfile` is enabled. This stays well clear of the limit
+/// of 32K characters on a Windows command line.
+const ARGFILE_THRESHOLD: usize = 16 * 1024;
+
+/// Keeps the names of temporary files written at the same time apart.
+static TEMP_FILE_COUNTER: AtomicUsize = ATOMIC_USIZE_INIT;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ProcessBuilder {
+    program: OsString,
+    args: Vec<OsString>,
+    env: HashMap<String, Option<OsString>>,
+    cwd: Option<OsString>,
+    timeout: Option<Duration>,
+    kill_process_group: bool,
+    new_process_group: bool,
+    detach: bool,
+    stdin_data: Option<Vec<u8>>,
+    stdin_file: Option<PathBuf>,
+    close_stdin: bool,
+    capture_on_failure: bool,
+    replay_on_failure: bool,
+    max_output_bytes: Option<usize>,
+    capture_tail: Option<usize>,
+    read_buffer_size: Option<usize>,
+    retries: u32,
+    retry_backoff: Option<Duration>,
+    success_codes: Vec<i32>,
+    env_clear: bool,
+    env_forward: Vec<String>,
+    env_expand: bool,
+    use_argfile: bool,
+    secret_args: Vec<(OsString, Vec<u8>)>,
+    path_args: Vec<usize>,
+    required_env: Vec<String>,
+    search_path: bool,
+    dry_run: bool,
+    stdout_file: Option<PathBuf>,
+    stderr_file: Option<PathBuf>,
+    // Descriptors on Unix and handles on Windows, kept as integers since
+    // handles aren't `Send`.
+    stdout_fd: Option<usize>,
+    stderr_fd: Option<usize>,
+    silent: bool,
+    dedup_args: bool,
+    swap_stdio: bool,
+    redirect_stderr_to_stdout: bool,
+    output_encoding: Encoding,
+    jobserver: Option<Client>,
+    on_exec: Option<Ignored<ExecHook>>,
+    on_spawn: Option<Ignored<SpawnHook>>,
+    command_hook: Option<Ignored<CommandHook>>,
+    recorder: Option<Ignored<Arc<CommandRecorder>>>,
+    progress_tick: Option<Ignored<ProgressTick>>,
+    progress_interval: Option<Duration>,
+    line_rate: Option<u32>,
+    error_formatter: Option<Ignored<ErrorFormatter>>,
+    arg_transform: Option<Ignored<ArgTransform>>,
+    verbose: Option<Ignored<VerboseSink>>,
+    env_filters: Vec<Ignored<EnvFilter>>,
+    lazy_env: HashMap<String, Ignored<LazyEnv>>,
+    fallbacks: Vec<OsString>,
+    #[cfg(unix)]
+    uid: Option<u32>,
+    #[cfg(unix)]
+    gid: Option<u32>,
+    #[cfg(unix)]
+    nice: Option<i32>,
+    #[cfg(unix)]
+    umask: Option<u32>,
+    #[cfg(unix)]
+    ignore_sigint: bool,
+    #[cfg(unix)]
+    cpu_time_limit: Option<u64>,
+    #[cfg(unix)]
+    file_size_limit: Option<u64>,
+    #[cfg(target_os = "linux")]
+    chroot: Option<PathBuf>,
+    #[cfg(target_os = "linux")]
+    comm: Option<String>,
+    #[cfg(all(target_os = "linux",
+                any(target_arch = "x86_64", target_arch = "aarch64")))]
+    seccomp_profile: Option<SeccompProfile>,
+    #[cfg(unix)]
+    arg0: Option<OsString>,
+    #[cfg(windows)]
+    creation_flags: u32,
+    #[cfg(unix)]
+    timeout_signal: Option<i32>,
+    #[cfg(unix)]
+    inherit_fds: Vec<(i32, i32)>,
+    #[cfg(unix)]
+    forward_signals: Vec<i32>,
+    pty: bool,
+    stderr_tail_lines: usize,
+    description: Option<String>,
+    tag: Option<String>,
+    cancel: Option<Ignored<Arc<AtomicBool>>>,
+    metadata: Metadata,
+    last_status: Ignored<LastStatus>,
+}
+
+/// The arguments, environment and working directory of a `ProcessBuilder`, as
+/// saved by `snapshot` to be put back with `restore`.
+#[derive(Clone, Debug)]
+pub struct ProcessBuilderState {
+    args: Vec<OsString>,
+    env: HashMap<String, Option<OsString>>,
+    lazy_env: HashMap<String, Ignored<LazyEnv>>,
+    cwd: Option<OsString>,
+}
+
+/// A field of `ProcessBuilder`, such as a callback, which can't be
+/// meaningfully compared or printed, and so is ignored by the `PartialEq` and
+/// `Debug` implementations of the builder.
+#[derive(Clone)]
+struct Ignored<T>(T);
+
+impl<T> PartialEq for Ignored<T> {
+    fn eq(&self, _other: &Ignored<T>) -> bool { true }
+}
+
+impl<T> fmt::Debug for Ignored<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "..")
+    }
+}
+
+/// A callback registered through `ProcessBuilder::on_exec`.
+type ExecHook = Arc<Box<Fn(&ProcessBuilder) + Send + Sync>>;
+
+/// A callback registered through `ProcessBuilder::on_spawn`.
+type SpawnHook = Arc<Box<Fn(u32) + Send + Sync>>;
+
+/// A callback registered through `ProcessBuilder::configure_command`.
+type CommandHook = Arc<Box<Fn(&mut Command) + Send + Sync>>;
+
+/// A callback registered through `ProcessBuilder::progress_tick`.
+type ProgressTick = Arc<Box<Fn(Duration) + Send + Sync>>;
+
+/// A formatter registered through `ProcessBuilder::error_formatter`.
+type ErrorFormatter = Arc<Box<Fn(&ProcessErrorContext) -> String + Send + Sync>>;
+
+/// What an `error_formatter` is given to describe a process which failed.
+pub struct ProcessErrorContext<'a> {
+    /// The message the error would have started with otherwise, such as
+    /// "Process didn't exit successfully: `foo`".
+    pub message: &'a str,
+    pub program: &'a OsStr,
+    pub args: &'a [OsString],
+    /// How the process exited, if it was spawned and ran to completion.
+    pub exit: Option<&'a ExitStatus>,
+    /// The output of the process, if it was captured.
+    pub output: Option<&'a Output>,
+}
+
+/// A rewrite of arguments registered through `ProcessBuilder::arg_transform`.
+type ArgTransform = Arc<Box<Fn(&OsStr) -> OsString + Send + Sync>>;
+
+/// A writer registered through `ProcessBuilder::verbose`, shared by all clones
+/// of the builder.
+type VerboseSink = Arc<Mutex<Box<Write + Send>>>;
+
+/// A predicate registered through `ProcessBuilder::env_remove_matching`.
+type EnvFilter = Arc<Box<Fn(&str) -> bool + Send + Sync>>;
+
+/// A producer of an environment variable registered through
+/// `ProcessBuilder::env_fn`.
+type LazyEnv = Arc<Box<Fn() -> Option<OsString> + Send + Sync>>;
+
+/// Entries set through `ProcessBuilder::set_metadata`, which are ignored by
+/// comparisons as they have no effect on the process.
+#[derive(Clone, Debug)]
+struct Metadata(HashMap<String, String>);
+
+impl PartialEq for Metadata {
+    fn eq(&self, _other: &Metadata) -> bool { true }
+}
+
+/// The exit status of the last process run by the exec methods of a builder,
+/// as returned by `ProcessBuilder::last_status`.
+///
+/// This records what happened rather than configuring anything, so clones of
+/// a builder start out without a status, and it's kept in an `Ignored` so
+/// that comparisons ignore it.
+struct LastStatus(Mutex<Option<ExitStatus>>);
+
+impl Clone for LastStatus {
+    fn clone(&self) -> LastStatus { LastStatus(Mutex::new(None)) }
+}
+
+impl fmt::Display for ProcessBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "`{}", quote(&self.program.to_string_lossy())));
+
+        for arg in self.args.iter() {
+            try!(write!(f, " {}", quote(&arg.to_string_lossy())));
+        }
+
+        write!(f, "`")
+    }
+}
+
+impl ProcessBuilder {
+    /// Like `get_env`, but never expands references to other variables.
+    fn unexpanded_env(&self, var: &str) -> Option<OsString> {
+        if let Some(&Ignored(ref f)) = self.lazy_env.get(&env_key(var)) {
+            return f()
+        }
+        match self.env.get(&env_key(var)) {
+            Some(val) => val.clone(),
+            None if self.is_forwarded(var) => env::var_os(var),
+            None if self.env_clear || self.is_filtered(var) => None,
+            None => env::var_os(var),
+        }
+    }
+
+    fn is_filtered(&self, var: &str) -> bool {
+        self.env_filters.iter().any(|&Ignored(ref pred)| pred(var))
+    }
+
+    fn is_forwarded(&self, var: &str) -> bool {
+        self.env_forward.contains(&env_key(var))
+    }
+
+    /// Returns the variables given to `env_forward` which aren't also set or
+    /// removed explicitly, sorted by name.
+    fn forwarded_env(&self) -> Vec<&str> {
+        let mut keys = self.env_forward.iter().map(|key| key.as_slice())
+                           .filter(|key| !self.is_overridden(key))
+                           .collect::<Vec<_>>();
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+
+    /// Whether `var` is set or removed explicitly, rather than inherited.
+    fn is_overridden(&self, var: &str) -> bool {
+        let var = env_key(var);
+        self.env.contains_key(&var) || self.lazy_env.contains_key(&var)
+    }
+
+    /// Returns every variable set or removed explicitly along with its value,
+    /// sorted by name, with the variables set through `env_fn` computed.
+    fn env_overrides(&self) -> Vec<(String, Option<OsString>)> {
+        let mut vars = self.env.iter().map(|(key, val)| {
+            (key.clone(), val.clone())
+        }).collect::<Vec<_>>();
+        vars.extend(self.lazy_env.iter().map(|(key, &Ignored(ref f))| {
+            (key.clone(), f())
+        }));
+        vars.sort_by(|a, b| a.0.cmp(&b.0));
+        vars.into_iter().map(|(key, val)| {
+            (key, val.map(|val| self.expand(val)))
+        }).collect()
+    }
+
+    /// Expands references to other variables in `val` as described by
+    /// `env_expand`, if that was enabled.
+    fn expand(&self, val: OsString) -> OsString {
+        if !self.env_expand {
+            return val
+        }
+        let val = match val.into_string() {
+            Ok(val) => val,
+            Err(val) => return val,
+        };
+
+        let mut expanded = OsString::new();
+        let mut rest = &val[..];
+        while let Some(i) = rest.find('$') {
+            expanded.push(&rest[..i]);
+            let after = &rest[i + 1..];
+            if after.starts_with("$") {
+                expanded.push("$");
+                rest = &after[1..];
+            } else if after.starts_with("{") && after.contains_char('}') {
+                let end = after.find('}').unwrap();
+                if let Some(val) = self.unexpanded_env(&after[1..end]) {
+                    expanded.push(&val);
+                }
+                rest = &after[end + 1..];
+            } else {
+                expanded.push("$");
+                rest = after;
+            }
+        }
+        expanded.push(rest);
+        expanded
+    }
+
+    /// Returns every variable in the environment of the process along with
+    /// its value, ignoring inherited variables whose names aren't unicode.
+    ///
+    /// On Windows the names are normalized as by `env_key`, so an inherited
+    /// `Path` and an overridden `PATH` only show up once.
+    fn effective_env(&self) -> BTreeMap<String, OsString> {
+        let inherited = env::vars_os().filter_map(|(key, _)| {
+            key.into_string().ok().map(|key| env_key(&key))
+        });
+        let keys = inherited.chain(self.env.keys().cloned())
+                            .chain(self.lazy_env.keys().cloned())
+                            .collect::<Vec<_>>();
+        keys.into_iter().filter_map(|key| {
+            self.get_env(&key).map(|val| (key, val))
+        }).collect()
+    }
+
+    /// Creates an error about this process, which records the command that
+    /// was run alongside the given details.
+    fn process_error(&self, msg: &str, cause: Option<io::Error>,
+                     status: Option<&ExitStatus>, output: Option<&Output>)
+                     -> ProcessError {
+        let mut err = process_error_with_tail(msg, cause, status, output,
+                                              self.stderr_tail_lines);
+        if let Some(Ignored(ref f)) = self.error_formatter {
+            err.desc = f(&ProcessErrorContext {
+                message: msg,
+                program: &self.program,
+                args: &self.args,
+                exit: status,
+                output: output,
+            });
+        }
+        if self.description.is_some() {
+            err.set_command_line(format!("`{}`", self.debug_string()));
+        }
+        err.program = Some(self.program.clone());
+        err.args = self.args.clone();
+        err.cwd = self.get_cwd().map(|cwd| cwd.to_path_buf());
+        err
+    }
+
+    fn is_success(&self, status: &ExitStatus) -> bool {
+        status.code().map(|code| {
+            self.success_codes.contains(&code)
+        }).unwrap_or(false)
+    }
+
+    fn spawn_error(&self, e: io::Error) -> ProcessError {
+        let msg = match self.root() {
+            _ if imp::too_many_open_files(&e) => {
+                format!("Could not execute process {}: too many files are open, \
+                         try running fewer jobs at once or raising the limit \
+                         on open files (`ulimit -n`)", self.error_name())
+            }
+            Some(root) => format!("Could not execute process {} inside of `{}`",
+                                  self.error_name(), root.display()),
+            None => format!("Could not execute process {}", self.error_name()),
+        };
+        self.process_error(&msg, Some(e), None, None)
+    }
+
+    /// Returns the directory the process is confined to by `chroot`, if any.
+    #[cfg(target_os = "linux")]
+    fn root(&self) -> Option<&Path> {
+        self.chroot.as_ref().map(|root| &**root)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn root(&self) -> Option<&Path> { None }
+
+    /// Returns the name given to `set_comm`, if any.
+    #[cfg(target_os = "linux")]
+    fn comm(&self) -> Option<&str> {
+        self.comm.as_ref().map(|comm| &comm[..])
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn comm(&self) -> Option<&str> { None }
+
+    /// Returns a symlink to `program` named after `set_comm`, for the process
+    /// to be run through, or `program` itself if there's no name to give it.
+    ///
+    /// The symlink and the directory holding it are returned as temporary
+    /// files, in the order they have to be removed in.
+    fn comm_link(&self, program: OsString)
+                 -> Result<(OsString, Vec<TempFile>), ProcessError> {
+        let comm = match self.comm() {
+            Some(comm) if self.root().is_none() => comm,
+            _ => return Ok((program, Vec::new())),
+        };
+        // The symlink lives elsewhere, so it has to point at the program
+        // through an absolute path. Programs which can't be found are left to
+        // fail to spawn as usual.
+        let target = if is_bare_name(&program) {
+            let paths = self.get_env("PATH").unwrap_or(OsString::new());
+            match find_in_path(&program, &paths, env::consts::EXE_SUFFIX) {
+                Some(path) => path,
+                None => return Ok((program, Vec::new())),
+            }
+        } else {
+            let cwd = self.get_cwd().map(|p| p.to_path_buf());
+            match cwd.or(env::current_dir().ok()) {
+                Some(cwd) => cwd.join(&program),
+                None => PathBuf::new(&program),
+            }
+        };
+
+        let name = format!("cargo-comm-{}-{}-{}", imp::current_pid(),
+                           time::precise_time_ns(),
+                           TEMP_FILE_COUNTER.fetch_add(1, Ordering::SeqCst));
+        let dir = TempFile { path: env::temp_dir().join(&name) };
+        let link = TempFile { path: dir.path.join(comm) };
+        try!(fs::create_dir(&dir.path).and_then(|()| {
+            fs::soft_link(&target, &link.path)
+        }).map_err(|e| {
+            let msg = format!("Could not create `{}` to name process {}",
+                              link.path.display(), self.error_name());
+            self.process_error(&msg, Some(e), None, None)
+        }));
+        Ok((link.path.clone().into_os_string(), vec![link, dir]))
+    }
+
+    fn exit_error(&self, status: &ExitStatus, output: Option<&Output>,
+                  elapsed: Duration) -> ProcessError {
+        // Only mention how long the process ran for when it's likely to be
+        // of interest, fast failures are noisy enough already.
+        let msg = if imp::exceeded_cpu_time(self, status) {
+            format!("Process exceeded its CPU time limit: {}", self.error_name())
+        } else if imp::exceeded_file_size(self, status) {
+            format!("Process wrote a file exceeding its file size limit: {}",
+                    self.error_name())
+        } else if elapsed >= Duration::seconds(SLOW_PROCESS_SECS) {
+            format!("Process didn't exit successfully after {}: {}",
+                    format_duration(elapsed), self.error_name())
+        } else {
+            format!("Process didn't exit successfully: {}",
+                    self.error_name())
+        };
+        let mut err = self.process_error(&msg, None, Some(status), output);
+        err.duration = Some(elapsed);
+        err
+    }
+
+    fn retries_error(&self, mut err: ProcessError, attempts: u32)
+                     -> ProcessError {
+        if attempts > 1 {
+            err.desc = format!("{} attempts to run {} failed, the last \
+                                error was:\n{}", attempts, self.error_name(),
+                               err.desc);
+        }
+        err
+    }
+
+    /// Whether the flag given to `cancel_on` has been set.
+    fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().map(|&Ignored(ref flag)| {
+            flag.load(Ordering::SeqCst)
+        }).unwrap_or(false)
+    }
+
+    /// Creates the error for a process which was cancelled before it could be
+    /// started.
+    fn not_started_error(&self) -> ProcessError {
+        let msg = format!("Process was cancelled before it started: {}",
+                          self.error_name());
+        let mut err = self.process_error(&msg, None, None, None);
+        err.cancelled = true;
+        err
+    }
+
+    fn cancelled_error(&self, status: &ExitStatus, output: Option<&Output>)
+                       -> ProcessError {
+        let msg = format!("Process was cancelled: {}", self.error_name());
+        let mut err = self.process_error(&msg, None, Some(status), output);
+        err.cancelled = true;
+        err
+    }
+
+    fn truncated_error(&self, dropped: usize, output: &Output) -> ProcessError {
+        let msg = format!("Process was killed for producing more than {} \
+                           bytes of output: {}",
+                          self.max_output_bytes.unwrap(), self.error_name());
+        let mut err = self.process_error(&msg, None, Some(&output.status),
+                                         Some(output));
+        err.truncated = Some(dropped);
+        err
+    }
+
+    fn timeout_error(&self, dur: Duration, status: &ExitStatus,
+                     output: Option<&Output>) -> ProcessError {
+        let msg = format!("Process timed out after {}ms: {}",
+                          dur.num_milliseconds(), self.error_name());
+        self.process_error(&msg, None, Some(status), output)
+    }
+
+    /// Builds the `Command` to spawn, looking up the program in `PATH` first
+    /// if that was requested or if there are fallbacks to choose from.
+    ///
+    /// Any temporary files written for the process, such as an argfile, are
+    /// returned as well and have to be kept alive until the process exits.
+    fn command(&self) -> Result<(Command, Vec<TempFile>), ProcessError> {
+        // Spawning in a missing directory fails just like spawning a missing
+        // program, so point out which of the two it is. Inside of a chroot the
+        // directory can only be found by the process itself.
+        if let (Some(cwd), None) = (self.get_cwd(), self.root()) {
+            if !fs::metadata(cwd).map(|m| m.is_dir()).unwrap_or(false) {
+                let msg = format!("Could not execute process {}: the working \
+                                   directory `{}` does not exist or is not a \
+                                   directory", self.error_name(),
+                                  cwd.display());
+                return Err(self.process_error(&msg, None, None, None))
+            }
+        }
+        try!(self.check_path_args());
+        try!(self.check_required_env());
+        let program = if self.fallbacks.len() > 0 {
+            try!(self.choose_program())
+        } else if self.search_path {
+            try!(self.find_program())
+        } else {
+            self.program.clone()
+        };
+        let (program, mut temp_files) = try!(self.comm_link(program));
+        let argfile = if self.use_argfile {
+            try!(self.write_argfile())
+        } else {
+            None
+        };
+        let mut args = match argfile {
+            Some(ref argfile) => {
+                let mut arg = OsString::from_str("@");
+                arg.push(&argfile.path);
+                vec![arg]
+            }
+            None => self.final_args().into_owned(),
+        };
+        temp_files.extend(argfile.into_iter());
+        for &(ref flag, ref secret) in self.secret_args.iter() {
+            let file = try!(self.write_temp_file("secret", secret));
+            args.push(flag.clone());
+            args.push(file.path.clone().into_os_string());
+            temp_files.push(file);
+        }
+        let mut command = self.build_command_with(&program, &args);
+        if let Some(ref path) = self.stdin_file {
+            let file = try!(File::open(path).map_err(|e| {
+                let msg = format!("Could not open `{}` for the stdin of process \
+                                   {}", path.display(), self.error_name());
+                self.process_error(&msg, Some(e), None, None)
+            }));
+            command.stdin(Stdio::from(file));
+        } else if self.close_stdin && self.stdin_data.is_none() {
+            command.stdin(Stdio::null());
+        }
+        if let Some(ref path) = self.stdout_file {
+            command.stdout(try!(self.open_redirect(path, "stdout")));
+        } else if let Some(fd) = self.stdout_fd {
+            command.stdout(try!(self.dup_redirect(fd, "stdout")));
+        } else if self.silent {
+            command.stdout(Stdio::null());
+        }
+        if let Some(ref path) = self.stderr_file {
+            command.stderr(try!(self.open_redirect(path, "stderr")));
+        } else if let Some(fd) = self.stderr_fd {
+            command.stderr(try!(self.dup_redirect(fd, "stderr")));
+        } else if self.silent {
+            command.stderr(Stdio::null());
+        }
+        Ok((command, temp_files))
+    }
+
+    /// Checks that the arguments given to `validate_path_args` name paths which
+    /// exist.
+    fn check_path_args(&self) -> Result<(), ProcessError> {
+        for &i in self.path_args.iter() {
+            let arg = match self.args.get(i) {
+                Some(arg) => Path::new(arg),
+                None => continue,
+            };
+            let path = match self.get_cwd() {
+                Some(cwd) => cwd.join(arg),
+                None => arg.to_path_buf(),
+            };
+            if fs::metadata(&path).is_err() {
+                let msg = format!("Could not execute process {}: the path `{}` \
+                                   given as argument {} does not exist",
+                                  self.error_name(), arg.display(), i);
+                return Err(self.process_error(&msg, None, None, None))
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the variables given to `require_env` are set to non-empty
+    /// values.
+    fn check_required_env(&self) -> Result<(), ProcessError> {
+        let missing = self.required_env.iter().filter(|key| {
+            self.get_env(key).map(|val| val.to_string_lossy().is_empty())
+                             .unwrap_or(true)
+        }).map(|key| format!("`{}`", key)).collect::<Vec<_>>();
+        if missing.is_empty() {
+            return Ok(())
+        }
+        let msg = format!("Could not execute process {}: the environment \
+                           variables {} it requires are not set",
+                          self.error_name(), missing.connect(", "));
+        Err(self.process_error(&msg, None, None, None))
+    }
+
+    /// Writes the arguments of the process to an argfile, one per line, if
+    /// they're longer than `ARGFILE_THRESHOLD`.
+    ///
+    /// Arguments which aren't unicode or span several lines can't be written
+    /// to an argfile, in which case they're passed directly after all.
+    fn write_argfile(&self) -> Result<Option<TempFile>, ProcessError> {
+        let mut contents = String::new();
+        for arg in self.final_args().iter() {
+            match arg.to_str() {
+                Some(arg) if !arg.contains_char('\n') => {
+                    contents.push_str(arg);
+                    contents.push('\n');
+                }
+                _ => return Ok(None),
+            }
+        }
+        if contents.len() <= ARGFILE_THRESHOLD {
+            return Ok(None)
+        }
+
+        self.write_temp_file("arguments", contents.as_bytes()).map(Some)
+    }
+
+    /// Writes `contents` to a new temporary file which only the current user
+    /// can access, naming `what` it holds should that fail.
+    fn write_temp_file(&self, what: &str, contents: &[u8])
+                       -> Result<TempFile, ProcessError> {
+        let name = format!("cargo-{}-{}-{}-{}", what, imp::current_pid(),
+                           time::precise_time_ns(),
+                           TEMP_FILE_COUNTER.fetch_add(1, Ordering::SeqCst));
+        let path = env::temp_dir().join(&name);
+        let mut file = try!(imp::create_private(&path).map_err(|e| {
+            let msg = format!("Could not create `{}` for the {} of process {}",
+                              path.display(), what, self.error_name());
+            self.process_error(&msg, Some(e), None, None)
+        }));
+        // From here on the file is removed again should anything go wrong.
+        let temp_file = TempFile { path: path };
+        try!(file.write_all(contents).map_err(|e| {
+            let msg = format!("Could not write `{}` with the {} of process {}",
+                              temp_file.path.display(), what,
+                              self.error_name());
+            self.process_error(&msg, Some(e), None, None)
+        }));
+        Ok(temp_file)
+    }
+
+    /// Whether the stdout of the process goes somewhere chosen by `command`,
+    /// and so mustn't be replaced.
+    fn stdout_redirected(&self) -> bool {
+        self.stdout_file.is_some() || self.stdout_fd.is_some() || self.silent
+    }
+
+    /// Like `stdout_redirected`, for stderr.
+    fn stderr_redirected(&self) -> bool {
+        self.stderr_file.is_some() || self.stderr_fd.is_some() || self.silent ||
+            self.redirect_stderr_to_stdout
+    }
+
+    fn dup_redirect(&self, fd: usize, stream: &str)
+                    -> Result<Stdio, ProcessError> {
+        imp::dup_raw(fd).map_err(|e| {
+            let msg = format!("Could not duplicate the descriptor given for \
+                               the {} of process {}", stream,
+                              self.error_name());
+            self.process_error(&msg, Some(e), None, None)
+        })
+    }
+
+    fn open_redirect(&self, path: &Path, stream: &str)
+                     -> Result<Stdio, ProcessError> {
+        let file = try!(File::create(path).map_err(|e| {
+            let msg = format!("Could not open `{}` for the {} of process \
+                               {}", path.display(), stream,
+                              self.error_name());
+            self.process_error(&msg, Some(e), None, None)
+        }));
+        Ok(Stdio::from(file))
+    }
+
+    fn find_program(&self) -> Result<OsString, ProcessError> {
+        // Programs given as a path rather than a bare name are used as is.
+        if !is_bare_name(&self.program) {
+            return Ok(self.program.clone())
+        }
+
+        let paths = self.get_env("PATH").unwrap_or(OsString::new());
+        match find_in_path(&self.program, &paths, env::consts::EXE_SUFFIX) {
+            Some(path) => Ok(path.into_os_string()),
+            None => {
+                let dirs = env::split_paths(&paths).map(|p| {
+                    p.display().to_string()
+                }).collect::<Vec<_>>();
+                let msg = format!("could not find executable `{}` on PATH, \
+                                   searched: {}",
+                                  self.program.to_string_lossy(),
+                                  dirs.connect(", "));
+                Err(self.process_error(&msg, None, None, None))
+            }
+        }
+    }
+
+    /// Returns the first of the program and its fallbacks which exists.
+    fn choose_program(&self) -> Result<OsString, ProcessError> {
+        let paths = self.get_env("PATH").unwrap_or(OsString::new());
+        let candidates = Some(&self.program).into_iter()
+                                            .chain(self.fallbacks.iter());
+        for candidate in candidates.clone() {
+            if is_bare_name(candidate) {
+                let exe_suffix = env::consts::EXE_SUFFIX;
+                if let Some(path) = find_in_path(candidate, &paths, exe_suffix) {
+                    return Ok(if self.search_path {
+                        path.into_os_string()
+                    } else {
+                        candidate.clone()
+                    })
+                }
+                continue
+            }
+            let path = match self.cwd {
+                Some(ref cwd) => Path::new(cwd).join(candidate),
+                None => Path::new(candidate).to_path_buf(),
+            };
+            if fs::metadata(&path).map(|m| m.is_file()).unwrap_or(false) {
+                return Ok(candidate.clone())
+            }
+        }
+
+        let names = candidates.map(|program| {
+            format!("`{}`", program.to_string_lossy())
+        }).collect::<Vec<_>>();
+        let msg = format!("could not find any of the programs {} to execute",
+                          names.connect(", "));
+        Err(self.process_error(&msg, None, None, None))
+    }
+
+    fn build_command_for(&self, program: &OsStr) -> Command {
+        self.build_command_with(program, &self.final_args())
+    }
+
+    /// Returns the arguments of the process after `arg_transform`.
+    fn final_args(&self) -> Cow<[OsString]> {
+        let args = match self.arg_transform {
+            Some(Ignored(ref f)) => {
+                Cow::Owned(self.args.iter().map(|arg| f(arg)).collect())
+            }
+            None => Cow::Borrowed(&self.args[..]),
+        };
+        if self.dedup_args {
+            Cow::Owned(dedup_flags(&args))
+        } else {
+            args
+        }
+    }
+
+    fn build_command_with(&self, program: &OsStr, args: &[OsString])
+                          -> Command {
+        let mut command = Command::new(&self.program_path(program));
+        // The process changes into its working directory itself once it has
+        // changed its root directory.
+        if let (Some(cwd), None) = (self.cwd.as_ref(), self.root()) {
+            command.current_dir(cwd);
+        }
+        if self.env_clear {
+            command.env_clear();
+            for key in self.forwarded_env().iter() {
+                if let Some(val) = env::var_os(key) {
+                    command.env(key, &val);
+                }
+            }
+        }
+        for arg in args.iter() {
+            command.arg(arg);
+        }
+        if !self.env_clear && self.env_filters.len() > 0 {
+            let mut keys = env::vars_os().filter_map(|(key, _)| {
+                key.into_string().ok()
+            }).collect::<Vec<_>>();
+            keys.sort();
+            for key in keys.iter() {
+                if !self.is_overridden(key) && !self.is_forwarded(key) &&
+                   self.is_filtered(key) {
+                    command.env_remove(key);
+                }
+            }
+        }
+        for (k, v) in self.env_overrides() {
+            match v {
+                Some(v) => { command.env(&k, &v); }
+                None => { command.env_remove(&k); }
+            }
+        }
+        if let Some(ref client) = self.jobserver {
+            client.configure(&mut command);
+        }
+        imp::configure(self, &mut command);
+        if let Some(Ignored(ref f)) = self.command_hook {
+            f(&mut command);
+        }
+        command
+    }
+
+    /// Returns what `Command` has to be given to run `program`.
+    fn program_path(&self, program: &OsStr) -> OsString {
+        // A relative path to a program is relative to the directory the
+        // process runs in, rather than wherever cargo happens to be.
+        match self.cwd {
+            Some(ref cwd) if !is_bare_name(program) &&
+                             Path::new(program).is_relative() => {
+                Path::new(cwd).join(program).into_os_string()
+            }
+            _ => program.to_os_string(),
+        }
+    }
+
+    /// Renders a line of output for a callback, prefixed with the tag if one
+    /// was set.
+    fn tagged(&self, line: &[u8]) -> String {
+        let line = String::from_utf8_lossy(line);
+        match self.tag {
+            Some(ref tag) => format!("[{}] {}", tag, line),
+            None => line.into_owned(),
+        }
+    }
+
+    /// Returns how the process is referred to in error messages.
+    fn error_name(&self) -> String {
+        match self.description {
+            Some(ref description) => description.clone(),
+            None => format!("`{}`", self.debug_string()),
+        }
+    }
+
+    fn debug_string(&self) -> String {
+        let mut program = quote(&self.program.to_string_lossy()).into_owned();
+        for arg in self.args.iter() {
+            program.push(' ');
+            program.push_str(&quote(&arg.to_string_lossy()));
+        }
+        program
+    }
+}
+
+/// A temporary file written for a process, such as an argfile or a file given
+/// to `secret_arg`, which is removed when dropped. Empty directories are
+/// removed as well.
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    fn keep(self) {
+        mem::forget(self)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if fs::remove_file(&self.path).is_err() {
+            let _ = fs::remove_dir(&self.path);
+        }
+    }
+}
+
+/// Returns the name under which the variable `key` is stored on a builder.
+///
+/// Environment variable names are case-insensitive on Windows, so they are
+/// uppercased there to keep `Path` and `PATH` from ending up as two separate
+/// variables. Elsewhere the name is left as is.
+#[cfg(windows)]
+fn env_key(key: &str) -> String {
+    key.to_ascii_uppercase()
+}
+
+#[cfg(unix)]
+fn env_key(key: &str) -> String {
+    key.to_string()
+}
+
+/// Drops the flags repeated in `args` which `dedup_args` considers safe to drop.
+fn dedup_flags(args: &[OsString]) -> Vec<OsString> {
+    let is_flag = |arg: &str| arg.starts_with("-") && arg != "-";
+    let is_assignment = |arg: &str| {
+        !arg.starts_with("-") && arg.contains_char('=')
+    };
+    let mut seen = HashSet::new();
+    let mut deduped = Vec::with_capacity(args.len());
+    let mut i = 0;
+    while i < args.len() {
+        let arg = args[i].to_str().unwrap_or("");
+        if arg == "--" {
+            deduped.push_all(&args[i..]);
+            break
+        }
+        let next = args.get(i + 1).and_then(|arg| arg.to_str()).unwrap_or("");
+        let len = if is_flag(arg) && arg.contains_char('=') {
+            1
+        } else if is_flag(arg) && is_assignment(next) {
+            2
+        } else {
+            deduped.push(args[i].clone());
+            i += 1;
+            continue
+        };
+        if seen.insert(&args[i..i + len]) {
+            deduped.push_all(&args[i..i + len]);
+        }
+        i += len;
+    }
+    deduped
+}
+
+/// Returns whether `program` is just the name of a program, to be looked up in
+/// `PATH`, rather than a path to one.
+fn is_bare_name(program: &OsStr) -> bool {
+    Path::new(program).components().count() <= 1
+}
+
+/// Looks for `name` in each of the directories in `paths`, returning the first
+/// match.
+///
+/// If `exe_suffix` is non-empty then `name` with the suffix appended is tried
+/// before `name` itself in each directory, unless `name` already ends in the
+/// suffix as `with_exe_suffix` would have it.
+fn find_in_path(name: &OsStr, paths: &OsStr, exe_suffix: &str)
+                -> Option<PathBuf> {
+    let mut with_suffix = name.to_os_string();
+    with_suffix.push(exe_suffix);
+    let has_suffix = name.to_str().map(|name| {
+        name.to_ascii_lowercase().ends_with(exe_suffix)
+    }).unwrap_or(false);
+
+    env::split_paths(paths).filter_map(|dir| {
+        let mut candidates = Vec::new();
+        if exe_suffix.len() > 0 && !has_suffix {
+            candidates.push(dir.join(&with_suffix));
+        }
+        candidates.push(dir.join(name));
+        candidates.into_iter().find(|p| {
+            fs::metadata(p).map(|m| m.is_file()).unwrap_or(false)
+        })
+    }).next()
+}
+
+fn trim_eol(line: &[u8]) -> &[u8] {
+    let line = if line.ends_with(b"\n") {&line[..line.len() - 1]} else {line};
+    if line.ends_with(b"\r") {&line[..line.len() - 1]} else {line}
+}
+
+pub fn process<T: AsOsStr + ?Sized>(cmd: &T) -> ProcessBuilder {
+    process_os(cmd.as_os_str().to_os_string())
+}
+
+/// Like `process`, but takes ownership of an already allocated program name.
+pub fn process_os(program: OsString) -> ProcessBuilder {
+    ProcessBuilder {
+        program: program,
+        args: Vec::new(),
+        cwd: None,
+        env: HashMap::new(),
+        timeout: None,
+        kill_process_group: false,
+        new_process_group: false,
+        detach: false,
+        stdin_data: None,
+        stdin_file: None,
+        close_stdin: false,
+        capture_on_failure: false,
+        replay_on_failure: false,
+        max_output_bytes: None,
+        capture_tail: None,
+        read_buffer_size: None,
+        retries: 0,
+        success_codes: vec![0],
+        retry_backoff: None,
+        env_clear: false,
+        env_forward: Vec::new(),
+        env_expand: false,
+        use_argfile: false,
+        secret_args: Vec::new(),
+        path_args: Vec::new(),
+        required_env: Vec::new(),
+        search_path: false,
+        dry_run: false,
+        stdout_file: None,
+        stderr_file: None,
+        stdout_fd: None,
+        stderr_fd: None,
+        silent: false,
+        dedup_args: false,
+        swap_stdio: false,
+        redirect_stderr_to_stdout: false,
+        output_encoding: Encoding::Utf8,
+        jobserver: None,
+        on_exec: None,
+        on_spawn: None,
+        command_hook: None,
+        recorder: None,
+        progress_tick: None,
+        progress_interval: None,
+        line_rate: None,
+        error_formatter: None,
+        arg_transform: None,
+        verbose: None,
+        env_filters: Vec::new(),
+        lazy_env: HashMap::new(),
+        fallbacks: Vec::new(),
+        #[cfg(unix)]
+        uid: None,
+        #[cfg(unix)]
+        gid: None,
+        #[cfg(unix)]
+        nice: None,
+        #[cfg(unix)]
+        umask: None,
+        #[cfg(unix)]
+        ignore_sigint: false,
+        #[cfg(unix)]
+        cpu_time_limit: None,
+        #[cfg(unix)]
+        file_size_limit: None,
+        #[cfg(target_os = "linux")]
+        chroot: None,
+        #[cfg(target_os = "linux")]
+        comm: None,
+        #[cfg(all(target_os = "linux",
+                    any(target_arch = "x86_64", target_arch = "aarch64")))]
+        seccomp_profile: None,
+        #[cfg(unix)]
+        arg0: None,
+        #[cfg(windows)]
+        creation_flags: 0,
+        #[cfg(unix)]
+        timeout_signal: None,
+        #[cfg(unix)]
+        inherit_fds: Vec::new(),
+        #[cfg(unix)]
+        forward_signals: Vec::new(),
+        pty: false,
+        stderr_tail_lines: STDERR_TAIL_LINES,
+        description: None,
+        tag: None,
+        cancel: None,
+        metadata: Metadata(HashMap::new()),
+        last_status: Ignored(LastStatus(Mutex::new(None))),
+    }
+}
+
+/// Creates a process running `program` with all of `args`, which is otherwise
+/// just like one created by `process`.
+///
+/// An empty program name is an error.
+pub fn process_with_args<P, I>(program: &P, args: I) -> CargoResult<ProcessBuilder>
+    where P: AsOsStr + ?Sized, I: IntoIterator<Item=OsString>
+{
+    let program = program.as_os_str();
+    if program.is_empty() {
+        return Err(human("no program given"))
+    }
+    let mut p = process(program);
+    p.args.extend(args);
+    Ok(p)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::ffi::{OsStr, OsString};
+    use std::fs::File;
+
+    use tempdir::TempDir;
+
+    use super::{ProcessBuilder, find_in_path, process, process_os};
+    use super::process_with_args;
+
+    #[cfg(unix)]
+    pub fn shell(script: &str) -> ProcessBuilder {
+        let mut p = process("sh");
+        p.arg("-c").arg(script);
+        p
+    }
+
+    #[cfg(windows)]
+    pub fn shell(script: &str) -> ProcessBuilder {
+        let mut p = process("cmd");
+        p.arg("/C").arg(script);
+        p
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn process_error_keeps_raw_output() {
+        let err = shell("printf 'a\\377'; printf '\\377\\376b' >&2; exit 1")
+                      .exec_with_output().unwrap_err();
+        assert_eq!(err.raw_stdout(), Some(&b"a\xff"[..]));
+        assert_eq!(err.raw_stderr(), Some(&b"\xff\xfeb"[..]));
+
+        let err = process("cargo-no-such-program").exec_with_output()
+                                                  .unwrap_err();
+        assert_eq!(err.raw_stdout(), None);
+        assert_eq!(err.raw_stderr(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn process_error_to_json_round_trips() {
+        use rustc_serialize::json;
+        use util::errors::{SerializedProcessError, SerializedOutput};
+
+        let mut p = shell("printf out; printf '\\377' >&2; exit 3");
+        p.cwd("/");
+        let err = p.exec_with_output().unwrap_err();
+        let json: SerializedProcessError = json::decode(&err.to_json()).unwrap();
+        assert_eq!(json.message, err.desc);
+        assert_eq!(json.program, Some("sh".to_string()));
+        assert_eq!(json.args, ["-c", "printf out; printf '\\377' >&2; exit 3"]);
+        assert_eq!(json.cwd, Some("/".to_string()));
+        assert_eq!(json.code, Some(3));
+        assert_eq!(json.signal, None);
+        assert_eq!(json.stdout, Some(SerializedOutput {
+            data: "out".to_string(),
+            base64: false,
+        }));
+        assert_eq!(json.stderr, Some(SerializedOutput {
+            data: "/w==".to_string(),
+            base64: true,
+        }));
+    }
+
+    #[test]
+    fn find_in_path_finds_programs() {
+        let td = TempDir::new("cargo").unwrap();
+        File::create(&td.path().join("foo")).unwrap();
+        let paths = env::join_paths([td.path()].iter()).unwrap();
+
+        assert_eq!(find_in_path(OsStr::from_str("foo"), &paths, ""),
+                   Some(td.path().join("foo")));
+        assert_eq!(find_in_path(OsStr::from_str("bar"), &paths, ""), None);
+    }
+
+    #[test]
+    fn find_in_path_tries_exe_suffix() {
+        let td = TempDir::new("cargo").unwrap();
+        File::create(&td.path().join("foo.exe")).unwrap();
+        let paths = env::join_paths([td.path()].iter()).unwrap();
+
+        assert_eq!(find_in_path(OsStr::from_str("foo"), &paths, ".exe"),
+                   Some(td.path().join("foo.exe")));
+        assert_eq!(find_in_path(OsStr::from_str("foo.exe"), &paths, ".exe"),
+                   Some(td.path().join("foo.exe")));
+        assert_eq!(find_in_path(OsStr::from_str("foo"), &paths, ""), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn process_does_not_need_a_cwd() {
+        let old = env::current_dir().unwrap();
+        let td = TempDir::new("cargo").unwrap();
+        env::set_current_dir(td.path()).unwrap();
+        td.close().unwrap();
+
+        let mut p = process("foo");
+        env::set_current_dir(&old).unwrap();
+        assert_eq!(p.get_cwd(), None);
+
+        p.cwd(&old);
+        assert_eq!(p.get_cwd(), Some(&*old));
+    }
+
+    #[test]
+    fn process_with_args_matches_piecemeal_form() {
+        let args = vec![OsString::from_str("-c"), OsString::from_str("exit 3")];
+        let p = process_with_args("sh", args).unwrap();
+        let mut q = process("sh");
+        q.arg("-c").arg("exit 3");
+        assert!(p == q);
+        assert_eq!(p.get_program(), q.get_program());
+        assert_eq!(p.get_args(), q.get_args());
+
+        assert!(process_with_args("", Vec::new()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn process_with_args_runs_like_process() {
+        let args = vec![OsString::from_str("-c"), OsString::from_str("exit 3")];
+        let p = process_with_args("sh", args).unwrap();
+        assert_eq!(p.exec().unwrap_err().code(), Some(3));
+    }
+
+    #[test]
+    fn process_os_matches_process() {
+        let mut a = process_os(OsString::from_str("rustc"));
+        let mut b = process("rustc");
+        a.arg("-V");
+        b.arg("-V");
+        assert_eq!(a, b);
+
+        a.set_program(OsString::from_str("cargo"));
+        assert_eq!(a.get_program(), &OsString::from_str("cargo"));
+        assert!(a != b);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn running_out_of_files_is_explained() {
+        use libc;
+        use std::io;
+
+        // Actually using up the limit would break the tests running
+        // alongside this one, so fake the error spawning would return.
+        let err = process("rustc").spawn_error(io::Error::from_raw_os_error(
+            libc::EMFILE));
+        assert!(err.did_not_start());
+        assert!(err.desc.contains("too many files are open"), "{}", err);
+        assert!(err.desc.contains("fewer jobs"), "{}", err);
+
+        let err = process("rustc").spawn_error(io::Error::from_raw_os_error(
+            libc::ENOENT));
+        assert!(!err.desc.contains("too many files are open"), "{}", err);
+    }
+
+    #[test]
+    fn display_quotes_args() {
+        let mut p = process("foo");
+        p.arg("hello world");
+        assert_eq!(p.to_string(), "`foo \"hello world\"`");
+
+        let mut p = process("foo");
+        p.arg("a\"b");
+        assert_eq!(p.to_string(), "`foo \"a\\\"b\"`");
+
+        let mut p = process("foo");
+        p.arg("");
+        assert_eq!(p.to_string(), "`foo \"\"`");
+
+        let mut p = process("my prog");
+        p.arg("C:\\dir\\").arg("plain");
+        assert_eq!(p.to_string(), "`\"my prog\" \"C:\\dir\\\\\" plain`");
+        assert_eq!(p.debug_string(), "\"my prog\" \"C:\\dir\\\\\" plain");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn display_quotes_shell_metacharacters() {
+        let mut p = process("foo");
+        p.arg("$HOME").arg("a;b").arg("`id`").arg("it's").arg("\\$x");
+        assert_eq!(p.debug_string(),
+                   "foo \"\\$HOME\" \"a;b\" \"\\`id\\`\" \"it's\" \"\\\\\\$x\"");
+
+        // Pasting the command into `sh` gives back the same arguments.
+        let script = format!("printf '%s\\n' {}", p.debug_string());
+        let output = shell(&script).exec_with_output().unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        assert_eq!(stdout.lines_any().collect::<Vec<_>>(),
+                   ["foo", "$HOME", "a;b", "`id`", "it's", "\\$x"]);
+    }
+}
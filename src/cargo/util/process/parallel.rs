@@ -0,0 +1,236 @@
+//! Running several processes at once.
+
+use std::cmp;
+use std::path::PathBuf;
+use std::process::Output;
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use util::{CargoError, CargoResult, ProcessError, human};
+use util::process_error;
+use super::{ProcessBuilder, imp};
+
+/// How many file descriptors `run_parallel` sets aside for every process it
+/// runs at once: the pipes for its output, and those used while spawning it.
+const FDS_PER_PARALLEL_PROCESS: u64 = 8;
+
+/// A batch of processes started by `run_parallel_cancellable`.
+///
+/// Dropping the handle leaves the processes to finish in the background.
+pub struct BatchHandle {
+    cancel: Arc<AtomicBool>,
+    count: usize,
+    results: Receiver<(usize, Result<Output, ProcessError>)>,
+}
+
+impl BatchHandle {
+    /// Kills every process of the batch which is still running, along with
+    /// its group if it was configured with `kill_process_group`, and keeps
+    /// the rest from starting.
+    ///
+    /// Processes are killed within `CANCEL_POLL_MS` milliseconds. Other
+    /// threads can cancel the batch through `cancel_flag`.
+    pub fn cancel_all(&self) {
+        self.cancel.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns a flag which cancels the batch once set, for other threads to
+    /// call `cancel_all` through.
+    pub fn cancel_flag(&self) -> Arc<AtomicBool> {
+        self.cancel.clone()
+    }
+
+    /// Waits for the batch to finish, returning the results in the same order
+    /// as the processes were given.
+    ///
+    /// The error for a process which was cancelled, whether it was running or
+    /// hadn't started yet, has its `cancelled` field set.
+    pub fn wait(self) -> Vec<Result<Output, ProcessError>> {
+        let mut results = (0..self.count).map(|_| None).collect::<Vec<_>>();
+        for (i, result) in self.results.iter() {
+            results[i] = Some(result);
+        }
+        results.into_iter().map(|result| {
+            result.unwrap_or_else(|| {
+                Err(process_error("process thread died", None, None, None))
+            })
+        }).collect()
+    }
+}
+
+/// Runs every process in `cmds` as by `exec_with_output`, with at most
+/// `max_concurrency` of them running at any one time.
+///
+/// The results are returned in the same order as `cmds`, and a process which
+/// fails doesn't keep the others from running. A `max_concurrency` of zero is
+/// treated as one.
+///
+/// Fewer processes are run at once if running `max_concurrency` of them could
+/// exhaust the limit on open files, leaving half of that limit for the rest
+/// of cargo.
+pub fn run_parallel(cmds: Vec<ProcessBuilder>, max_concurrency: usize)
+                    -> Vec<CargoResult<Output>> {
+    let count = cmds.len();
+    let rx = start_batch(cmds, max_concurrency);
+    let mut results = (0..count).map(|_| None).collect::<Vec<_>>();
+    for (i, result) in rx.iter() {
+        results[i] = Some(result);
+    }
+    results.into_iter().map(|result| {
+        match result {
+            Some(result) => Ok(try!(result)),
+            None => Err(human("process thread died")),
+        }
+    }).collect()
+}
+
+/// Like `run_parallel`, but runs the processes in the background, returning a
+/// handle through which all of them can be cancelled at once.
+///
+/// Every process is made cancellable through a flag shared by the batch,
+/// replacing any flag given to `cancel_on`.
+pub fn run_parallel_cancellable(cmds: Vec<ProcessBuilder>,
+                                max_concurrency: usize) -> BatchHandle {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let count = cmds.len();
+    let cmds = cmds.into_iter().map(|mut cmd| {
+        cmd.cancel_on(cancel.clone());
+        cmd
+    }).collect();
+    BatchHandle {
+        cancel: cancel,
+        count: count,
+        results: start_batch(cmds, max_concurrency),
+    }
+}
+
+/// Starts running `cmds` on at most `max_concurrency` threads, sending the
+/// result of each along with its index once it's done.
+///
+/// Fewer processes are run at once if running `max_concurrency` of them could
+/// exhaust the limit on open files. Processes which are cancelled before
+/// their turn comes aren't run at all.
+fn start_batch(cmds: Vec<ProcessBuilder>, max_concurrency: usize)
+               -> Receiver<(usize, Result<Output, ProcessError>)> {
+    let count = cmds.len();
+    let queue = Arc::new(Mutex::new(cmds.into_iter().enumerate()));
+    let (tx, rx) = channel();
+    let mut jobs = cmp::max(max_concurrency, 1);
+    if let Some(limit) = imp::open_files_limit() {
+        let fits = limit / 2 / FDS_PER_PARALLEL_PROCESS;
+        jobs = cmp::min(jobs as u64, cmp::max(fits, 1)) as usize;
+    }
+    for _ in 0..cmp::min(jobs, count) {
+        let queue = queue.clone();
+        let tx = tx.clone();
+        thread::spawn(move || {
+            loop {
+                // Only hold on to the lock while taking the next process, not
+                // while it runs.
+                let next = queue.lock().unwrap().next();
+                let (i, cmd) = match next {
+                    Some(next) => next,
+                    None => break,
+                };
+                let result = if cmd.is_cancelled() {
+                    Err(cmd.not_started_error())
+                } else {
+                    cmd.exec_with_output()
+                };
+                if tx.send((i, result)).is_err() { break }
+            }
+        });
+    }
+    rx
+}
+
+/// Runs `base` as by `exec_with_output` once in each of `dirs`, one after the
+/// other.
+///
+/// Each result is paired with the directory it was run in, in the order of
+/// `dirs`, and a run which fails doesn't keep the rest from happening.
+/// Whatever `cwd` was set on `base` is replaced for each run.
+pub fn run_in_dirs(base: &ProcessBuilder, dirs: &[PathBuf])
+                   -> Vec<(PathBuf, CargoResult<Output>)> {
+    dirs.iter().map(|dir| {
+        let mut p = base.clone();
+        p.cwd(dir);
+        let result = p.exec_with_output().map_err(|e| {
+            Box::new(e) as Box<CargoError>
+        });
+        (dir.clone(), result)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use util::process::tests::shell;
+    use util::process::process;
+    use super::{run_in_dirs, run_parallel, run_parallel_cancellable};
+
+    #[test]
+    fn run_parallel_keeps_results_in_order() {
+        let cmds = (0..5).map(|i| {
+            shell(&format!("echo {} && exit {}", i, if i == 2 {1} else {0}))
+        }).collect::<Vec<_>>();
+        let results = run_parallel(cmds, 2);
+        assert_eq!(results.len(), 5);
+        for (i, result) in results.into_iter().enumerate() {
+            if i == 2 {
+                assert!(result.is_err());
+                continue
+            }
+            let output = result.unwrap();
+            assert_eq!(String::from_utf8(output.stdout).unwrap().trim(),
+                       i.to_string());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_in_dirs_runs_in_each_directory() {
+        let a = TempDir::new("cargo-run-in-dirs").unwrap();
+        let b = TempDir::new("cargo-run-in-dirs").unwrap();
+        let dirs = [a.path().to_path_buf(), b.path().to_path_buf()];
+        let mut p = process("pwd");
+        p.cwd("/");
+        let results = run_in_dirs(&p, &dirs);
+        assert_eq!(results.len(), 2);
+        for ((dir, result), expected) in results.into_iter().zip(dirs.iter()) {
+            assert_eq!(&dir, expected);
+            let stdout = String::from_utf8(result.unwrap().stdout).unwrap();
+            let name = expected.file_name().unwrap().to_str().unwrap();
+            assert!(stdout.trim().ends_with(name), "{}", stdout);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cancel_all_stops_the_whole_batch() {
+        use time;
+        use std::time::Duration;
+        use util::process::watchdog::sleep;
+
+        let mut cmds = vec![shell("echo done")];
+        cmds.extend((0..4).map(|_| shell("exec sleep 30")));
+        let batch = run_parallel_cancellable(cmds, 3);
+        sleep(Duration::milliseconds(300));
+        let start = time::precise_time_ns();
+        batch.cancel_all();
+        let results = batch.wait();
+        assert!(time::precise_time_ns() - start < 5_000_000_000);
+
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0].as_ref().unwrap().stdout, b"done\n");
+        for result in results[1..].iter() {
+            assert!(result.as_ref().unwrap_err().cancelled);
+        }
+        // The last sleeper never got a thread to run on.
+        let err = results[4].as_ref().unwrap_err();
+        assert!(err.desc.contains("before it started"), "{}", err.desc);
+    }
+}
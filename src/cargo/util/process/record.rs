@@ -0,0 +1,184 @@
+//! Recording the commands which are run, and caching tool probes.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::fs;
+use std::io::prelude::*;
+use std::io;
+use std::path::Path;
+use std::process::{Output, ExitStatus};
+use std::sync::Mutex;
+
+use rustc_serialize::json;
+
+use util::{CargoError, CargoResult, ProcessError};
+use super::ProcessBuilder;
+
+/// A log of processes that have been run, to which `ProcessBuilder::record_to`
+/// makes builders add an entry for each run.
+///
+/// Entries are written to the underlying writer as `RecordedCommand` objects
+/// encoded as JSON, one per line. Each one is written in one go, so entries
+/// from processes running concurrently don't get mixed up.
+pub struct CommandRecorder {
+    out: Mutex<Box<Write + Send>>,
+}
+
+impl CommandRecorder {
+    pub fn new(out: Box<Write + Send>) -> CommandRecorder {
+        CommandRecorder { out: Mutex::new(out) }
+    }
+
+    /// Creates a recorder which appends to the file at `path`, creating it
+    /// if it doesn't exist yet.
+    pub fn to_file(path: &Path) -> io::Result<CommandRecorder> {
+        let file = try!(fs::OpenOptions::new().write(true).append(true)
+                                              .create(true).open(path));
+        Ok(CommandRecorder::new(Box::new(file)))
+    }
+
+    /// Writes out `entry`. Failing to do so isn't worth failing the build
+    /// over, so errors are ignored.
+    fn record(&self, entry: &RecordedCommand) {
+        let mut line = json::encode(entry).unwrap();
+        line.push('\n');
+        let mut out = self.out.lock().unwrap();
+        let _ = out.write_all(line.as_bytes()).and_then(|()| out.flush());
+    }
+}
+
+/// An entry written by a `CommandRecorder` for a process which ran.
+///
+/// Arguments, paths and values which aren't valid unicode are rendered
+/// lossily.
+#[derive(RustcEncodable, RustcDecodable, Clone, PartialEq, Debug)]
+pub struct RecordedCommand {
+    pub program: String,
+    pub args: Vec<String>,
+    pub cwd: Option<String>,
+    /// The variables which the process saw but cargo didn't, with their
+    /// values.
+    pub env_added: BTreeMap<String, String>,
+    /// The variables the process saw with a value different from cargo's.
+    pub env_changed: BTreeMap<String, String>,
+    /// The variables of cargo which the process didn't see.
+    pub env_removed: Vec<String>,
+    pub code: Option<i32>,
+    /// The signal which terminated the process, on Unix.
+    pub signal: Option<i32>,
+    /// How long the process ran for, in milliseconds.
+    pub duration_ms: i64,
+}
+
+/// The results of `ProcessBuilder::probe_cached`, shared between builders.
+///
+/// Caches belong to whoever creates them, so that how long results are kept
+/// is up to them, such as for the duration of one cargo invocation.
+pub struct ProbeCache {
+    results: Mutex<HashMap<(OsString, Vec<OsString>), ProbeResult>>,
+}
+
+impl ProbeCache {
+    pub fn new() -> ProbeCache {
+        ProbeCache { results: Mutex::new(HashMap::new()) }
+    }
+}
+
+/// What `ProcessBuilder::probe_cached` found out about a program.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ProbeResult {
+    /// Whether the program could be run at all.
+    pub exists: bool,
+    /// The first line the program printed, on stdout or otherwise on stderr,
+    /// which is its version for the usual `--version` probe.
+    pub version: String,
+    /// How the program exited, if it could be run.
+    pub status: Option<ExitStatus>,
+}
+
+/// Returns the first non-empty line of the output of a probe, preferring
+/// stdout, with surrounding whitespace removed.
+fn probed_version(output: &Output) -> String {
+    let first_line = |bytes: &[u8]| {
+        String::from_utf8_lossy(bytes).lines_any().map(|line| line.trim())
+                                      .find(|line| !line.is_empty())
+                                      .map(|line| line.to_string())
+    };
+    first_line(&output.stdout).or_else(|| first_line(&output.stderr))
+                              .unwrap_or(String::new())
+}
+
+impl ProcessBuilder {
+    /// Runs the process to find out whether the program is there and which
+    /// version it is, unless `cache` already knows from a previous probe.
+    ///
+    /// Results are kept per program, as found on `PATH`, and arguments, so
+    /// this only spawns the process the first time it's called with a given
+    /// cache. A program which can't be run at all is recorded as not
+    /// existing rather than being an error. Two threads probing the same
+    /// program at once may both end up running it.
+    pub fn probe_cached(&self, cache: &ProbeCache) -> CargoResult<ProbeResult> {
+        let program = self.find_program().unwrap_or(self.program.clone());
+        let key = (program, self.args.clone());
+        if let Some(result) = cache.results.lock().unwrap().get(&key) {
+            return Ok(result.clone())
+        }
+
+        let output = match self.exec_with_output() {
+            Ok(output) => Some(output),
+            Err(ref e) if e.did_not_start() => None,
+            Err(ProcessError { output: Some(output), .. }) => Some(output),
+            Err(e) => return Err(Box::new(e) as Box<CargoError>),
+        };
+        let result = match output {
+            Some(output) => ProbeResult {
+                exists: true,
+                version: probed_version(&output),
+                status: Some(output.status),
+            },
+            None => ProbeResult {
+                exists: false,
+                version: String::new(),
+                status: None,
+            },
+        };
+        cache.results.lock().unwrap().insert(key, result.clone());
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use util::process::tests::shell;
+    use util::process::process;
+    use super::ProbeCache;
+
+    #[test]
+    fn probe_cached_only_runs_the_probe_once() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let spawns = Arc::new(AtomicUsize::new(0));
+        let spawns2 = spawns.clone();
+        let mut p = shell("echo tool 1.2.3");
+        p.on_spawn(Box::new(move |_| { spawns2.fetch_add(1, Ordering::SeqCst); }));
+        let cache = ProbeCache::new();
+
+        let first = p.probe_cached(&cache).unwrap();
+        assert!(first.exists);
+        assert_eq!(first.version, "tool 1.2.3");
+        assert!(first.status.unwrap().success());
+        assert_eq!(p.probe_cached(&cache).unwrap(), first);
+        assert_eq!(spawns.load(Ordering::SeqCst), 1);
+
+        // Another cache knows nothing of the first probe.
+        p.probe_cached(&ProbeCache::new()).unwrap();
+        assert_eq!(spawns.load(Ordering::SeqCst), 2);
+
+        let missing = process("cargo-no-such-program");
+        let result = missing.probe_cached(&cache).unwrap();
+        assert!(!result.exists);
+        assert_eq!(result.status, None);
+    }
+}
@@ -0,0 +1,271 @@
+//! Quoting and splitting of shell command lines.
+
+use std::borrow::Cow;
+use std::iter::repeat;
+
+use util::human;
+
+/// Quotes `arg` so it can be pasted into `sh` and come out as a single
+/// argument again.
+///
+/// Arguments containing anything `shell_quote` doesn't consider safe are
+/// wrapped in double quotes, which are easier to read in messages than single
+/// quotes. As `sh` still expands `$` and backticks inside of those, these are
+/// escaped with a backslash along with quotes, and so are backslashes which
+/// would otherwise escape the character after them.
+#[cfg(unix)]
+pub fn quote(arg: &str) -> Cow<str> {
+    let is_safe = |c: char| {
+        c.is_alphanumeric() || "_-./:=@%+,".contains_char(c)
+    };
+    if arg.len() > 0 && arg.chars().all(is_safe) {
+        return Cow::Borrowed(arg)
+    }
+
+    let mut quoted = String::from_str("\"");
+    let mut chars = arg.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                quoted.push('\\');
+                match chars.peek() {
+                    None | Some(&'\\') | Some(&'"') | Some(&'$') |
+                    Some(&'`') => quoted.push('\\'),
+                    Some(..) => {}
+                }
+            }
+            '"' | '$' | '`' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    Cow::Owned(quoted)
+}
+
+/// Quotes `arg` so it can be pasted into `cmd` and come out as a single
+/// argument again.
+///
+/// Arguments containing whitespace, quotes, backslashes or characters `cmd`
+/// treats specially, such as `&` and `|`, are wrapped in double quotes. Inside
+/// of those, quotes are escaped with a backslash and any backslashes preceding
+/// a quote are doubled, which is what the Windows argument parser expects.
+/// References to variables such as `%PATH%` are still expanded by `cmd`, as
+/// there is no way to escape them inside of quotes.
+#[cfg(windows)]
+pub fn quote(arg: &str) -> Cow<str> {
+    let needs_quotes = arg.is_empty() || arg.chars().any(|c| {
+        c.is_whitespace() || "\"\\&|<>^()".contains_char(c)
+    });
+    if !needs_quotes {
+        return Cow::Borrowed(arg)
+    }
+
+    let mut quoted = String::from_str("\"");
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.extend(repeat('\\').take(backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            c => {
+                quoted.extend(repeat('\\').take(backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    quoted.extend(repeat('\\').take(backslashes * 2));
+    quoted.push('"');
+    Cow::Owned(quoted)
+}
+
+/// Quotes `arg` for a POSIX shell, wrapping it in single quotes unless it only
+/// consists of characters which are never special to the shell.
+pub fn shell_quote(arg: &str) -> Cow<str> {
+    let is_safe = |c: char| {
+        c.is_alphanumeric() || "_-./:=@%+,".contains_char(c)
+    };
+    if arg.len() > 0 && arg.chars().all(is_safe) {
+        return Cow::Borrowed(arg)
+    }
+    Cow::Owned(format!("'{}'", arg.replace("'", "'\\''")))
+}
+
+/// Creates a process which has the shell of the platform interpret `cmd`,
+/// such as `ls *.rs | wc -l`.
+///
+/// On Unix this runs `$SHELL -c cmd`, falling back to `/bin/sh` if `SHELL`
+/// isn't set, and on Windows it runs `cmd /C cmd`. The shell gets to expand
+/// globs and variables, and to act on pipes, redirections and `;` in `cmd`,
+/// so it must never contain untrusted input. Prefer `process_from_shell`
+/// unless such shell syntax is actually needed.
+pub fn process_shell(cmd: &str) -> CargoResult<ProcessBuilder> {
+    if cmd.trim().is_empty() {
+        return Err(human("no command given to run in a shell"))
+    }
+    let (shell, flag) = platform_shell();
+    let mut p = process_os(shell);
+    p.arg(flag).arg(cmd);
+    Ok(p)
+}
+
+#[cfg(unix)]
+fn platform_shell() -> (OsString, &'static str) {
+    let shell = env::var_os("SHELL").into_iter().find(|s| !s.is_empty());
+    (shell.unwrap_or(OsString::from_str("/bin/sh")), "-c")
+}
+
+#[cfg(windows)]
+fn platform_shell() -> (OsString, &'static str) {
+    (OsString::from_str("cmd"), "/C")
+}
+
+/// Creates a process from a whole command line, such as `qemu-arm -L /usr/arm`.
+///
+/// The command is split into words following the quoting rules of a POSIX
+/// shell: single quotes preserve everything literally, double quotes only
+/// allow `\"` and `\\` to be escaped, and outside of quotes a backslash escapes
+/// any character. The first word is the program and the rest are arguments.
+/// No other shell syntax, such as variable expansion, is understood.
+pub fn process_from_shell(cmd: &str) -> CargoResult<ProcessBuilder> {
+    let words = try!(split_shell_words(cmd));
+    if words.is_empty() {
+        return Err(human(format!("no program given in command `{}`", cmd)))
+    }
+    let mut p = process(&words[0]);
+    p.args(&words[1..]);
+    Ok(p)
+}
+
+fn split_shell_words(cmd: &str) -> CargoResult<Vec<String>> {
+    let unbalanced = |quote: &str| {
+        human(format!("unbalanced {} quote in command `{}`", quote, cmd))
+    };
+    let mut words = Vec::new();
+    let mut word = String::new();
+    // Tracked separately from `word` so that `''` yields an empty argument.
+    let mut in_word = false;
+    let mut chars = cmd.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(unbalanced("single")),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ '"') | Some(c @ '\\') => word.push(c),
+                            Some(c) => { word.push('\\'); word.push(c); }
+                            None => return Err(unbalanced("double")),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(unbalanced("double")),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                match chars.next() {
+                    Some(c) => word.push(c),
+                    None => return Err(human(format!("command `{}` ends with \
+                                                      an unescaped backslash",
+                                                     cmd))),
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(word);
+                    word = String::new();
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::ffi::OsString;
+
+    use super::{process_from_shell, process_shell};
+
+    fn shell_words(cmd: &str) -> Vec<String> {
+        let p = process_from_shell(cmd).unwrap();
+        Some(p.get_program()).into_iter().chain(p.get_args().iter()).map(|s| {
+            s.to_str().unwrap().to_string()
+        }).collect()
+    }
+
+    #[test]
+    fn process_from_shell_handles_quotes() {
+        assert_eq!(shell_words("qemu-arm -L '/usr/a b' \"x \\\"y\\\" z\" ''"),
+                   ["qemu-arm", "-L", "/usr/a b", "x \"y\" z", ""]);
+        assert_eq!(shell_words("'it'\"'\"'s'"), ["it's"]);
+    }
+
+    #[test]
+    fn process_from_shell_handles_escapes() {
+        assert_eq!(shell_words("  foo\\ bar  baz\\\\ "), ["foo bar", "baz\\"]);
+        assert_eq!(shell_words("\"a\\b\""), ["a\\b"]);
+    }
+
+    #[test]
+    fn process_from_shell_rejects_malformed_commands() {
+        assert!(process_from_shell("foo 'bar").is_err());
+        assert!(process_from_shell("foo \"bar\\\"").is_err());
+        assert!(process_from_shell("foo\\").is_err());
+        assert!(process_from_shell("   ").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn process_shell_runs_the_shell() {
+        let p = process_shell("echo a | tr a b").unwrap();
+        let shell = env::var_os("SHELL").into_iter().find(|s| !s.is_empty())
+                        .unwrap_or(OsString::from_str("/bin/sh"));
+        assert_eq!(*p.get_program(), shell);
+        assert_eq!(p.get_args(), [OsString::from_str("-c"),
+                                  OsString::from_str("echo a | tr a b")]);
+        assert_eq!(p.exec_with_output().unwrap().stdout, b"b\n".to_vec());
+
+        assert!(process_shell(" ").is_err());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn process_shell_runs_the_shell() {
+        let p = process_shell("echo a & exit 3").unwrap();
+        assert_eq!(*p.get_program(), OsString::from_str("cmd"));
+        assert_eq!(p.get_args(), [OsString::from_str("/C"),
+                                  OsString::from_str("echo a & exit 3")]);
+        assert_eq!(p.exec().unwrap_err().code(), Some(3));
+
+        assert!(process_shell(" ").is_err());
+    }
+}
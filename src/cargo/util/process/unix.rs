@@ -0,0 +1,697 @@
+//! The parts of running processes which are specific to Unix.
+
+use std::cmp;
+use std::ffi::{CStr, CString, OsString};
+use std::fs;
+use std::io;
+use std::mem;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::ExitStatusExt;
+use std::path::Path;
+use std::process::ExitStatus;
+use std::sync::atomic::{ATOMIC_BOOL_INIT, ATOMIC_USIZE_INIT};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{StaticMutex, MUTEX_INIT};
+use std::time::Duration;
+
+use libc;
+
+use util::spawn::{Child, Command, FileDesc, Stdio};
+use super::{ProcessBuilder, ResourceUsage};
+
+extern {
+    fn nice(inc: libc::c_int) -> libc::c_int;
+    fn umask(mask: libc::mode_t) -> libc::mode_t;
+    #[link_name = "signal"]
+    fn set_signal_handler(signum: libc::c_int,
+                          handler: libc::size_t) -> libc::size_t;
+    fn wait4(pid: libc::pid_t, status: *mut libc::c_int,
+             options: libc::c_int, rusage: *mut rusage) -> libc::pid_t;
+}
+
+const SIG_IGN: libc::size_t = 1;
+
+#[repr(C)]
+struct rusage {
+    ru_utime: libc::timeval,
+    ru_stime: libc::timeval,
+    ru_maxrss: libc::c_long,
+    // The remaining fields aren't used, but still have to be there for
+    // `wait4` to fill in.
+    ru_rest: [libc::c_long; 13],
+}
+
+/// Waits for `child` to exit like `Child::wait`, also returning the
+/// resources it used.
+pub fn wait_with_usage(child: &mut Child)
+                       -> io::Result<(ExitStatus, ResourceUsage)> {
+    let mut status = 0;
+    let mut usage: rusage = unsafe { mem::zeroed() };
+    loop {
+        let pid = child.id() as libc::pid_t;
+        if unsafe { wait4(pid, &mut status, 0, &mut usage) } != -1 {
+            break
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err)
+        }
+    }
+
+    // Linux reports the peak RSS in kilobytes, OS X in bytes.
+    let scale = if cfg!(target_os = "macos") {1} else {1024};
+    let usage = ResourceUsage {
+        user_time: to_duration(&usage.ru_utime),
+        system_time: to_duration(&usage.ru_stime),
+        max_rss: usage.ru_maxrss as u64 * scale,
+    };
+    Ok((ExitStatus::from_raw(status), usage))
+}
+
+fn to_duration(tv: &libc::timeval) -> Duration {
+    Duration::seconds(tv.tv_sec as i64) +
+        Duration::microseconds(tv.tv_usec as i64)
+}
+
+extern {
+    fn posix_openpt(flags: libc::c_int) -> libc::c_int;
+    fn grantpt(fd: libc::c_int) -> libc::c_int;
+    fn unlockpt(fd: libc::c_int) -> libc::c_int;
+    fn ptsname(fd: libc::c_int) -> *mut libc::c_char;
+}
+
+// `ptsname` returns a buffer which is shared between all threads.
+static PTSNAME_LOCK: StaticMutex = MUTEX_INIT;
+
+/// Connects the stdio of `command` to a new pseudo-terminal if `builder`
+/// asks for one, returning the master end to read the output from.
+pub fn attach_pty(builder: &ProcessBuilder, command: &mut Command)
+                  -> io::Result<Option<FileDesc>> {
+    if !builder.pty {
+        return Ok(None)
+    }
+    // Neither end may become the controlling terminal of cargo.
+    let flags = libc::O_RDWR | libc::O_NOCTTY;
+    let master = try!(open_fd(|| unsafe { posix_openpt(flags) }));
+    try!(master.set_cloexec(true));
+    let name = unsafe {
+        if grantpt(master.raw()) != 0 || unlockpt(master.raw()) != 0 {
+            return Err(io::Error::last_os_error())
+        }
+        let _lock = PTSNAME_LOCK.lock();
+        let name = ptsname(master.raw());
+        if name.is_null() {
+            return Err(io::Error::last_os_error())
+        }
+        CString::new(CStr::from_ptr(name).to_bytes()).unwrap()
+    };
+    let slave = try!(open_fd(|| unsafe {
+        libc::open(name.as_ptr(), flags, 0)
+    }));
+    try!(slave.set_cloexec(true));
+
+    if !builder.stdout_redirected() {
+        command.stdout(try!(dup(&slave)));
+    }
+    if !builder.stderr_redirected() {
+        command.stderr(try!(dup(&slave)));
+    }
+    command.stdin(Stdio::from_desc(slave));
+    Ok(Some(master))
+}
+
+// Stderr is made a copy of stdout in the child by `configure` instead.
+pub fn merge_stderr(_builder: &ProcessBuilder, _command: &mut Command)
+                    -> io::Result<Option<FileDesc>> {
+    Ok(None)
+}
+
+/// Takes ownership of the descriptor `f` opens, if it succeeds.
+fn open_fd<F: FnOnce() -> libc::c_int>(f: F) -> io::Result<FileDesc> {
+    let fd = f();
+    if fd == -1 {
+        return Err(io::Error::last_os_error())
+    }
+    Ok(unsafe { FileDesc::new(fd) })
+}
+
+fn dup(fd: &FileDesc) -> io::Result<Stdio> {
+    dup_raw(fd.raw() as usize)
+}
+
+/// Duplicates the descriptor `fd`, which is left open, into a stream for
+/// the child which isn't inherited by other processes.
+pub fn dup_raw(fd: usize) -> io::Result<Stdio> {
+    let fd = try!(open_fd(|| unsafe { libc::dup(fd as libc::c_int) }));
+    try!(fd.set_cloexec(true));
+    Ok(Stdio::from_desc(fd))
+}
+
+/// Applies the Unix specific parts of `builder` to `command`.
+pub fn configure(builder: &ProcessBuilder, command: &mut Command) {
+    if builder.detach {
+        // `setsid` is async-signal-safe, and can't fail in a freshly
+        // forked child as it can't be a process group leader yet.
+        unsafe {
+            command.pre_exec(|| {
+                libc::setsid();
+                Ok(())
+            });
+        }
+    }
+    if let Some(uid) = builder.uid {
+        command.uid(uid);
+    }
+    if let Some(gid) = builder.gid {
+        command.gid(gid);
+    }
+    if let Some(ref arg0) = builder.arg0 {
+        command.arg0(arg0);
+    } else if builder.comm().is_some() {
+        // Don't let running through the symlink of `set_comm` show.
+        command.arg0(&builder.program);
+    }
+    if builder.redirect_stderr_to_stdout {
+        // `dup2` is async-signal-safe, and this runs after stdout has
+        // been set up, whatever it was connected to.
+        unsafe {
+            command.pre_exec(|| {
+                if libc::dup2(1, 2) == -1 {
+                    return Err(io::Error::last_os_error())
+                }
+                Ok(())
+            });
+        }
+    }
+    if builder.new_process_group || builder.kill_process_group {
+        // This runs in the child between fork and exec, where only
+        // async-signal-safe functions such as `setpgid` may be called.
+        unsafe {
+            command.pre_exec(|| {
+                libc::setpgid(0, 0);
+                Ok(())
+            });
+        }
+    }
+    if builder.inherit_fds.len() > 0 {
+        let fds = builder.inherit_fds.clone();
+        // Both `dup2` and `fcntl` are async-signal-safe. Descriptors
+        // created by `dup2` never have close-on-exec set, but one which is
+        // already in the right place has to have it cleared explicitly.
+        unsafe {
+            command.pre_exec(move || {
+                for &(child_fd, source) in fds.iter() {
+                    if child_fd == source {
+                        let flags = libc::fcntl(source, libc::F_GETFD);
+                        if flags == -1 {
+                            return Err(io::Error::last_os_error())
+                        }
+                        let flags = flags & !libc::FD_CLOEXEC;
+                        if libc::fcntl(source, libc::F_SETFD, flags) == -1 {
+                            return Err(io::Error::last_os_error())
+                        }
+                    } else if libc::dup2(source, child_fd) == -1 {
+                        return Err(io::Error::last_os_error())
+                    }
+                }
+                Ok(())
+            });
+        }
+    }
+    if let Some(mask) = builder.umask {
+        // `umask` can't fail, and is async-signal-safe.
+        unsafe {
+            command.pre_exec(move || {
+                umask(mask as libc::mode_t);
+                Ok(())
+            });
+        }
+    }
+    if let Some(secs) = builder.cpu_time_limit {
+        // Both `getrlimit` and `setrlimit` are async-signal-safe. Only the
+        // soft limit is lowered, which is what triggers `SIGXCPU`.
+        unsafe {
+            command.pre_exec(move || {
+                let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+                if libc::getrlimit(libc::RLIMIT_CPU, &mut limit) != 0 {
+                    return Err(io::Error::last_os_error())
+                }
+                limit.rlim_cur = cmp::min(secs as libc::rlim_t,
+                                          limit.rlim_max);
+                if libc::setrlimit(libc::RLIMIT_CPU, &limit) != 0 {
+                    return Err(io::Error::last_os_error())
+                }
+                Ok(())
+            });
+        }
+    }
+    if let Some(bytes) = builder.file_size_limit {
+        // As with the CPU time limit, only the soft limit is lowered,
+        // which is what triggers `SIGXFSZ`.
+        unsafe {
+            command.pre_exec(move || {
+                let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+                if libc::getrlimit(libc::RLIMIT_FSIZE, &mut limit) != 0 {
+                    return Err(io::Error::last_os_error())
+                }
+                limit.rlim_cur = cmp::min(bytes as libc::rlim_t,
+                                          limit.rlim_max);
+                if libc::setrlimit(libc::RLIMIT_FSIZE, &limit) != 0 {
+                    return Err(io::Error::last_os_error())
+                }
+                Ok(())
+            });
+        }
+    }
+    if builder.ignore_sigint {
+        // `signal` is async-signal-safe, and an ignored signal stays
+        // ignored across exec.
+        unsafe {
+            command.pre_exec(|| {
+                set_signal_handler(libc::SIGINT, SIG_IGN);
+                Ok(())
+            });
+        }
+    }
+    if let Some(increment) = builder.nice {
+        // Failures can't be told apart from a new niceness of -1 without
+        // touching errno, and either way the process should still run.
+        unsafe {
+            command.pre_exec(move || {
+                nice(increment as libc::c_int);
+                Ok(())
+            });
+        }
+    }
+    configure_chroot(builder, command);
+    // This comes last so that the other hooks aren't subject to the
+    // filter.
+    configure_seccomp(builder, command);
+}
+
+#[cfg(target_os = "linux")]
+fn configure_chroot(builder: &ProcessBuilder, command: &mut Command) {
+    extern {
+        fn chroot(path: *const libc::c_char) -> libc::c_int;
+    }
+
+    let root = match builder.chroot {
+        Some(ref root) => root,
+        None => return,
+    };
+    // The paths are converted up front, so that nothing has to be
+    // allocated between fork and exec. Both `chroot` and `chdir` are
+    // async-signal-safe.
+    let root = CString::new(root.as_os_str().as_bytes()).ok();
+    let cwd = builder.cwd.as_ref().map(|cwd| CString::new(cwd.as_bytes()));
+    unsafe {
+        command.pre_exec(move || {
+            let root = match root {
+                Some(ref root) => root,
+                None => return Err(io::Error::from_raw_os_error(libc::EINVAL)),
+            };
+            if chroot(root.as_ptr()) != 0 ||
+               libc::chdir(b"/\0".as_ptr() as *const libc::c_char) != 0 {
+                return Err(io::Error::last_os_error())
+            }
+            match cwd {
+                Some(Ok(ref cwd)) if libc::chdir(cwd.as_ptr()) != 0 => {
+                    Err(io::Error::last_os_error())
+                }
+                Some(Err(..)) => Err(io::Error::from_raw_os_error(libc::EINVAL)),
+                _ => Ok(()),
+            }
+        });
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn configure_chroot(_builder: &ProcessBuilder, _command: &mut Command) {}
+
+#[cfg(all(target_os = "linux",
+            any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn configure_seccomp(builder: &ProcessBuilder, command: &mut Command) {
+    if let Some(profile) = builder.seccomp_profile {
+        // The filter is built up front, so that nothing has to be
+        // allocated between fork and exec. `prctl` is async-signal-safe.
+        let filter = seccomp::filter(profile);
+        unsafe {
+            command.pre_exec(move || seccomp::install(&filter));
+        }
+    }
+}
+
+#[cfg(not(all(target_os = "linux",
+              any(target_arch = "x86_64", target_arch = "aarch64"))))]
+fn configure_seccomp(_builder: &ProcessBuilder, _command: &mut Command) {}
+
+pub fn success_status() -> ExitStatus {
+    ExitStatus::from_raw(0)
+}
+
+/// Joins `argv` into a command line for `sh`, quoting words just like
+/// `shell_quote` but working on bytes.
+pub fn command_line(argv: &[OsString]) -> OsString {
+    let is_safe = |b: u8| {
+        match b {
+            b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' => true,
+            b => b"_-./:=@%+,".contains(&b),
+        }
+    };
+    let mut line = Vec::new();
+    for (i, arg) in argv.iter().enumerate() {
+        if i > 0 {
+            line.push(b' ');
+        }
+        let arg = arg.as_bytes();
+        if arg.len() > 0 && arg.iter().all(|&b| is_safe(b)) {
+            line.push_all(arg);
+            continue
+        }
+        line.push(b'\'');
+        for &b in arg.iter() {
+            if b == b'\'' {
+                line.push_all(b"'\\''");
+            } else {
+                line.push(b);
+            }
+        }
+        line.push(b'\'');
+    }
+    OsString::from_vec(line)
+}
+
+pub fn is_executable(_path: &Path, metadata: &fs::Metadata) -> bool {
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+pub fn os_string_from_bytes(bytes: Vec<u8>) -> Option<OsString> {
+    Some(OsString::from_vec(bytes))
+}
+
+// There are no code pages on Unix, where output is taken to be UTF-8.
+pub fn decode_code_page(bytes: &[u8], _oem: bool) -> Option<String> {
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+/// Creates a new file at `path` which only the current user can access,
+/// failing if anything already exists there.
+pub fn create_private(path: &Path) -> io::Result<FileDesc> {
+    let path = try!(CString::new(path.as_os_str().as_bytes()).map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidInput,
+                       "path contains a nul byte", None)
+    }));
+    let flags = libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL |
+                libc::O_CLOEXEC;
+    open_fd(|| unsafe { libc::open(path.as_ptr(), flags, 0o600) })
+}
+
+pub fn current_pid() -> u32 {
+    unsafe { libc::getpid() as u32 }
+}
+
+/// Returns the signal to send on timeout, if it isn't `SIGKILL`.
+pub fn timeout_signal(builder: &ProcessBuilder) -> Option<i32> {
+    builder.timeout_signal.and_then(|sig| {
+        if sig == libc::SIGKILL {None} else {Some(sig)}
+    })
+}
+
+/// Whether `status` shows that the process ran into the limit set through
+/// `cpu_time_limit`.
+pub fn exceeded_cpu_time(builder: &ProcessBuilder, status: &ExitStatus)
+                         -> bool {
+    builder.cpu_time_limit.is_some() &&
+        status.signal() == Some(libc::SIGXCPU)
+}
+
+/// Whether `status` shows that the process ran into the limit set through
+/// `file_size_limit`.
+pub fn exceeded_file_size(builder: &ProcessBuilder, status: &ExitStatus)
+                          -> bool {
+    builder.file_size_limit.is_some() &&
+        status.signal() == Some(libc::SIGXFSZ)
+}
+
+pub fn stdout_is_tty() -> bool {
+    unsafe { libc::isatty(libc::STDOUT_FILENO) != 0 }
+}
+
+/// Whether `err` means we ran out of file descriptors.
+pub fn too_many_open_files(err: &io::Error) -> bool {
+    match err.raw_os_error() {
+        Some(libc::EMFILE) | Some(libc::ENFILE) => true,
+        _ => false,
+    }
+}
+
+/// Returns the soft limit on the number of files this process may have
+/// open, if there is one.
+pub fn open_files_limit() -> Option<u64> {
+    let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        return None
+    }
+    Some(limit.rlim_cur as u64)
+}
+
+pub const ARG_LENGTH_UNIT: &'static str = "bytes";
+
+/// Returns how much space the arguments and environment of a new process
+/// may take up together, if the system says.
+pub fn arg_max() -> Option<usize> {
+    match unsafe { libc::sysconf(libc::_SC_ARG_MAX) } {
+        n if n > 0 => Some(n as usize),
+        _ => None,
+    }
+}
+
+/// Returns how much of the space counted by `arg_max` the process would
+/// take up: each argument and variable, their terminators, and the
+/// pointers to them.
+pub fn arg_length(builder: &ProcessBuilder) -> usize {
+    let ptr = mem::size_of::<usize>();
+    let argv = builder.get_argv().iter().fold(0, |sum, arg| {
+        sum + arg.as_bytes().len() + 1 + ptr
+    });
+    let env = builder.resolved_env().iter().fold(0, |sum, (key, val)| {
+        sum + key.as_bytes().len() + val.as_bytes().len() + 2 + ptr
+    });
+    argv + env + 2 * ptr
+}
+
+pub fn kill(pid: u32, group: bool) {
+    signal(pid, group, libc::SIGKILL)
+}
+
+/// Blocks until `child` has exited, but leaves it to be reaped.
+pub fn wait_exited(child: &Child) -> io::Result<()> {
+    let mut info: libc::siginfo_t = unsafe { mem::zeroed() };
+    let pid = child.id() as libc::id_t;
+    let options = libc::WEXITED | libc::WNOWAIT;
+    if unsafe { libc::waitid(libc::P_PID, pid, &mut info, options) } == -1 {
+        return Err(io::Error::last_os_error())
+    }
+    Ok(())
+}
+
+static FORWARDING: AtomicBool = ATOMIC_BOOL_INIT;
+static FORWARD_PID: AtomicUsize = ATOMIC_USIZE_INIT;
+static FORWARD_GROUP: AtomicBool = ATOMIC_BOOL_INIT;
+
+// Only reads atomics and calls `kill`, which is async-signal-safe.
+extern fn forward_signal(sig: libc::c_int) {
+    let pid = FORWARD_PID.load(Ordering::SeqCst);
+    if pid != 0 {
+        signal(pid as u32, FORWARD_GROUP.load(Ordering::SeqCst), sig)
+    }
+}
+
+/// Relays the signals given to `forward_signals` to a running process
+/// until dropped, when the previous handlers are reinstated.
+pub struct SignalForwarder {
+    previous: Vec<(libc::c_int, libc::size_t)>,
+}
+
+pub fn forward_signals(builder: &ProcessBuilder, child: &Child)
+                       -> Option<SignalForwarder> {
+    if builder.forward_signals.is_empty() ||
+       FORWARDING.compare_and_swap(false, true, Ordering::SeqCst) {
+        return None
+    }
+    let group = builder.new_process_group || builder.kill_process_group;
+    FORWARD_GROUP.store(group, Ordering::SeqCst);
+    FORWARD_PID.store(child.id() as usize, Ordering::SeqCst);
+    let handler = forward_signal as extern fn(libc::c_int) as libc::size_t;
+    let previous = builder.forward_signals.iter().map(|&sig| {
+        (sig, unsafe { set_signal_handler(sig, handler) })
+    }).collect();
+    Some(SignalForwarder { previous: previous })
+}
+
+impl Drop for SignalForwarder {
+    fn drop(&mut self) {
+        for &(sig, handler) in self.previous.iter().rev() {
+            unsafe { set_signal_handler(sig, handler); }
+        }
+        FORWARD_PID.store(0, Ordering::SeqCst);
+        FORWARDING.store(false, Ordering::SeqCst);
+    }
+}
+
+pub fn signal(pid: u32, group: bool, sig: i32) {
+    let pid = pid as libc::pid_t;
+    unsafe { libc::kill(if group {-pid} else {pid}, sig); }
+}
+
+/// Building and installing the seccomp filters applied through
+/// `seccomp_profile`.
+#[cfg(all(target_os = "linux",
+            any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod seccomp {
+    use std::io;
+
+    use libc;
+
+    use super::super::SeccompProfile;
+
+    extern {
+        fn prctl(option: libc::c_int, arg2: libc::c_ulong,
+                 arg3: libc::c_ulong, arg4: libc::c_ulong,
+                 arg5: libc::c_ulong) -> libc::c_int;
+    }
+
+    const PR_SET_SECCOMP: libc::c_int = 22;
+    const PR_SET_NO_NEW_PRIVS: libc::c_int = 38;
+    const SECCOMP_MODE_FILTER: libc::c_ulong = 2;
+
+    const SECCOMP_RET_KILL: u32 = 0;
+    const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+    const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+
+    const BPF_LD_W_ABS: u16 = 0x20;
+    const BPF_JEQ_K: u16 = 0x15;
+    const BPF_JSET_K: u16 = 0x45;
+    const BPF_RET_K: u16 = 0x06;
+
+    // Offsets into `struct seccomp_data`. Only the low half of the first
+    // argument is ever looked at.
+    const OFFSET_NR: u32 = 0;
+    const OFFSET_ARCH: u32 = 4;
+    #[cfg(target_endian = "little")]
+    const OFFSET_ARG0: u32 = 16;
+    #[cfg(target_endian = "big")]
+    const OFFSET_ARG0: u32 = 20;
+
+    const AF_UNIX: u32 = 1;
+    const CLONE_THREAD: u32 = 0x10000;
+
+    #[cfg(target_arch = "x86_64")]
+    mod sys {
+        pub const AUDIT_ARCH: u32 = 0xc000_003e;
+        pub const SOCKET: u32 = 41;
+        pub const CLONE: u32 = 56;
+        pub const CLONE3: u32 = 435;
+        pub const FORKS: &'static [u32] = &[57, 58];
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    mod sys {
+        pub const AUDIT_ARCH: u32 = 0xc000_00b7;
+        pub const SOCKET: u32 = 198;
+        pub const CLONE: u32 = 220;
+        pub const CLONE3: u32 = 435;
+        pub const FORKS: &'static [u32] = &[];
+    }
+
+    /// A single instruction of a classic BPF program, as `sock_filter`.
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct Instruction {
+        code: u16,
+        jt: u8,
+        jf: u8,
+        k: u32,
+    }
+
+    #[repr(C)]
+    struct Program {
+        len: libc::c_ushort,
+        filter: *const Instruction,
+    }
+
+    fn stmt(code: u16, k: u32) -> Instruction {
+        Instruction { code: code, jt: 0, jf: 0, k: k }
+    }
+
+    fn jump(code: u16, k: u32, jt: u8, jf: u8) -> Instruction {
+        Instruction { code: code, jt: jt, jf: jf, k: k }
+    }
+
+    /// Builds the filter for `profile`.
+    ///
+    /// Processes running under a different architecture, whose system
+    /// call numbers don't match, are killed outright.
+    pub fn filter(profile: SeccompProfile) -> Vec<Instruction> {
+        let deny = |errno: libc::c_int| {
+            stmt(BPF_RET_K, SECCOMP_RET_ERRNO | errno as u32)
+        };
+        let mut filter = vec![
+            stmt(BPF_LD_W_ABS, OFFSET_ARCH),
+            jump(BPF_JEQ_K, sys::AUDIT_ARCH, 1, 0),
+            stmt(BPF_RET_K, SECCOMP_RET_KILL),
+            stmt(BPF_LD_W_ABS, OFFSET_NR),
+        ];
+        // Each check leaves the system call number in the accumulator
+        // for the next one when it lets the call through.
+        match profile {
+            SeccompProfile::DenyNetwork => {
+                filter.push_all(&[
+                    jump(BPF_JEQ_K, sys::SOCKET, 0, 4),
+                    stmt(BPF_LD_W_ABS, OFFSET_ARG0),
+                    jump(BPF_JEQ_K, AF_UNIX, 1, 0),
+                    deny(libc::EPERM),
+                    stmt(BPF_LD_W_ABS, OFFSET_NR),
+                ]);
+            }
+            SeccompProfile::DenyNewProcesses => {
+                for &nr in sys::FORKS.iter() {
+                    filter.push(jump(BPF_JEQ_K, nr, 0, 1));
+                    filter.push(deny(libc::EPERM));
+                }
+                // With `clone3` unavailable, libc falls back to `clone`,
+                // whose flags can be inspected.
+                filter.push_all(&[
+                    jump(BPF_JEQ_K, sys::CLONE3, 0, 1),
+                    deny(libc::ENOSYS),
+                    jump(BPF_JEQ_K, sys::CLONE, 0, 4),
+                    stmt(BPF_LD_W_ABS, OFFSET_ARG0),
+                    jump(BPF_JSET_K, CLONE_THREAD, 1, 0),
+                    deny(libc::EPERM),
+                    stmt(BPF_LD_W_ABS, OFFSET_NR),
+                ]);
+            }
+        }
+        filter.push(stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+        filter
+    }
+
+    /// Installs `filter` for the calling thread, which also has to give up
+    /// gaining privileges through exec for that to be allowed.
+    pub fn install(filter: &[Instruction]) -> io::Result<()> {
+        let program = Program {
+            len: filter.len() as libc::c_ushort,
+            filter: filter.as_ptr(),
+        };
+        unsafe {
+            if prctl(PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 ||
+               prctl(PR_SET_SECCOMP, SECCOMP_MODE_FILTER,
+                     &program as *const Program as libc::c_ulong,
+                     0, 0) != 0 {
+                return Err(io::Error::last_os_error())
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+//! The timers which watch over a running process.
+
+use std::cmp;
+use std::i64;
+use std::io;
+use std::mem;
+use std::process::ExitStatus;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Condvar};
+use std::thread;
+use std::time::Duration;
+
+use time;
+
+use util::spawn::Child;
+use super::imp;
+
+/// How long a process which timed out has to exit after being sent the signal
+/// configured through `timeout_signal`, before it's killed outright.
+const TIMEOUT_GRACE_SECS: u64 = 5;
+
+/// How often `wait_timeout` checks whether the child has exited.
+const WAIT_POLL_MS: i64 = 10;
+
+/// How often a process which can be cancelled through `cancel_on` checks
+/// whether it has been.
+const CANCEL_POLL_MS: u64 = 50;
+
+/// Calls `f` again for as long as it fails because it was interrupted by a
+/// signal.
+pub fn retry_interrupted<T, F>(mut f: F) -> io::Result<T>
+    where F: FnMut() -> io::Result<T>
+{
+    loop {
+        match f() {
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            result => return result,
+        }
+    }
+}
+
+/// Kills a child process if it's still running once a deadline has passed, or
+/// once it has been cancelled.
+///
+/// The watchdog runs on its own thread and signals the child by pid, as the
+/// `Child` itself is tied up in a blocking `wait` on the spawning thread. It
+/// has to be disarmed through `disarm` before the child is reaped. If
+/// it's given a `graceful` signal that is sent first on timeout, and the child
+/// is only killed if it's still running once `TIMEOUT_GRACE_SECS` have
+/// passed. Cancelled children are killed right away.
+pub struct Watchdog {
+    state: Arc<(Mutex<WatchdogState>, Condvar)>,
+}
+
+struct WatchdogState {
+    done: bool,
+    fired: Option<Fired>,
+}
+
+/// Why a `Watchdog` went after its child.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Fired {
+    TimedOut,
+    Cancelled,
+}
+
+impl Watchdog {
+    pub fn start(pid: u32, timeout: Option<Duration>,
+             cancel: Option<Arc<AtomicBool>>, group: bool,
+             graceful: Option<i32>) -> Watchdog {
+        let state = Arc::new((Mutex::new(WatchdogState {
+            done: false,
+            fired: None,
+        }), Condvar::new()));
+        let state2 = state.clone();
+        let deadline = timeout.map(|dur| {
+            let nanos = dur.num_nanoseconds().unwrap_or(i64::MAX);
+            time::precise_time_ns().saturating_add(nanos as u64)
+        });
+
+        thread::spawn(move || {
+            let mut deadline = deadline;
+            let mut graceful = graceful;
+            let &(ref lock, ref cvar) = &*state2;
+            let mut state = lock.lock().unwrap();
+            while !state.done {
+                let now = time::precise_time_ns();
+                let cancelled = cancel.as_ref().map(|flag| {
+                    flag.load(Ordering::SeqCst)
+                }).unwrap_or(false);
+                if cancelled && state.fired.is_none() {
+                    state.fired = Some(Fired::Cancelled);
+                    imp::kill(pid, group);
+                    break
+                }
+                if deadline.map(|d| now >= d).unwrap_or(false) {
+                    state.fired = Some(Fired::TimedOut);
+                    match graceful.take() {
+                        Some(sig) => {
+                            imp::signal(pid, group, sig);
+                            let grace = TIMEOUT_GRACE_SECS * 1_000_000_000;
+                            deadline = Some(now.saturating_add(grace));
+                            continue
+                        }
+                        None => {
+                            imp::kill(pid, group);
+                            break
+                        }
+                    }
+                }
+                let mut remaining = deadline.map(|d| d - now)
+                                            .unwrap_or(i64::MAX as u64);
+                if cancel.is_some() {
+                    remaining = cmp::min(remaining, CANCEL_POLL_MS * 1_000_000);
+                }
+                let remaining = Duration::nanoseconds(remaining as i64);
+                state = cvar.wait_timeout(state, remaining).unwrap().0;
+            }
+        });
+
+        Watchdog { state: state }
+    }
+
+    /// Disarms the watchdog, returning why it went after the child if it
+    /// already did.
+    pub fn finish(self) -> Option<Fired> {
+        let &(ref lock, ref cvar) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        state.done = true;
+        cvar.notify_one();
+        state.fired
+    }
+}
+
+/// Waits for `child` to exit without reaping it, then stops the watchdog and
+/// the signal forwarder which might otherwise still signal it by pid,
+/// returning why the watchdog went after the child if it did.
+///
+/// An exited child which hasn't been reaped stays around as a zombie, so its
+/// pid and process group can't be reused before the caller goes on to reap
+/// it. That keeps a late timeout from killing an unrelated process which was
+/// given the same pid.
+pub fn disarm(child: &Child, watchdog: Option<Watchdog>,
+          forwarder: Option<imp::SignalForwarder>) -> Option<Fired> {
+    if watchdog.is_none() && forwarder.is_none() {
+        return None
+    }
+    let _ = retry_interrupted(|| imp::wait_exited(child));
+    drop(forwarder);
+    watchdog.and_then(|w| w.finish())
+}
+
+/// Calls a `progress_tick` callback on its own thread at a fixed interval,
+/// until it's dropped.
+///
+/// The callback runs with the lock held, so none is still running or about
+/// to be started once the ticker has been dropped.
+pub struct Ticker {
+    done: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl Ticker {
+    pub fn start(cb: Arc<Box<Fn(Duration) + Send + Sync>>, interval: Duration)
+             -> Ticker {
+        let done = Arc::new((Mutex::new(false), Condvar::new()));
+        let done2 = done.clone();
+        let start = time::precise_time_ns();
+        thread::spawn(move || {
+            let &(ref lock, ref cvar) = &*done2;
+            let mut done = lock.lock().unwrap();
+            let mut next = interval;
+            while !*done {
+                let elapsed = elapsed_since(start);
+                if elapsed < next {
+                    done = cvar.wait_timeout(done, next - elapsed).unwrap().0;
+                    continue
+                }
+                cb(elapsed);
+                // Ticks which were missed because the callback took too long
+                // are skipped rather than made up for.
+                while next <= elapsed_since(start) {
+                    next = next + interval;
+                }
+            }
+        });
+        Ticker { done: done }
+    }
+}
+
+impl Drop for Ticker {
+    fn drop(&mut self) {
+        let &(ref lock, ref cvar) = &*self.done;
+        *lock.lock().unwrap() = true;
+        cvar.notify_one();
+    }
+}
+
+/// Keeps track of how many lines of a stream `throttle_lines` lets through in
+/// the current second.
+pub struct Throttle {
+    max: Option<u32>,
+    window_start: u64,
+    passed: u32,
+    suppressed: usize,
+}
+
+impl Throttle {
+    pub fn new(max: Option<u32>) -> Throttle {
+        Throttle {
+            max: max,
+            window_start: time::precise_time_ns(),
+            passed: 0,
+            suppressed: 0,
+        }
+    }
+
+    /// Counts another line, returning how many lines were suppressed if that
+    /// has to be reported now, and whether the line itself is let through.
+    pub fn admit(&mut self) -> (Option<usize>, bool) {
+        let max = match self.max {
+            Some(max) => max,
+            None => return (None, true),
+        };
+        let mut report = None;
+        if elapsed_since(self.window_start) >= Duration::seconds(1) {
+            report = self.finish();
+            self.window_start = time::precise_time_ns();
+            self.passed = 0;
+        }
+        if self.passed < max {
+            self.passed += 1;
+            (report, true)
+        } else {
+            self.suppressed += 1;
+            (report, false)
+        }
+    }
+
+    /// Returns how many lines were suppressed and not yet reported, if any.
+    pub fn finish(&mut self) -> Option<usize> {
+        match mem::replace(&mut self.suppressed, 0) {
+            0 => None,
+            n => Some(n),
+        }
+    }
+}
+
+/// Blocks the current thread for at least `dur`.
+pub fn sleep(dur: Duration) {
+    let start = time::precise_time_ns();
+    loop {
+        let elapsed = elapsed_since(start);
+        if elapsed >= dur { break }
+        // Parking can wake up spuriously, so keep going until enough time
+        // has actually passed.
+        thread::park_timeout(dur - elapsed);
+    }
+}
+
+pub fn elapsed_since(start: u64) -> Duration {
+    Duration::nanoseconds((time::precise_time_ns() - start) as i64)
+}
+
+pub fn format_duration(dur: Duration) -> String {
+    let ms = dur.num_milliseconds();
+    format!("{}.{:03}s", ms / 1000, ms % 1000)
+}
+
+/// Waits for `child` to exit for at most `dur`.
+///
+/// Returns `Ok(None)` if the child is still running once `dur` has passed, in
+/// which case it's left untouched so the caller can kill it and wait again.
+pub fn wait_timeout(child: &mut Child, dur: Duration)
+                    -> io::Result<Option<ExitStatus>> {
+    let start = time::precise_time_ns();
+    loop {
+        if let Some(status) = try!(child.try_wait()) {
+            return Ok(Some(status))
+        }
+        let elapsed = elapsed_since(start);
+        if elapsed >= dur { return Ok(None) }
+        sleep(cmp::min(dur - elapsed, Duration::milliseconds(WAIT_POLL_MS)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use util::process::tests::shell;
+    use super::wait_timeout;
+
+    #[test]
+    fn wait_timeout_returns_status_or_none() {
+        use std::time::Duration;
+        use time;
+
+        let mut child = shell("exit 3").build_command().spawn().unwrap();
+        let status = wait_timeout(&mut child, Duration::seconds(10)).unwrap();
+        assert_eq!(status.and_then(|s| s.code()), Some(3));
+
+        let mut child = shell("sleep 10").build_command().spawn().unwrap();
+        let start = time::precise_time_ns();
+        let status = wait_timeout(&mut child, Duration::milliseconds(200));
+        assert!(status.unwrap().is_none());
+        assert!(time::precise_time_ns() - start < 5_000_000_000);
+
+        // The child is still there to be killed and reaped.
+        child.kill().unwrap();
+        assert!(!child.wait().unwrap().success());
+    }
+}
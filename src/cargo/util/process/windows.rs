@@ -0,0 +1,299 @@
+//! The parts of running processes which are specific to Windows.
+
+extern crate "kernel32-sys" as kernel32;
+extern crate winapi;
+
+use std::ascii::AsciiExt;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::iter::repeat;
+use std::mem;
+use std::os::windows::ffi::OsStrExt;
+use std::os::windows::process::ExitStatusExt;
+use std::path::Path;
+use std::process::ExitStatus;
+use std::ptr;
+
+use libc;
+
+use util::spawn::{self, Child, Command, Handle, Stdio};
+use super::ProcessBuilder;
+
+// Pseudo-terminals are only supported on Unix.
+pub fn attach_pty(_builder: &ProcessBuilder, _command: &mut Command)
+                  -> io::Result<Option<Handle>> {
+    Ok(None)
+}
+
+extern "system" {
+    fn CreatePipe(read: *mut winapi::HANDLE, write: *mut winapi::HANDLE,
+                  attributes: *mut libc::c_void, size: winapi::DWORD)
+                  -> winapi::BOOL;
+    fn DuplicateHandle(source_process: winapi::HANDLE,
+                       source: winapi::HANDLE,
+                       target_process: winapi::HANDLE,
+                       target: *mut winapi::HANDLE,
+                       access: winapi::DWORD, inherit: winapi::BOOL,
+                       options: winapi::DWORD) -> winapi::BOOL;
+}
+
+const DUPLICATE_SAME_ACCESS: winapi::DWORD = 0x2;
+
+/// Connects both the stdout and the stderr of `command` to a single new
+/// pipe if `builder` asks for them to be merged, returning the end to read
+/// the output from.
+pub fn merge_stderr(builder: &ProcessBuilder, command: &mut Command)
+                    -> io::Result<Option<Handle>> {
+    if !builder.redirect_stderr_to_stdout || builder.stdout_redirected() {
+        return Ok(None)
+    }
+    let (mut read, mut write) = (ptr::null_mut(), ptr::null_mut());
+    if unsafe { CreatePipe(&mut read, &mut write, ptr::null_mut(), 0) } == 0 {
+        return Err(io::Error::last_os_error())
+    }
+    let read = unsafe { Handle::new(read) };
+    let write = unsafe { Handle::new(write) };
+    command.stderr(try!(dup_raw(write.raw() as usize)));
+    command.stdout(Stdio::from_handle(write));
+    Ok(Some(read))
+}
+
+/// Duplicates `handle`, which is left open, into a stream for the child
+/// which isn't inherited by other processes.
+pub fn dup_raw(handle: usize) -> io::Result<Stdio> {
+    let mut copy = ptr::null_mut();
+    let r = unsafe {
+        let process = kernel32::GetCurrentProcess();
+        DuplicateHandle(process, handle as winapi::HANDLE, process,
+                        &mut copy, 0, winapi::FALSE, DUPLICATE_SAME_ACCESS)
+    };
+    if r == 0 {
+        return Err(io::Error::last_os_error())
+    }
+    Ok(Stdio::from_handle(unsafe { Handle::new(copy) }))
+}
+
+pub fn configure(builder: &ProcessBuilder, command: &mut Command) {
+    let mut flags = builder.creation_flags;
+    if builder.new_process_group {
+        flags |= winapi::CREATE_NEW_PROCESS_GROUP;
+    }
+    if builder.detach {
+        flags |= winapi::DETACHED_PROCESS;
+    }
+    if flags != 0 {
+        command.creation_flags(flags);
+    }
+}
+
+pub fn success_status() -> ExitStatus {
+    ExitStatus::from_raw(0)
+}
+
+pub fn is_executable(path: &Path, _metadata: &fs::Metadata) -> bool {
+    let ext = path.extension().and_then(|ext| ext.to_str());
+    match ext.map(|ext| ext.to_ascii_lowercase()) {
+        Some(ref ext) => ["exe", "com", "bat", "cmd"].contains(&&ext[..]),
+        None => false,
+    }
+}
+
+/// Converts `bytes` to an `OsString`, which they can only be if they're
+/// valid UTF-8.
+pub fn os_string_from_bytes(bytes: Vec<u8>) -> Option<OsString> {
+    String::from_utf8(bytes).ok().map(|s| OsString::from_str(&s))
+}
+
+extern "system" {
+    fn MultiByteToWideChar(code_page: winapi::UINT, flags: winapi::DWORD,
+                           input: *const libc::c_char, input_len: libc::c_int,
+                           output: *mut u16, output_len: libc::c_int)
+                           -> libc::c_int;
+}
+
+const CP_ACP: winapi::UINT = 0;
+const CP_OEMCP: winapi::UINT = 1;
+const MB_ERR_INVALID_CHARS: winapi::DWORD = 0x8;
+
+/// Decodes `bytes` from the active OEM or ANSI code page, failing on
+/// anything which isn't valid in it.
+pub fn decode_code_page(bytes: &[u8], oem: bool) -> Option<String> {
+    if bytes.is_empty() { return Some(String::new()) }
+    let page = if oem {CP_OEMCP} else {CP_ACP};
+    let input = bytes.as_ptr() as *const libc::c_char;
+    let input_len = bytes.len() as libc::c_int;
+    unsafe {
+        let len = MultiByteToWideChar(page, MB_ERR_INVALID_CHARS, input,
+                                      input_len, ptr::null_mut(), 0);
+        if len <= 0 { return None }
+        let mut wide = repeat(0u16).take(len as usize).collect::<Vec<_>>();
+        let len = MultiByteToWideChar(page, MB_ERR_INVALID_CHARS, input,
+                                      input_len, wide.as_mut_ptr(), len);
+        if len <= 0 { return None }
+        String::from_utf16(&wide[..len as usize]).ok()
+    }
+}
+
+#[link(name = "advapi32")]
+extern "system" {
+    fn ConvertStringSecurityDescriptorToSecurityDescriptorW(
+        descriptor: *const u16, revision: winapi::DWORD,
+        security_descriptor: *mut *mut libc::c_void,
+        size: *mut winapi::DWORD) -> winapi::BOOL;
+}
+
+extern "system" {
+    fn CreateFileW(name: *const u16, access: winapi::DWORD,
+                   share: winapi::DWORD,
+                   attributes: *mut SecurityAttributes,
+                   disposition: winapi::DWORD, flags: winapi::DWORD,
+                   template: winapi::HANDLE) -> winapi::HANDLE;
+    fn LocalFree(mem: *mut libc::c_void) -> *mut libc::c_void;
+}
+
+#[repr(C)]
+struct SecurityAttributes {
+    length: winapi::DWORD,
+    descriptor: *mut libc::c_void,
+    inherit: winapi::BOOL,
+}
+
+const SDDL_REVISION_1: winapi::DWORD = 1;
+const GENERIC_WRITE: winapi::DWORD = 0x40000000;
+const CREATE_NEW: winapi::DWORD = 1;
+const FILE_ATTRIBUTE_NORMAL: winapi::DWORD = 0x80;
+
+/// Creates a new file at `path` which only its owner can access, failing
+/// if anything already exists there.
+pub fn create_private(path: &Path) -> io::Result<Handle> {
+    // A protected DACL, which doesn't inherit entries from the directory,
+    // granting the owner full access and nobody else anything.
+    let sddl = to_wide(OsStr::from_str("D:P(A;;FA;;;OW)"));
+    let mut descriptor = ptr::null_mut();
+    let r = unsafe {
+        ConvertStringSecurityDescriptorToSecurityDescriptorW(
+            sddl.as_ptr(), SDDL_REVISION_1, &mut descriptor, ptr::null_mut())
+    };
+    if r == 0 {
+        return Err(io::Error::last_os_error())
+    }
+    let mut attributes = SecurityAttributes {
+        length: mem::size_of::<SecurityAttributes>() as winapi::DWORD,
+        descriptor: descriptor,
+        inherit: winapi::FALSE,
+    };
+    let path = to_wide(path.as_os_str());
+    let (handle, err) = unsafe {
+        let handle = CreateFileW(path.as_ptr(), GENERIC_WRITE, 0,
+                                 &mut attributes, CREATE_NEW,
+                                 FILE_ATTRIBUTE_NORMAL, ptr::null_mut());
+        let err = io::Error::last_os_error();
+        LocalFree(descriptor);
+        (handle, err)
+    };
+    if handle == winapi::INVALID_HANDLE_VALUE {
+        return Err(err)
+    }
+    Ok(unsafe { Handle::new(handle) })
+}
+
+fn to_wide(s: &OsStr) -> Vec<u16> {
+    let mut wide = s.encode_wide().collect::<Vec<u16>>();
+    wide.push(0);
+    wide
+}
+
+pub fn current_pid() -> u32 {
+    unsafe { kernel32::GetCurrentProcessId() }
+}
+
+/// Joins `argv` into the command line `CreateProcess` is given for it.
+pub fn command_line(argv: &[OsString]) -> OsString {
+    spawn::command_line(argv)
+}
+
+// There are no signals to send instead of terminating the process.
+pub fn timeout_signal(_builder: &ProcessBuilder) -> Option<i32> { None }
+
+pub fn exceeded_cpu_time(_builder: &ProcessBuilder, _status: &ExitStatus)
+                         -> bool {
+    false
+}
+
+pub fn exceeded_file_size(_builder: &ProcessBuilder, _status: &ExitStatus)
+                          -> bool {
+    false
+}
+
+// Only consoles have a mode, anything else is a pipe or a file.
+pub fn stdout_is_tty() -> bool {
+    unsafe {
+        let handle = kernel32::GetStdHandle(winapi::winbase::STD_OUTPUT_HANDLE);
+        let mut mode = 0;
+        kernel32::GetConsoleMode(handle, &mut mode) != 0
+    }
+}
+
+pub fn too_many_open_files(err: &io::Error) -> bool {
+    err.raw_os_error() == Some(winapi::ERROR_TOO_MANY_OPEN_FILES as i32)
+}
+
+// Handles aren't limited the way file descriptors are on Unix.
+pub fn open_files_limit() -> Option<u64> { None }
+
+pub const ARG_LENGTH_UNIT: &'static str = "characters";
+
+/// The longest command line `CreateProcess` accepts, in UTF-16 units
+/// including the terminator, less `QUOTING_MARGIN`. The environment is
+/// limited separately.
+pub fn arg_max() -> Option<usize> { Some(32768 - QUOTING_MARGIN) }
+
+/// Room left for the program to be resolved to its full path, which may
+/// then need quoting, on its way to `CreateProcess`.
+const QUOTING_MARGIN: usize = 262;
+
+pub fn arg_length(builder: &ProcessBuilder) -> usize {
+    command_line(&builder.get_argv()).encode_wide().count() + 1
+}
+
+pub fn signal(pid: u32, group: bool, _sig: i32) {
+    kill(pid, group)
+}
+
+/// Signals are only forwarded on Unix.
+pub struct SignalForwarder;
+
+pub fn forward_signals(_builder: &ProcessBuilder, _child: &Child)
+                       -> Option<SignalForwarder> {
+    None
+}
+
+pub fn kill(pid: u32, _group: bool) {
+    unsafe {
+        let handle = kernel32::OpenProcess(winapi::PROCESS_TERMINATE,
+                                           winapi::FALSE, pid);
+        if !handle.is_null() {
+            kernel32::TerminateProcess(handle, 1);
+            kernel32::CloseHandle(handle);
+        }
+    }
+}
+
+// The handle `Child` holds on to keeps the pid from being reused, so
+// waiting through a second handle leaves nothing for the caller to reap.
+pub fn wait_exited(child: &Child) -> io::Result<()> {
+    unsafe {
+        let handle = kernel32::OpenProcess(winapi::SYNCHRONIZE,
+                                           winapi::FALSE, child.id());
+        if handle.is_null() {
+            return Err(io::Error::last_os_error())
+        }
+        let r = kernel32::WaitForSingleObject(handle, winapi::INFINITE);
+        kernel32::CloseHandle(handle);
+        if r != winapi::WAIT_OBJECT_0 {
+            return Err(io::Error::last_os_error())
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,1173 @@
+//! Spawning of child processes.
+//!
+//! This follows the interface of `std::process`, adding the parts of it
+//! which `ProcessBuilder` needs but the standard library doesn't offer yet:
+//! running code in the child before it execs, setting `argv[0]` and the
+//! identity of the child on Unix, passing creation flags on Windows, handing
+//! an open file or descriptor to the child as its stdio, and checking whether
+//! a child has exited without blocking.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::{OsStr, OsString, AsOsStr};
+use std::fs::File;
+use std::io::prelude::*;
+use std::io;
+use std::process::{ExitStatus, Output};
+use std::thread;
+
+#[cfg(unix)]
+pub use self::imp::FileDesc;
+#[cfg(windows)]
+pub use self::imp::Handle;
+#[cfg(windows)]
+pub use self::imp::command_line;
+
+#[cfg(unix)]
+use self::imp::FileDesc as Desc;
+#[cfg(windows)]
+use self::imp::Handle as Desc;
+
+/// A process which is yet to be spawned, along with how to spawn it.
+pub struct Command {
+    program: OsString,
+    args: Vec<OsString>,
+    // `None` until the environment is changed, so the child inherits the
+    // one of cargo as it is when spawned. Entries are keyed by the name they
+    // are looked up under, which ignores case on Windows.
+    env: Option<BTreeMap<OsString, (OsString, OsString)>>,
+    cwd: Option<OsString>,
+    stdin: Option<Stdio>,
+    stdout: Option<Stdio>,
+    stderr: Option<Stdio>,
+    #[cfg(unix)]
+    arg0: Option<OsString>,
+    #[cfg(unix)]
+    uid: Option<u32>,
+    #[cfg(unix)]
+    gid: Option<u32>,
+    #[cfg(unix)]
+    closures: Vec<Box<FnMut() -> io::Result<()> + Send + Sync>>,
+    #[cfg(windows)]
+    flags: u32,
+}
+
+/// Where one of the standard streams of a child is connected to.
+pub struct Stdio(StdioImp);
+
+enum StdioImp {
+    Inherit,
+    Piped,
+    Null,
+    File(File),
+    Desc(Desc),
+}
+
+/// A spawned child process. The process keeps running when this is dropped.
+pub struct Child {
+    handle: imp::Process,
+    status: Option<ExitStatus>,
+
+    /// The end of the pipe connected to the stdin of the child, if piped.
+    pub stdin: Option<ChildStdin>,
+    /// The end of the pipe connected to the stdout of the child, if piped.
+    pub stdout: Option<ChildStdout>,
+    /// The end of the pipe connected to the stderr of the child, if piped.
+    pub stderr: Option<ChildStderr>,
+}
+
+/// The stdin of a child, closed when dropped.
+pub struct ChildStdin {
+    inner: Desc,
+}
+
+/// The stdout of a child.
+pub struct ChildStdout {
+    inner: Desc,
+}
+
+/// The stderr of a child.
+pub struct ChildStderr {
+    inner: Desc,
+}
+
+impl Command {
+    /// Creates a command running `program`, with no arguments, the
+    /// environment and working directory of cargo, and inheriting its stdio
+    /// unless captured.
+    pub fn new<S: AsOsStr + ?Sized>(program: &S) -> Command {
+        Command {
+            program: program.as_os_str().to_os_string(),
+            args: Vec::new(),
+            env: None,
+            cwd: None,
+            stdin: None,
+            stdout: None,
+            stderr: None,
+            #[cfg(unix)]
+            arg0: None,
+            #[cfg(unix)]
+            uid: None,
+            #[cfg(unix)]
+            gid: None,
+            #[cfg(unix)]
+            closures: Vec::new(),
+            #[cfg(windows)]
+            flags: 0,
+        }
+    }
+
+    /// Adds an argument to pass to the program.
+    pub fn arg<S: AsOsStr + ?Sized>(&mut self, arg: &S) -> &mut Command {
+        self.args.push(arg.as_os_str().to_os_string());
+        self
+    }
+
+    /// Adds several arguments to pass to the program.
+    pub fn args<S: AsOsStr>(&mut self, args: &[S]) -> &mut Command {
+        self.args.extend(args.iter().map(|arg| {
+            arg.as_os_str().to_os_string()
+        }));
+        self
+    }
+
+    /// Sets the environment variable `key` of the child to `val`.
+    pub fn env<K: ?Sized, V: ?Sized>(&mut self, key: &K, val: &V)
+                                     -> &mut Command
+        where K: AsOsStr, V: AsOsStr
+    {
+        let key = key.as_os_str().to_os_string();
+        let val = val.as_os_str().to_os_string();
+        self.env_mut().insert(imp::env_key(&key), (key, val));
+        self
+    }
+
+    /// Removes the environment variable `key` from the child, whether
+    /// inherited or set.
+    pub fn env_remove<K: AsOsStr + ?Sized>(&mut self, key: &K) -> &mut Command {
+        self.env_mut().remove(&imp::env_key(key.as_os_str()));
+        self
+    }
+
+    /// Clears the environment of the child, including what it inherits.
+    pub fn env_clear(&mut self) -> &mut Command {
+        self.env = Some(BTreeMap::new());
+        self
+    }
+
+    fn env_mut(&mut self) -> &mut BTreeMap<OsString, (OsString, OsString)> {
+        if self.env.is_none() {
+            let mut map = BTreeMap::new();
+            for (key, val) in env::vars_os() {
+                map.insert(imp::env_key(&key), (key, val));
+            }
+            self.env = Some(map);
+        }
+        self.env.as_mut().unwrap()
+    }
+
+    /// Returns the value the environment variable `key` has in the child.
+    fn get_env(&self, key: &str) -> Option<OsString> {
+        match self.env {
+            Some(ref env) => {
+                env.get(&imp::env_key(OsStr::from_str(key))).map(|&(_, ref v)| {
+                    v.clone()
+                })
+            }
+            None => env::var_os(key),
+        }
+    }
+
+    /// Sets the working directory of the child.
+    pub fn current_dir<P: AsOsStr + ?Sized>(&mut self, dir: &P) -> &mut Command {
+        self.cwd = Some(dir.as_os_str().to_os_string());
+        self
+    }
+
+    /// Sets what the stdin of the child is connected to.
+    pub fn stdin(&mut self, cfg: Stdio) -> &mut Command {
+        self.stdin = Some(cfg);
+        self
+    }
+
+    /// Sets what the stdout of the child is connected to.
+    pub fn stdout(&mut self, cfg: Stdio) -> &mut Command {
+        self.stdout = Some(cfg);
+        self
+    }
+
+    /// Sets what the stderr of the child is connected to.
+    pub fn stderr(&mut self, cfg: Stdio) -> &mut Command {
+        self.stderr = Some(cfg);
+        self
+    }
+
+    /// Sets the user the child runs as.
+    #[cfg(unix)]
+    pub fn uid(&mut self, id: u32) -> &mut Command {
+        self.uid = Some(id);
+        self
+    }
+
+    /// Sets the group the child runs as.
+    #[cfg(unix)]
+    pub fn gid(&mut self, id: u32) -> &mut Command {
+        self.gid = Some(id);
+        self
+    }
+
+    /// Sets the `argv[0]` the program sees, which is the program itself by
+    /// default.
+    #[cfg(unix)]
+    pub fn arg0<S: AsOsStr + ?Sized>(&mut self, arg: &S) -> &mut Command {
+        self.arg0 = Some(arg.as_os_str().to_os_string());
+        self
+    }
+
+    /// Runs `f` in the child once its stdio, identity and working directory
+    /// have been set up, right before it execs the program. An error stops
+    /// the program from running, and is returned from `spawn`.
+    ///
+    /// Closures run in the order they were added. This is unsafe because
+    /// the child is a copy of a process which may have had other threads
+    /// running, so `f` must only do things which are async-signal-safe: it
+    /// can't allocate, or take any lock which another thread may have held.
+    #[cfg(unix)]
+    pub unsafe fn pre_exec<F>(&mut self, f: F) -> &mut Command
+        where F: FnMut() -> io::Result<()> + Send + Sync + 'static
+    {
+        self.closures.push(Box::new(f));
+        self
+    }
+
+    /// Sets the flags the child is created with by `CreateProcess`, besides
+    /// the one saying the environment is in UTF-16.
+    #[cfg(windows)]
+    pub fn creation_flags(&mut self, flags: u32) -> &mut Command {
+        self.flags = flags;
+        self
+    }
+
+    /// Spawns the child, which inherits any stdio that isn't configured.
+    pub fn spawn(&mut self) -> io::Result<Child> {
+        imp::spawn(self, &StdioImp::Inherit)
+    }
+
+    /// Runs the child to completion, collecting its output. Any stdio that
+    /// isn't configured is piped, giving the child an empty stdin.
+    pub fn output(&mut self) -> io::Result<Output> {
+        imp::spawn(self, &StdioImp::Piped).and_then(|child| {
+            child.wait_with_output()
+        })
+    }
+
+    /// Runs the child to completion, which inherits any stdio that isn't
+    /// configured.
+    pub fn status(&mut self) -> io::Result<ExitStatus> {
+        self.spawn().and_then(|mut child| child.wait())
+    }
+}
+
+impl Stdio {
+    /// Connects the stream to a new pipe, the other end of which ends up in
+    /// the `Child`.
+    pub fn piped() -> Stdio { Stdio(StdioImp::Piped) }
+
+    /// Has the child inherit the stream from cargo.
+    pub fn inherit() -> Stdio { Stdio(StdioImp::Inherit) }
+
+    /// Connects the stream to the null device.
+    pub fn null() -> Stdio { Stdio(StdioImp::Null) }
+
+    /// Connects the stream to `file`, which is closed once the `Command` is
+    /// dropped.
+    pub fn from(file: File) -> Stdio { Stdio(StdioImp::File(file)) }
+
+    /// Connects the stream to the descriptor `fd`, which is closed once the
+    /// `Command` is dropped.
+    #[cfg(unix)]
+    pub fn from_desc(fd: FileDesc) -> Stdio { Stdio(StdioImp::Desc(fd)) }
+
+    /// Connects the stream to `handle`, which is closed once the `Command` is
+    /// dropped.
+    #[cfg(windows)]
+    pub fn from_handle(handle: Handle) -> Stdio {
+        Stdio(StdioImp::Desc(handle))
+    }
+}
+
+impl Child {
+    /// Returns the OS assigned identifier of the child.
+    pub fn id(&self) -> u32 {
+        self.handle.id()
+    }
+
+    /// Forcibly terminates the child, unless it's already been waited for.
+    pub fn kill(&mut self) -> io::Result<()> {
+        if self.status.is_some() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "can't kill an exited process", None))
+        }
+        self.handle.kill()
+    }
+
+    /// Closes the stdin of the child if piped, and waits for it to exit.
+    pub fn wait(&mut self) -> io::Result<ExitStatus> {
+        drop(self.stdin.take());
+        if let Some(status) = self.status {
+            return Ok(status)
+        }
+        let status = try!(self.handle.wait());
+        self.status = Some(status);
+        Ok(status)
+    }
+
+    /// Returns the exit status of the child if it has exited, without
+    /// blocking otherwise.
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        if self.status.is_none() {
+            self.status = try!(self.handle.try_wait());
+        }
+        Ok(self.status)
+    }
+
+    /// Waits for the child to exit, collecting whatever it writes to stdout
+    /// and stderr if they're piped.
+    pub fn wait_with_output(mut self) -> io::Result<Output> {
+        drop(self.stdin.take());
+        let stderr = self.stderr.take();
+        let stderr = thread::spawn(move || read_all(stderr));
+        let stdout = read_all(self.stdout.take());
+        let stderr = stderr.join().unwrap_or_else(|_| {
+            Err(io::Error::new(io::ErrorKind::Other,
+                               "thread reading stderr panicked", None))
+        });
+        let status = try!(self.wait());
+        Ok(Output {
+            status: status,
+            stdout: try!(stdout),
+            stderr: try!(stderr),
+        })
+    }
+}
+
+fn read_all<R: Read>(pipe: Option<R>) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    if let Some(mut pipe) = pipe {
+        try!(pipe.read_to_end(&mut buf));
+    }
+    Ok(buf)
+}
+
+impl Write for ChildStdin {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+impl Read for ChildStdout {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Read for ChildStderr {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+fn new_child(handle: imp::Process, stdin: Option<Desc>, stdout: Option<Desc>,
+             stderr: Option<Desc>) -> Child {
+    Child {
+        handle: handle,
+        status: None,
+        stdin: stdin.map(|inner| ChildStdin { inner: inner }),
+        stdout: stdout.map(|inner| ChildStdout { inner: inner }),
+        stderr: stderr.map(|inner| ChildStderr { inner: inner }),
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::ffi::{CString, OsStr, OsString};
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::io::{AsRawFd, RawFd};
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::ExitStatus;
+    use std::ptr;
+
+    use libc;
+
+    use super::{Child, Command, StdioImp, new_child};
+
+    extern {
+        fn setgroups(size: libc::size_t, list: *const libc::gid_t) -> libc::c_int;
+        #[link_name = "signal"]
+        fn set_signal_handler(signum: libc::c_int,
+                              handler: libc::size_t) -> libc::size_t;
+        #[cfg(target_os = "linux")]
+        fn pipe2(fds: *mut libc::c_int, flags: libc::c_int) -> libc::c_int;
+    }
+
+    const SIG_DFL: libc::size_t = 0;
+
+    /// An owned file descriptor, which is closed when dropped.
+    #[derive(Debug)]
+    pub struct FileDesc {
+        fd: libc::c_int,
+    }
+
+    impl FileDesc {
+        /// Takes ownership of `fd`.
+        pub unsafe fn new(fd: libc::c_int) -> FileDesc {
+            FileDesc { fd: fd }
+        }
+
+        pub fn raw(&self) -> libc::c_int { self.fd }
+
+        /// Sets whether the descriptor is closed in processes spawned later.
+        pub fn set_cloexec(&self, set: bool) -> io::Result<()> {
+            unsafe { set_cloexec(self.fd, set) }
+        }
+    }
+
+    impl io::Read for FileDesc {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let ptr = buf.as_mut_ptr() as *mut libc::c_void;
+            let n = unsafe { libc::read(self.fd, ptr, buf.len() as libc::size_t) };
+            if n == -1 {
+                return Err(io::Error::last_os_error())
+            }
+            Ok(n as usize)
+        }
+    }
+
+    impl io::Write for FileDesc {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let ptr = buf.as_ptr() as *const libc::c_void;
+            let n = unsafe { libc::write(self.fd, ptr, buf.len() as libc::size_t) };
+            if n == -1 {
+                return Err(io::Error::last_os_error())
+            }
+            Ok(n as usize)
+        }
+
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    impl AsRawFd for FileDesc {
+        fn as_raw_fd(&self) -> RawFd { self.fd }
+    }
+
+    impl Drop for FileDesc {
+        fn drop(&mut self) {
+            unsafe { libc::close(self.fd); }
+        }
+    }
+
+    pub fn env_key(key: &OsStr) -> OsString {
+        key.to_os_string()
+    }
+
+    /// Creates a pipe, neither end of which is inherited by processes spawned
+    /// without asking for it.
+    pub fn pipe() -> io::Result<(FileDesc, FileDesc)> {
+        let mut fds = [0; 2];
+        try!(cvt(unsafe { raw_pipe(&mut fds) }));
+        let fds = unsafe { (FileDesc::new(fds[0]), FileDesc::new(fds[1])) };
+        try!(fds.0.set_cloexec(true));
+        try!(fds.1.set_cloexec(true));
+        Ok(fds)
+    }
+
+    // Other threads can't fork in between creating the pipe and setting the
+    // flags on Linux, where it can be done at once.
+    #[cfg(target_os = "linux")]
+    unsafe fn raw_pipe(fds: &mut [libc::c_int; 2]) -> libc::c_int {
+        pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    unsafe fn raw_pipe(fds: &mut [libc::c_int; 2]) -> libc::c_int {
+        libc::pipe(fds.as_mut_ptr())
+    }
+
+    unsafe fn set_cloexec(fd: libc::c_int, set: bool) -> io::Result<()> {
+        let flags = try!(cvt(libc::fcntl(fd, libc::F_GETFD)));
+        let flags = if set {
+            flags | libc::FD_CLOEXEC
+        } else {
+            flags & !libc::FD_CLOEXEC
+        };
+        try!(cvt(libc::fcntl(fd, libc::F_SETFD, flags)));
+        Ok(())
+    }
+
+    fn cvt(r: libc::c_int) -> io::Result<libc::c_int> {
+        if r == -1 {Err(io::Error::last_os_error())} else {Ok(r)}
+    }
+
+    fn cstring(s: &OsStr) -> io::Result<CString> {
+        CString::new(s.as_bytes()).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput,
+                           "nul byte found in an argument", None)
+        })
+    }
+
+    /// The descriptor a stream of the child is set up from, and whichever
+    /// descriptors have to stay open until the child has been spawned, or
+    /// are handed to the `Child`.
+    struct Setup {
+        fd: Option<RawFd>,
+        _child: Option<FileDesc>,
+        parent: Option<FileDesc>,
+    }
+
+    fn setup(stdio: &StdioImp, readable: bool) -> io::Result<Setup> {
+        Ok(match *stdio {
+            StdioImp::Inherit => Setup { fd: None, _child: None, parent: None },
+            StdioImp::Null => {
+                let path = CString::new(&b"/dev/null"[..]).unwrap();
+                let flags = if readable {libc::O_RDONLY} else {libc::O_WRONLY};
+                let fd = unsafe {
+                    try!(cvt(libc::open(path.as_ptr(), flags | libc::O_CLOEXEC, 0)))
+                };
+                Setup { fd: Some(fd), _child: Some(unsafe { FileDesc::new(fd) }),
+                        parent: None }
+            }
+            StdioImp::Piped => {
+                let (read, write) = try!(pipe());
+                let (child, parent) = if readable {
+                    (read, write)
+                } else {
+                    (write, read)
+                };
+                Setup { fd: Some(child.raw()), _child: Some(child),
+                        parent: Some(parent) }
+            }
+            StdioImp::File(ref file) => {
+                Setup { fd: Some(file.as_raw_fd()), _child: None, parent: None }
+            }
+            StdioImp::Desc(ref desc) => {
+                Setup { fd: Some(desc.raw()), _child: None, parent: None }
+            }
+        })
+    }
+
+    pub fn spawn(cmd: &mut Command, default: &StdioImp) -> io::Result<Child> {
+        // Everything the child needs is prepared up front, as it can't
+        // allocate once forked.
+        let arg0 = cmd.arg0.as_ref().unwrap_or(&cmd.program);
+        let mut argv = vec![try!(cstring(arg0))];
+        for arg in cmd.args.iter() {
+            argv.push(try!(cstring(arg)));
+        }
+        let mut argv_ptrs = argv.iter().map(|s| s.as_ptr()).collect::<Vec<_>>();
+        argv_ptrs.push(ptr::null());
+
+        // Programs which turn out to be scripts without a `#!` line are run
+        // by the shell, as `execvp` does. The script goes in the second slot.
+        let sh = CString::new(&b"/bin/sh"[..]).unwrap();
+        let mut sh_argv = vec![sh.as_ptr(), ptr::null()];
+        sh_argv.push_all(&argv_ptrs[1..]);
+
+        let envp = match cmd.env {
+            Some(ref env) => {
+                let mut vars = Vec::new();
+                for &(ref key, ref val) in env.values() {
+                    let mut var = key.as_bytes().to_vec();
+                    var.push(b'=');
+                    var.push_all(val.as_bytes());
+                    vars.push(try!(cstring(OsStr::from_bytes(&var))));
+                }
+                Some(vars)
+            }
+            None => None,
+        };
+        let envp_ptrs = envp.as_ref().map(|vars| {
+            let mut ptrs = vars.iter().map(|s| s.as_ptr()).collect::<Vec<_>>();
+            ptrs.push(ptr::null());
+            ptrs
+        });
+
+        let candidates = try!(candidates(cmd));
+        let cwd = match cmd.cwd {
+            Some(ref cwd) => Some(try!(cstring(cwd))),
+            None => None,
+        };
+
+        let stdin = try!(setup(cmd.stdin.as_ref().map(|s| &s.0).unwrap_or(default),
+                               true));
+        let stdout = try!(setup(cmd.stdout.as_ref().map(|s| &s.0).unwrap_or(default),
+                                false));
+        let stderr = try!(setup(cmd.stderr.as_ref().map(|s| &s.0).unwrap_or(default),
+                                false));
+        let fds = [stdin.fd, stdout.fd, stderr.fd];
+
+        // The child reports why it couldn't exec the program over this pipe,
+        // which is closed without a word once the exec succeeds.
+        let (mut err_read, err_write) = try!(pipe());
+
+        let pid = unsafe { libc::fork() };
+        if pid == -1 {
+            return Err(io::Error::last_os_error())
+        }
+        if pid == 0 {
+            unsafe {
+                let err = exec(cmd, &fds, cwd.as_ref(), &candidates, &argv_ptrs,
+                               envp_ptrs.as_ref().map(|e| &e[..]), &mut sh_argv);
+                let errno = err.raw_os_error().unwrap_or(libc::EINVAL);
+                let bytes = [(errno >> 24) as u8, (errno >> 16) as u8,
+                             (errno >> 8) as u8, errno as u8];
+                libc::write(err_write.raw(), bytes.as_ptr() as *const libc::c_void,
+                            4);
+                libc::_exit(127);
+            }
+        }
+        drop(err_write);
+
+        let mut bytes = [0u8; 4];
+        let n = loop {
+            match io::Read::read(&mut err_read, &mut bytes) {
+                Ok(n) => break n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        };
+        let process = Process { pid: pid };
+        if n == 0 {
+            return Ok(new_child(process, stdin.parent, stdout.parent,
+                                stderr.parent))
+        }
+
+        // The child exits right away, so it's reaped so as to not leave a
+        // zombie behind.
+        let _ = process.wait();
+        if n != bytes.len() {
+            return Err(io::Error::new(io::ErrorKind::Other,
+                                      "short read on the exec error pipe", None))
+        }
+        let errno = ((bytes[0] as i32) << 24) | ((bytes[1] as i32) << 16) |
+                    ((bytes[2] as i32) << 8) | (bytes[3] as i32);
+        Err(io::Error::from_raw_os_error(errno))
+    }
+
+    /// Returns the paths to try to exec the program of `cmd` at, searching
+    /// the `PATH` of the child for it like `execvp`.
+    fn candidates(cmd: &Command) -> io::Result<Vec<CString>> {
+        let program = cmd.program.as_bytes();
+        if program.contains(&b'/') {
+            return Ok(vec![try!(cstring(&cmd.program))])
+        }
+        let path = cmd.get_env("PATH").unwrap_or_else(|| {
+            OsString::from_str("/bin:/usr/bin")
+        });
+        let mut candidates = Vec::new();
+        for dir in path.as_bytes().split(|&b| b == b':') {
+            let dir = if dir.is_empty() {&b"."[..]} else {dir};
+            let mut candidate = dir.to_vec();
+            candidate.push(b'/');
+            candidate.push_all(program);
+            candidates.push(try!(cstring(OsStr::from_bytes(&candidate))));
+        }
+        Ok(candidates)
+    }
+
+    /// Turns the freshly forked child into the program, returning why it
+    /// couldn't. Only async-signal-safe functions may be called in here.
+    unsafe fn exec(cmd: &mut Command, fds: &[Option<RawFd>; 3],
+                   cwd: Option<&CString>, candidates: &[CString],
+                   argv: &[*const libc::c_char],
+                   envp: Option<&[*const libc::c_char]>,
+                   sh_argv: &mut [*const libc::c_char]) -> io::Error {
+        macro_rules! t {
+            ($e:expr) => (if $e == -1 { return io::Error::last_os_error() })
+        }
+
+        for (target, fd) in fds.iter().enumerate() {
+            let target = target as libc::c_int;
+            match *fd {
+                // Already in place, it only has to survive the exec.
+                Some(fd) if fd == target => {
+                    if let Err(e) = set_cloexec(fd, false) { return e }
+                }
+                Some(fd) => t!(libc::dup2(fd, target)),
+                None => {}
+            }
+        }
+        if let Some(gid) = cmd.gid {
+            t!(libc::setgid(gid as libc::gid_t));
+        }
+        if let Some(uid) = cmd.uid {
+            // Supplementary groups can only be dropped while still root, and
+            // if they can't the process just keeps them.
+            let _ = setgroups(0, ptr::null());
+            t!(libc::setuid(uid as libc::uid_t));
+        }
+        if let Some(cwd) = cwd {
+            t!(libc::chdir(cwd.as_ptr()));
+        }
+        // Cargo ignores SIGPIPE, which the program shouldn't inherit.
+        set_signal_handler(libc::SIGPIPE, SIG_DFL);
+        for f in cmd.closures.iter_mut() {
+            if let Err(e) = (*f)() { return e }
+        }
+
+        let mut errno = libc::ENOENT;
+        for candidate in candidates.iter() {
+            match envp {
+                Some(envp) => libc::execve(candidate.as_ptr(), argv.as_ptr(),
+                                           envp.as_ptr()),
+                None => libc::execv(candidate.as_ptr(), argv.as_ptr()),
+            };
+            let err = io::Error::last_os_error();
+            match err.raw_os_error() {
+                Some(libc::ENOEXEC) => {
+                    sh_argv[1] = candidate.as_ptr();
+                    match envp {
+                        Some(envp) => libc::execve(sh_argv[0], sh_argv.as_ptr(),
+                                                   envp.as_ptr()),
+                        None => libc::execv(sh_argv[0], sh_argv.as_ptr()),
+                    };
+                    return io::Error::last_os_error()
+                }
+                // The program may still be found further along the `PATH`,
+                // but report that it wasn't allowed to run if it isn't.
+                Some(libc::EACCES) => errno = libc::EACCES,
+                Some(libc::ENOENT) | Some(libc::ENOTDIR) => {}
+                _ => return err,
+            }
+        }
+        io::Error::from_raw_os_error(errno)
+    }
+
+    pub struct Process {
+        pid: libc::pid_t,
+    }
+
+    impl Process {
+        pub fn id(&self) -> u32 { self.pid as u32 }
+
+        pub fn kill(&self) -> io::Result<()> {
+            try!(cvt(unsafe { libc::kill(self.pid, libc::SIGKILL) }));
+            Ok(())
+        }
+
+        pub fn wait(&self) -> io::Result<ExitStatus> {
+            let mut status = 0;
+            try!(cvt(unsafe { libc::waitpid(self.pid, &mut status, 0) }));
+            Ok(ExitStatus::from_raw(status))
+        }
+
+        pub fn try_wait(&self) -> io::Result<Option<ExitStatus>> {
+            let mut status = 0;
+            let pid = try!(cvt(unsafe {
+                libc::waitpid(self.pid, &mut status, libc::WNOHANG)
+            }));
+            if pid == 0 {
+                Ok(None)
+            } else {
+                Ok(Some(ExitStatus::from_raw(status)))
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    extern crate "kernel32-sys" as kernel32;
+    extern crate winapi;
+
+    use std::ascii::AsciiExt;
+    use std::env;
+    use std::ffi::{OsStr, OsString};
+    use std::fs;
+    use std::io;
+    use std::iter::repeat;
+    use std::mem;
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use std::os::windows::io::AsRawHandle;
+    use std::os::windows::process::ExitStatusExt;
+    use std::path::Path;
+    use std::process::ExitStatus;
+    use std::ptr;
+    use std::sync::{StaticMutex, MUTEX_INIT};
+
+    use libc;
+
+    use super::{Child, Command, StdioImp, new_child};
+
+    extern "system" {
+        fn CreatePipe(read: *mut winapi::HANDLE, write: *mut winapi::HANDLE,
+                      attributes: *mut libc::c_void, size: winapi::DWORD)
+                      -> winapi::BOOL;
+        fn DuplicateHandle(source_process: winapi::HANDLE,
+                           source: winapi::HANDLE,
+                           target_process: winapi::HANDLE,
+                           target: *mut winapi::HANDLE,
+                           access: winapi::DWORD, inherit: winapi::BOOL,
+                           options: winapi::DWORD) -> winapi::BOOL;
+        fn CreateProcessW(application: *const u16, command_line: *mut u16,
+                          process_attributes: *mut libc::c_void,
+                          thread_attributes: *mut libc::c_void,
+                          inherit: winapi::BOOL, flags: winapi::DWORD,
+                          environment: *mut libc::c_void,
+                          current_dir: *const u16,
+                          startup_info: *mut winapi::STARTUPINFOW,
+                          process_info: *mut winapi::PROCESS_INFORMATION)
+                          -> winapi::BOOL;
+        fn GetExitCodeProcess(process: winapi::HANDLE, code: *mut winapi::DWORD)
+                              -> winapi::BOOL;
+        fn CreateFileW(name: *const u16, access: winapi::DWORD,
+                       share: winapi::DWORD, attributes: *mut libc::c_void,
+                       disposition: winapi::DWORD, flags: winapi::DWORD,
+                       template: winapi::HANDLE) -> winapi::HANDLE;
+        fn ReadFile(file: winapi::HANDLE, buf: *mut libc::c_void,
+                    len: winapi::DWORD, read: *mut winapi::DWORD,
+                    overlapped: *mut libc::c_void) -> winapi::BOOL;
+        fn WriteFile(file: winapi::HANDLE, buf: *const libc::c_void,
+                     len: winapi::DWORD, written: *mut winapi::DWORD,
+                     overlapped: *mut libc::c_void) -> winapi::BOOL;
+    }
+
+    const DUPLICATE_SAME_ACCESS: winapi::DWORD = 0x2;
+    const STARTF_USESTDHANDLES: winapi::DWORD = 0x100;
+    const CREATE_UNICODE_ENVIRONMENT: winapi::DWORD = 0x400;
+    const GENERIC_READ: winapi::DWORD = 0x80000000;
+    const GENERIC_WRITE: winapi::DWORD = 0x40000000;
+    const FILE_SHARE_READ: winapi::DWORD = 0x1;
+    const FILE_SHARE_WRITE: winapi::DWORD = 0x2;
+    const OPEN_EXISTING: winapi::DWORD = 3;
+    const WAIT_TIMEOUT: winapi::DWORD = 0x102;
+    const ERROR_BROKEN_PIPE: i32 = 109;
+
+    // Handles are made inheritable only while this is held, so that a child
+    // doesn't also inherit the stdio of another one spawned at the same time.
+    static SPAWN_LOCK: StaticMutex = MUTEX_INIT;
+
+    /// An owned handle, which is closed when dropped.
+    #[derive(Debug)]
+    pub struct Handle {
+        raw: winapi::HANDLE,
+    }
+
+    // Handles may be used from any thread.
+    unsafe impl Send for Handle {}
+    unsafe impl Sync for Handle {}
+
+    impl Handle {
+        /// Takes ownership of `raw`.
+        pub unsafe fn new(raw: winapi::HANDLE) -> Handle {
+            Handle { raw: raw }
+        }
+
+        pub fn raw(&self) -> winapi::HANDLE { self.raw }
+
+        /// Duplicates the handle, possibly into one which is inherited by
+        /// processes spawned from now on.
+        fn duplicate(raw: winapi::HANDLE, inherit: bool) -> io::Result<Handle> {
+            let mut copy = ptr::null_mut();
+            let inherit = if inherit {winapi::TRUE} else {winapi::FALSE};
+            let r = unsafe {
+                let process = kernel32::GetCurrentProcess();
+                DuplicateHandle(process, raw, process, &mut copy, 0, inherit,
+                                DUPLICATE_SAME_ACCESS)
+            };
+            if r == 0 {
+                return Err(io::Error::last_os_error())
+            }
+            Ok(unsafe { Handle::new(copy) })
+        }
+    }
+
+    impl io::Read for Handle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let mut read = 0;
+            let r = unsafe {
+                ReadFile(self.raw, buf.as_mut_ptr() as *mut libc::c_void,
+                         buf.len() as winapi::DWORD, &mut read, ptr::null_mut())
+            };
+            if r != 0 {
+                return Ok(read as usize)
+            }
+            // The other end of a pipe being closed is its end of file.
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(ERROR_BROKEN_PIPE) {
+                Ok(0)
+            } else {
+                Err(err)
+            }
+        }
+    }
+
+    impl io::Write for Handle {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let mut written = 0;
+            let r = unsafe {
+                WriteFile(self.raw, buf.as_ptr() as *const libc::c_void,
+                          buf.len() as winapi::DWORD, &mut written,
+                          ptr::null_mut())
+            };
+            if r == 0 {
+                return Err(io::Error::last_os_error())
+            }
+            Ok(written as usize)
+        }
+
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    impl Drop for Handle {
+        fn drop(&mut self) {
+            unsafe { kernel32::CloseHandle(self.raw); }
+        }
+    }
+
+    /// Environment variable names are case-insensitive, so they're looked
+    /// up by their uppercased name.
+    pub fn env_key(key: &OsStr) -> OsString {
+        let key = key.encode_wide().map(|c| {
+            if c < 0x80 {(c as u8 as char).to_ascii_uppercase() as u16} else {c}
+        }).collect::<Vec<u16>>();
+        OsString::from_wide(&key)
+    }
+
+    /// Creates a pipe, neither end of which is inherited.
+    pub fn pipe() -> io::Result<(Handle, Handle)> {
+        let (mut read, mut write) = (ptr::null_mut(), ptr::null_mut());
+        if unsafe { CreatePipe(&mut read, &mut write, ptr::null_mut(), 0) } == 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(unsafe { (Handle::new(read), Handle::new(write)) })
+    }
+
+    /// Returns the handle a stream of the child is given, and the end of the
+    /// pipe handed to the `Child` if it's piped.
+    fn setup(stdio: &StdioImp, which: winapi::DWORD, readable: bool)
+             -> io::Result<(Option<Handle>, Option<Handle>)> {
+        match *stdio {
+            StdioImp::Inherit => {
+                let raw = unsafe { kernel32::GetStdHandle(which) };
+                if raw.is_null() || raw == winapi::INVALID_HANDLE_VALUE {
+                    return Ok((None, None))
+                }
+                Ok((Some(try!(Handle::duplicate(raw, true))), None))
+            }
+            StdioImp::Null => {
+                let name = to_wide(OsStr::from_str("NUL"));
+                let access = if readable {GENERIC_READ} else {GENERIC_WRITE};
+                let raw = unsafe {
+                    CreateFileW(name.as_ptr(), access,
+                                FILE_SHARE_READ | FILE_SHARE_WRITE,
+                                ptr::null_mut(), OPEN_EXISTING, 0,
+                                ptr::null_mut())
+                };
+                if raw == winapi::INVALID_HANDLE_VALUE {
+                    return Err(io::Error::last_os_error())
+                }
+                let null = unsafe { Handle::new(raw) };
+                Ok((Some(try!(Handle::duplicate(null.raw(), true))), None))
+            }
+            StdioImp::Piped => {
+                let (read, write) = try!(pipe());
+                let (child, parent) = if readable {
+                    (read, write)
+                } else {
+                    (write, read)
+                };
+                Ok((Some(try!(Handle::duplicate(child.raw(), true))),
+                    Some(parent)))
+            }
+            StdioImp::File(ref file) => {
+                let raw = file.as_raw_handle() as winapi::HANDLE;
+                Ok((Some(try!(Handle::duplicate(raw, true))), None))
+            }
+            StdioImp::Desc(ref handle) => {
+                Ok((Some(try!(Handle::duplicate(handle.raw(), true))), None))
+            }
+        }
+    }
+
+    fn to_wide(s: &OsStr) -> Vec<u16> {
+        let mut wide = s.encode_wide().collect::<Vec<u16>>();
+        wide.push(0);
+        wide
+    }
+
+    pub fn spawn(cmd: &mut Command, default: &StdioImp) -> io::Result<Child> {
+        let program = cmd.get_env("PATH").and_then(|path| {
+            find_program(&cmd.program, &path)
+        });
+        let program = program.as_ref().map(|p| to_wide(p.as_os_str()));
+        let mut argv = vec![cmd.program.clone()];
+        argv.push_all(&cmd.args);
+        let mut line = to_wide(&command_line(&argv));
+        let mut env = cmd.env.as_ref().map(|env| {
+            let mut block = Vec::new();
+            for &(ref key, ref val) in env.values() {
+                block.extend(key.encode_wide());
+                block.push('=' as u16);
+                block.extend(val.encode_wide());
+                block.push(0);
+            }
+            // The block ends with an empty string, and can't be empty itself.
+            if block.is_empty() {
+                block.push(0);
+            }
+            block.push(0);
+            block
+        });
+        let cwd = cmd.cwd.as_ref().map(|cwd| to_wide(cwd));
+
+        let _lock = SPAWN_LOCK.lock();
+        let (stdin, parent_stdin) = try!(setup(
+            cmd.stdin.as_ref().map(|s| &s.0).unwrap_or(default),
+            winapi::winbase::STD_INPUT_HANDLE, true));
+        let (stdout, parent_stdout) = try!(setup(
+            cmd.stdout.as_ref().map(|s| &s.0).unwrap_or(default),
+            winapi::winbase::STD_OUTPUT_HANDLE, false));
+        let (stderr, parent_stderr) = try!(setup(
+            cmd.stderr.as_ref().map(|s| &s.0).unwrap_or(default),
+            winapi::winbase::STD_ERROR_HANDLE, false));
+
+        let mut si: winapi::STARTUPINFOW = unsafe { mem::zeroed() };
+        si.cb = mem::size_of::<winapi::STARTUPINFOW>() as winapi::DWORD;
+        si.dwFlags = STARTF_USESTDHANDLES;
+        si.hStdInput = stdin.as_ref().map(|h| h.raw()).unwrap_or(ptr::null_mut());
+        si.hStdOutput = stdout.as_ref().map(|h| h.raw()).unwrap_or(ptr::null_mut());
+        si.hStdError = stderr.as_ref().map(|h| h.raw()).unwrap_or(ptr::null_mut());
+        let mut pi: winapi::PROCESS_INFORMATION = unsafe { mem::zeroed() };
+
+        let program = program.as_ref().map(|p| p.as_ptr()).unwrap_or(ptr::null());
+        let env = env.as_mut().map(|e| e.as_mut_ptr() as *mut libc::c_void)
+                     .unwrap_or(ptr::null_mut());
+        let cwd = cwd.as_ref().map(|c| c.as_ptr()).unwrap_or(ptr::null());
+        let r = unsafe {
+            CreateProcessW(program, line.as_mut_ptr(), ptr::null_mut(),
+                           ptr::null_mut(), winapi::TRUE,
+                           cmd.flags | CREATE_UNICODE_ENVIRONMENT, env, cwd,
+                           &mut si, &mut pi)
+        };
+        if r == 0 {
+            return Err(io::Error::last_os_error())
+        }
+        unsafe { kernel32::CloseHandle(pi.hThread); }
+        let process = Process {
+            handle: unsafe { Handle::new(pi.hProcess) },
+            pid: pi.dwProcessId,
+        };
+        Ok(new_child(process, parent_stdin, parent_stdout, parent_stderr))
+    }
+
+    /// Looks for `program` in a `PATH` the child has been given, which
+    /// `CreateProcess` doesn't know about as it searches the one of cargo.
+    fn find_program(program: &OsStr, path: &OsStr) -> Option<OsString> {
+        if program.encode_wide().any(|c| c == '/' as u16 || c == '\\' as u16) {
+            return None
+        }
+        let with_exe = Path::new(program).extension().is_none();
+        for dir in env::split_paths(path) {
+            let candidate = dir.join(program);
+            if with_exe {
+                let exe = candidate.with_extension("exe");
+                if fs::metadata(&exe).is_ok() {
+                    return Some(exe.into_os_string())
+                }
+            }
+            if fs::metadata(&candidate).is_ok() {
+                return Some(candidate.into_os_string())
+            }
+        }
+        None
+    }
+
+    /// Joins `argv` into a command line which `CommandLineToArgvW` splits
+    /// into the same arguments again, working on UTF-16 so nothing is lost.
+    ///
+    /// Arguments containing whitespace or quotes are wrapped in double quotes,
+    /// inside of which quotes are escaped with a backslash and backslashes
+    /// preceding a quote are doubled.
+    pub fn command_line(argv: &[OsString]) -> OsString {
+        let (quote, backslash) = ('"' as u16, '\\' as u16);
+        let mut line = Vec::new();
+        for (i, arg) in argv.iter().enumerate() {
+            if i > 0 {
+                line.push(' ' as u16);
+            }
+            let arg = arg.encode_wide().collect::<Vec<u16>>();
+            let needs_quotes = arg.len() == 0 || arg.iter().any(|&c| {
+                c == ' ' as u16 || c == '\t' as u16 || c == '\n' as u16 ||
+                    c == quote
+            });
+            if !needs_quotes {
+                line.push_all(&arg);
+                continue
+            }
+
+            line.push(quote);
+            let mut backslashes = 0;
+            for &c in arg.iter() {
+                if c == backslash {
+                    backslashes += 1;
+                    continue
+                }
+                let escapes = if c == quote {backslashes * 2 + 1} else {backslashes};
+                line.extend(repeat(backslash).take(escapes));
+                line.push(c);
+                backslashes = 0;
+            }
+            line.extend(repeat(backslash).take(backslashes * 2));
+            line.push(quote);
+        }
+        OsString::from_wide(&line)
+    }
+
+    pub struct Process {
+        handle: Handle,
+        pid: winapi::DWORD,
+    }
+
+    impl Process {
+        pub fn id(&self) -> u32 { self.pid as u32 }
+
+        pub fn kill(&self) -> io::Result<()> {
+            if unsafe { kernel32::TerminateProcess(self.handle.raw(), 1) } == 0 {
+                return Err(io::Error::last_os_error())
+            }
+            Ok(())
+        }
+
+        pub fn wait(&self) -> io::Result<ExitStatus> {
+            let r = unsafe {
+                kernel32::WaitForSingleObject(self.handle.raw(), winapi::INFINITE)
+            };
+            if r != winapi::WAIT_OBJECT_0 {
+                return Err(io::Error::last_os_error())
+            }
+            self.exit_status()
+        }
+
+        pub fn try_wait(&self) -> io::Result<Option<ExitStatus>> {
+            let r = unsafe {
+                kernel32::WaitForSingleObject(self.handle.raw(), 0)
+            };
+            match r {
+                winapi::WAIT_OBJECT_0 => self.exit_status().map(Some),
+                WAIT_TIMEOUT => Ok(None),
+                _ => Err(io::Error::last_os_error()),
+            }
+        }
+
+        fn exit_status(&self) -> io::Result<ExitStatus> {
+            let mut code = 0;
+            if unsafe { GetExitCodeProcess(self.handle.raw(), &mut code) } == 0 {
+                return Err(io::Error::last_os_error())
+            }
+            Ok(ExitStatus::from_raw(code))
+        }
+    }
+}
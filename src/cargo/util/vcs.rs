@@ -19,11 +19,11 @@ impl GitRepo {
 
 impl HgRepo {
     pub fn init(path: &Path) -> CargoResult<HgRepo> {
-        try!(try!(process("hg")).arg("init").arg(path).exec());
+        try!(process("hg").arg("init").arg(path).exec());
         return Ok(HgRepo)
     }
     pub fn discover(path: &Path) -> CargoResult<HgRepo> {
-        try!(try!(process("hg")).arg("root").cwd(path).exec_with_output());
+        try!(process("hg").arg("root").cwd(path).exec_with_output());
         return Ok(HgRepo)
     }
 }
@@ -122,7 +122,7 @@ impl ProjectBuilder {
     }
 
     pub fn process<T: AsOsStr + ?Sized>(&self, program: &T) -> ProcessBuilder {
-        let mut p = process(program).unwrap();
+        let mut p = process(program);
         p.cwd(&self.root()).env("HOME", &paths::home());
         return p;
     }
@@ -58,7 +58,7 @@ test!(simple_cross {
                 execs().with_status(0));
     assert_that(&p.target_bin(target, "foo"), existing_file());
 
-    assert_that(process(&p.target_bin(target, "foo")).unwrap(),
+    assert_that(process(&p.target_bin(target, "foo")),
                 execs().with_status(0));
 });
 
@@ -94,7 +94,7 @@ test!(simple_deps {
                 execs().with_status(0));
     assert_that(&p.target_bin(target, "foo"), existing_file());
 
-    assert_that(process(&p.target_bin(target, "foo")).unwrap(),
+    assert_that(process(&p.target_bin(target, "foo")),
                 execs().with_status(0));
 });
 
@@ -170,7 +170,7 @@ test!(plugin_deps {
                 execs().with_status(0));
     assert_that(&foo.target_bin(target, "foo"), existing_file());
 
-    assert_that(process(&foo.target_bin(target, "foo")).unwrap(),
+    assert_that(process(&foo.target_bin(target, "foo")),
                 execs().with_status(0));
 });
 
@@ -254,7 +254,7 @@ test!(plugin_to_the_max {
                 execs().with_status(0));
     assert_that(&foo.target_bin(target, "foo"), existing_file());
 
-    assert_that(process(&foo.target_bin(target, "foo")).unwrap(),
+    assert_that(process(&foo.target_bin(target, "foo")),
                 execs().with_status(0));
 });
 